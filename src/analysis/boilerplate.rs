@@ -0,0 +1,39 @@
+use kerbalobjects::ksm::Instr;
+use kerbalobjects::Opcode;
+
+use crate::index::instr_opcode;
+
+/// Finds the number of instructions at the start of a MAIN section that make up the setup code
+/// the kOS compiler emits for every program
+///
+/// That is an optional label reset, the scope push for the file's scope, one push/swap/store
+/// triple for every program parameter, and the argument bottom check that ends parameter
+/// handling. Returns 0 if the section doesn't begin with it.
+pub fn detect_preamble(instructions: &[&Instr]) -> usize {
+    let opcodes: Vec<Opcode> = instructions
+        .iter()
+        .map(|instr| instr_opcode(instr))
+        .collect();
+
+    let mut i = 0;
+
+    if opcodes.get(i) == Some(&Opcode::Lbrt) {
+        i += 1;
+    }
+
+    if opcodes.get(i) != Some(&Opcode::Bscp) {
+        return 0;
+    }
+
+    i += 1;
+
+    while opcodes.get(i..i + 3) == Some(&[Opcode::Push, Opcode::Swap, Opcode::Stol]) {
+        i += 3;
+    }
+
+    if opcodes.get(i) == Some(&Opcode::Argb) {
+        i += 1;
+    }
+
+    i
+}
@@ -0,0 +1,106 @@
+use kerbalobjects::{KOSValue, Opcode};
+
+/// Where a function begins inside of a code section
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionStart {
+    /// The index of the label reset that begins the function
+    pub index: usize,
+    pub name: String,
+}
+
+/// Finds the functions that begin inside of a code section, including one that begins the section
+///
+/// A function begins with a label reset to the name of the function, instead of to a numbered
+/// label like @0042. The kOS compiler writes these as the name and the file it was defined in,
+/// separated by a backtick, which is left off. Any other name only counts if a scope push comes
+/// right after it, the same as the start of every function the compiler writes.
+pub fn find_function_starts(instrs: &[(Opcode, Option<&KOSValue>)]) -> Vec<FunctionStart> {
+    instrs
+        .iter()
+        .enumerate()
+        .filter_map(|(index, &(opcode, first_operand))| {
+            let label = match (opcode, first_operand) {
+                (Opcode::Lbrt, Some(KOSValue::String(s) | KOSValue::StringValue(s))) => s,
+                _ => return None,
+            };
+
+            if label.is_empty() || label.starts_with('@') {
+                return None;
+            }
+
+            let name = match label.split_once('`') {
+                Some((name, _)) => name,
+                None if instrs.get(index + 1).map(|(opcode, _)| *opcode) == Some(Opcode::Bscp) => {
+                    label
+                }
+                None => return None,
+            };
+
+            Some(FunctionStart {
+                index,
+                name: name.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> KOSValue {
+        KOSValue::String(String::from(s))
+    }
+
+    #[test]
+    fn finds_functions_named_by_the_compiler() {
+        let f = string("f`0:/lib.ks");
+        let g = string("g`0:/lib.ks");
+
+        let starts = find_function_starts(&[
+            (Opcode::Lbrt, Some(&f)),
+            (Opcode::Bscp, None),
+            (Opcode::Ret, None),
+            (Opcode::Lbrt, Some(&g)),
+            (Opcode::Push, None),
+        ]);
+
+        assert_eq!(
+            starts,
+            vec![
+                FunctionStart {
+                    index: 0,
+                    name: String::from("f"),
+                },
+                FunctionStart {
+                    index: 3,
+                    name: String::from("g"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn other_names_need_a_scope_push() {
+        let numbered = string("@0001");
+        let scoped = string("h");
+        let unscoped = string("loop");
+
+        let starts = find_function_starts(&[
+            (Opcode::Lbrt, Some(&numbered)),
+            (Opcode::Bscp, None),
+            (Opcode::Lbrt, Some(&scoped)),
+            (Opcode::Bscp, None),
+            (Opcode::Lbrt, Some(&unscoped)),
+            (Opcode::Push, None),
+        ]);
+
+        assert_eq!(
+            starts,
+            vec![FunctionStart {
+                index: 2,
+                name: String::from("h"),
+            }]
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+
+use kerbalobjects::Opcode;
+
+/// Whether an instruction refers to a function it calls, or pushes a delegate to one
+pub fn is_call_like(opcode: Opcode) -> bool {
+    matches!(opcode, Opcode::Call | Opcode::Pdrl | Opcode::Prl)
+}
+
+/// Something that can be called, which is either a function in the file or something outside of
+/// it, like a built in function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallNode {
+    pub name: String,
+    pub external: bool,
+}
+
+/// Which functions call or take delegates of which others, and how many times
+#[derive(Debug, Clone, Default)]
+pub struct CallGraph {
+    pub nodes: Vec<CallNode>,
+    /// The number of calls from one node to another, by their positions in nodes
+    pub edges: BTreeMap<(usize, usize), usize>,
+}
+
+impl CallGraph {
+    /// Finds a node by name, adding it if it isn't in the graph yet
+    pub fn node(&mut self, name: &str, external: bool) -> usize {
+        match self.nodes.iter().position(|node| node.name == name) {
+            Some(position) => position,
+            None => {
+                self.nodes.push(CallNode {
+                    name: name.to_string(),
+                    external,
+                });
+
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Records a call from one function to another
+    pub fn add_call(&mut self, caller: &str, callee: &str, external: bool) {
+        let from = self.node(caller, false);
+        let to = self.node(callee, external);
+
+        *self.edges.entry((from, to)).or_insert(0) += 1;
+    }
+}
+
+/// What made a function that was never given a name in the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnonymousKind {
+    /// The body of a when or on trigger, which is installed with addt
+    Trigger,
+    /// An anonymous function, which is pushed as a delegate
+    Function,
+}
+
+/// Whether the compiler made a function's label up, instead of taking it from the source
+pub fn is_opaque_name(name: &str) -> bool {
+    name.starts_with('@') || name == "FUNC"
+}
+
+/// Names an anonymous function after the source line that makes it, like trigger@line_42, or after
+/// its label if there is no debug information for that line
+pub fn anonymous_name(kind: AnonymousKind, line: Option<isize>, label: &str) -> String {
+    let prefix = match kind {
+        AnonymousKind::Trigger => "trigger",
+        AnonymousKind::Function => "anon",
+    };
+
+    match line {
+        Some(line) => format!("{}@line_{}", prefix, line),
+        None => format!("{}{}", prefix, label),
+    }
+}
@@ -0,0 +1,115 @@
+use kerbalobjects::{KOSValue, Opcode};
+
+/// What the control flow graph needs to know about an instruction
+#[derive(Debug, Clone, Copy)]
+pub struct FlowInstr {
+    pub opcode: Opcode,
+    /// For branches, how many instructions away the destination is, if it could be resolved
+    pub distance: Option<isize>,
+}
+
+impl FlowInstr {
+    /// Describes an instruction, taking the branch distance from its first operand's value
+    pub fn new(opcode: Opcode, first_operand: Option<&KOSValue>) -> Self {
+        let distance = if is_branch(opcode) {
+            first_operand.and_then(branch_distance)
+        } else {
+            None
+        };
+
+        FlowInstr { opcode, distance }
+    }
+}
+
+/// A run of instructions that is only ever entered at the top and left at the bottom
+#[derive(Debug, Clone)]
+pub struct BasicBlock {
+    /// The index of the first instruction in the block
+    pub start: usize,
+    /// The index one past the last instruction in the block
+    pub end: usize,
+    /// The indices of the blocks that control can flow to after this one
+    pub successors: Vec<usize>,
+}
+
+/// Whether an instruction is a jump, which are the only instructions with a destination in their own section
+pub fn is_branch(opcode: Opcode) -> bool {
+    matches!(opcode, Opcode::Jmp | Opcode::Bfa | Opcode::Btr)
+}
+
+fn ends_flow(opcode: Opcode) -> bool {
+    matches!(opcode, Opcode::Ret | Opcode::Eop)
+}
+
+/// Branches store how far away their destination is as an integer operand
+pub fn branch_distance(value: &KOSValue) -> Option<isize> {
+    match value {
+        KOSValue::Byte(b) => Some(*b as isize),
+        KOSValue::Int16(i) => Some(*i as isize),
+        KOSValue::Int32(i) | KOSValue::ScalarInt(i) => Some(*i as isize),
+        _ => None,
+    }
+}
+
+/// Splits the instructions of one code section into basic blocks, in order
+///
+/// Blocks begin at the first instruction, at every branch destination, and after every branch
+/// or return. Branches whose destination can't be resolved, or that point outside of the
+/// section, only get their fall through edge.
+pub fn build_cfg(instrs: &[FlowInstr]) -> Vec<BasicBlock> {
+    let target_of = |i: usize| {
+        instrs[i]
+            .distance
+            .and_then(|distance| i.checked_add_signed(distance))
+            .filter(|target| *target < instrs.len())
+    };
+
+    let mut leaders = vec![false; instrs.len()];
+
+    for (i, instr) in instrs.iter().enumerate() {
+        if i == 0 {
+            leaders[i] = true;
+        }
+
+        if is_branch(instr.opcode) || ends_flow(instr.opcode) {
+            if let Some(next) = leaders.get_mut(i + 1) {
+                *next = true;
+            }
+        }
+
+        if let Some(target) = target_of(i) {
+            leaders[target] = true;
+        }
+    }
+
+    let starts: Vec<usize> = (0..instrs.len()).filter(|i| leaders[*i]).collect();
+    let block_of = |instr_index: usize| starts.partition_point(|start| *start <= instr_index) - 1;
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(block_index, &start)| {
+            let end = starts.get(block_index + 1).copied().unwrap_or(instrs.len());
+            let last = end - 1;
+            let opcode = instrs[last].opcode;
+
+            let mut successors = Vec::new();
+
+            if let Some(target) = target_of(last) {
+                successors.push(block_of(target));
+            }
+
+            let falls_through = !ends_flow(opcode) && opcode != Opcode::Jmp && end < instrs.len();
+
+            if falls_through && !successors.contains(&(block_index + 1)) {
+                successors.push(block_index + 1);
+            }
+
+            BasicBlock {
+                start,
+                end,
+                successors,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,116 @@
+use kerbalobjects::ksm::sections::CodeType;
+use kerbalobjects::ksm::Instr;
+use kerbalobjects::{KOSValue, Opcode};
+
+use crate::index::IndexedKsm;
+
+use super::detect_preamble;
+
+/// One pattern that was found in a file, and how strongly it points to a toolchain
+#[derive(Debug, Clone)]
+pub struct Evidence {
+    pub toolchain: String,
+    pub pattern: String,
+    /// How likely the pattern is to appear only in files made by this toolchain, from 0 to 1
+    pub weight: f64,
+}
+
+/// The toolchain a file was most likely made by, along with everything that was found that
+/// points to a toolchain
+#[derive(Debug, Clone)]
+pub struct Attribution {
+    /// The name of the toolchain, or None if no patterns were found
+    pub toolchain: Option<String>,
+    /// The combined weight of every piece of evidence for the chosen toolchain, from 0 to 1
+    pub confidence: f64,
+    pub evidence: Vec<Evidence>,
+}
+
+const KOS_COMPILER: &str = "kOS compiler";
+
+/// Finds the toolchain that most likely produced a KSM file
+///
+/// Each piece of evidence is treated as independent, so the confidence for a toolchain is the
+/// chance that not all of its evidence is a coincidence.
+pub fn attribute_ksm(index: &IndexedKsm) -> Attribution {
+    let ksmfile = index.ksmfile();
+    let mut evidence = Vec::new();
+
+    match ksmfile.arg_section.arguments().next() {
+        Some(KOSValue::String(s)) if s.starts_with('@') => evidence.push(Evidence {
+            toolchain: String::from(KOS_COMPILER),
+            pattern: String::from("first argument is a label reset"),
+            weight: 0.5,
+        }),
+        // Other tools stamp their name into the first argument, which kOS never executes
+        Some(KOSValue::String(s)) if !s.contains('`') => evidence.push(Evidence {
+            toolchain: s.clone(),
+            pattern: format!("first argument names the toolchain: \"{}\"", s),
+            weight: 0.8,
+        }),
+        _ => {}
+    }
+
+    let backtick_functions = ksmfile
+        .code_sections()
+        .filter(|code_section| code_section.section_type == CodeType::Function)
+        .filter(|code_section| match code_section.instructions().next() {
+            Some(Instr::OneOp(Opcode::Lbrt, op1)) => matches!(
+                index.value(*op1),
+                Some(KOSValue::String(s) | KOSValue::StringValue(s)) if s.contains('`')
+            ),
+            _ => false,
+        })
+        .count();
+
+    if backtick_functions > 0 {
+        evidence.push(Evidence {
+            toolchain: String::from(KOS_COMPILER),
+            pattern: format!(
+                "{} function label(s) in the name`file form",
+                backtick_functions
+            ),
+            weight: 0.4,
+        });
+    }
+
+    let has_preamble = ksmfile
+        .code_sections()
+        .filter(|code_section| code_section.section_type == CodeType::Main)
+        .any(|code_section| {
+            let instructions: Vec<&Instr> = code_section.instructions().collect();
+
+            detect_preamble(&instructions) > 0
+        });
+
+    if has_preamble {
+        evidence.push(Evidence {
+            toolchain: String::from(KOS_COMPILER),
+            pattern: String::from("MAIN begins with the standard kOS preamble"),
+            weight: 0.3,
+        });
+    }
+
+    let mut toolchains: Vec<&str> = evidence.iter().map(|e| e.toolchain.as_str()).collect();
+    toolchains.sort_unstable();
+    toolchains.dedup();
+
+    let best = toolchains
+        .into_iter()
+        .map(|toolchain| (toolchain, confidence_for(&evidence, toolchain)))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b));
+
+    Attribution {
+        toolchain: best.map(|(toolchain, _)| toolchain.to_string()),
+        confidence: best.map(|(_, confidence)| confidence).unwrap_or(0.0),
+        evidence,
+    }
+}
+
+fn confidence_for(evidence: &[Evidence], toolchain: &str) -> f64 {
+    1.0 - evidence
+        .iter()
+        .filter(|e| e.toolchain == toolchain)
+        .map(|e| 1.0 - e.weight)
+        .product::<f64>()
+}
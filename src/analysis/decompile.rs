@@ -0,0 +1,668 @@
+use std::collections::{HashMap, HashSet};
+
+use kerbalobjects::{KOSValue, Opcode};
+
+use crate::cancel::{Deadline, StopReason};
+
+/// One instruction, along with what the decompiler needs to know about it that only the file can
+/// tell it
+#[derive(Debug, Clone)]
+pub struct LiftInstr<'a> {
+    pub opcode: Opcode,
+    pub operands: Vec<Option<&'a KOSValue>>,
+    pub label: String,
+    /// For branches, the position in the section of the instruction they go to
+    pub target: Option<usize>,
+    /// For calls and delegate pushes, the name of the function they go to, if it is in the file
+    pub callee: Option<String>,
+}
+
+/// Something on the kOS stack while a section is being lifted
+#[derive(Debug, Clone)]
+enum Item {
+    /// The argument marker, which is pushed below the arguments of every call
+    Marker,
+    Expr {
+        text: String,
+        /// Whether the expression can be used inside of another one without parentheses
+        atomic: bool,
+        /// The function and arguments, if this is a call, so that a call statement can be
+        /// written the way it would be in a script
+        call: Option<(String, Vec<String>)>,
+    },
+}
+
+impl Item {
+    fn atom(text: impl Into<String>) -> Self {
+        Item::Expr {
+            text: text.into(),
+            atomic: true,
+            call: None,
+        }
+    }
+
+    fn compound(text: String) -> Self {
+        Item::Expr {
+            text,
+            atomic: false,
+            call: None,
+        }
+    }
+
+    /// The expression as it is written on its own
+    fn text(&self) -> String {
+        match self {
+            Item::Marker => String::from("@"),
+            Item::Expr { text, .. } => text.clone(),
+        }
+    }
+
+    /// The expression as it is written inside of another one
+    fn operand(&self) -> String {
+        match self {
+            Item::Expr {
+                text,
+                atomic: false,
+                ..
+            } => format!("({})", text),
+            _ => self.text(),
+        }
+    }
+}
+
+/// How a conditional branch forward is written
+#[derive(Debug, Clone, Copy)]
+struct Opener {
+    keyword: &'static str,
+    /// Whether the condition is written with not in front of it
+    negate: bool,
+    /// Where the else part begins, for an if with an else
+    else_at: Option<usize>,
+    end: usize,
+}
+
+/// A block that has been opened and not yet closed
+#[derive(Debug, Clone, Copy)]
+struct OpenBlock {
+    else_at: Option<usize>,
+    end: usize,
+}
+
+/// The control flow that could be turned into blocks, worked out before anything is written
+#[derive(Debug, Default)]
+struct Structure {
+    openers: HashMap<usize, Opener>,
+    /// The jumps that are part of a block, which aren't written
+    consumed: HashSet<usize>,
+    /// Where the branches that couldn't be turned into blocks go, which get a label comment
+    labelled: HashSet<usize>,
+}
+
+/// Finds the branches that are ifs, ifs with elses, and until loops
+///
+/// kOS compiles an if to a bfa over its body, which ends in a jmp over the else if there is one,
+/// and an until loop to a btr out of the loop, whose body ends in a jmp back to the condition.
+/// Blocks have to nest, so a branch that would overlap the block it is in is left as it is.
+fn find_structure(instrs: &[LiftInstr]) -> Structure {
+    let mut structure = Structure::default();
+    let mut ends: Vec<usize> = Vec::new();
+
+    for (i, instr) in instrs.iter().enumerate() {
+        while ends.last().is_some_and(|end| *end <= i) {
+            ends.pop();
+        }
+
+        let Some(target) = instr.target else {
+            continue;
+        };
+
+        let conditional = matches!(instr.opcode, Opcode::Bfa | Opcode::Btr);
+
+        // A branch past the end of the section can't close a block anywhere
+        if !conditional || target <= i + 1 || target > instrs.len() {
+            if !structure.consumed.contains(&i) {
+                structure.labelled.insert(target);
+            }
+
+            continue;
+        }
+
+        let last = &instrs[target - 1];
+        let last_target = last.target.filter(|_| last.opcode == Opcode::Jmp);
+
+        let opener = match last_target {
+            Some(top) if top <= i => Opener {
+                keyword: "until",
+                negate: instr.opcode == Opcode::Bfa,
+                else_at: None,
+                end: target,
+            },
+            Some(end) if end > target && target - 1 > i => Opener {
+                keyword: "if",
+                negate: instr.opcode == Opcode::Btr,
+                else_at: Some(target),
+                end,
+            },
+            _ => Opener {
+                keyword: "if",
+                negate: instr.opcode == Opcode::Btr,
+                else_at: None,
+                end: target,
+            },
+        };
+
+        if ends.last().is_some_and(|end| opener.end > *end) || opener.end > instrs.len() {
+            structure.labelled.insert(target);
+            continue;
+        }
+
+        if last_target.is_some() && (opener.keyword == "until" || opener.else_at.is_some()) {
+            structure.consumed.insert(target - 1);
+        }
+
+        structure.openers.insert(i, opener);
+        ends.push(opener.end);
+    }
+
+    structure
+}
+
+/// Writes a value the way it would be written in a script, where strings that begin with $ are
+/// variable names
+fn value_text(value: &KOSValue) -> String {
+    match value {
+        KOSValue::Null => String::from("null"),
+        KOSValue::ArgMarker => String::from("@"),
+        KOSValue::Bool(b) | KOSValue::BoolValue(b) => b.to_string(),
+        KOSValue::Byte(b) => b.to_string(),
+        KOSValue::Int16(i) => i.to_string(),
+        KOSValue::Int32(i) | KOSValue::ScalarInt(i) => i.to_string(),
+        KOSValue::Float(f) => f.to_string(),
+        KOSValue::Double(d) | KOSValue::ScalarDouble(d) => d.to_string(),
+        KOSValue::String(s) | KOSValue::StringValue(s) => match s.strip_prefix('$') {
+            Some(name) => name.to_string(),
+            None => format!("{:?}", s),
+        },
+    }
+}
+
+/// The name an operand gives, like a variable, suffix, or function name, without the $ in front
+/// of variables or the () after built in functions
+fn name_text(value: Option<&KOSValue>) -> String {
+    match value {
+        Some(KOSValue::String(s) | KOSValue::StringValue(s)) => {
+            let name = s.strip_prefix('$').unwrap_or(s);
+            let name = name.strip_suffix("()").unwrap_or(name);
+
+            name.strip_suffix('*').unwrap_or(name).to_string()
+        }
+        Some(value) => value_text(value),
+        None => String::from("<?>"),
+    }
+}
+
+fn binary_operator(opcode: Opcode) -> Option<&'static str> {
+    Some(match opcode {
+        Opcode::Add => "+",
+        Opcode::Sub => "-",
+        Opcode::Mul => "*",
+        Opcode::Div => "/",
+        Opcode::Pow => "^",
+        Opcode::Cgt => ">",
+        Opcode::Clt => "<",
+        Opcode::Cgte => ">=",
+        Opcode::Clte => "<=",
+        Opcode::Ceq => "=",
+        Opcode::Cne => "<>",
+        Opcode::And => "and",
+        Opcode::Or => "or",
+        _ => return None,
+    })
+}
+
+/// Lifts the instructions of one section into statements
+struct Lifter<'a, 'b> {
+    instrs: &'b [LiftInstr<'a>],
+    lines: Vec<String>,
+    depth: usize,
+    stack: Vec<Item>,
+    /// Whether each open scope was merged into the block it begins, and so has no braces of its own
+    scopes: Vec<bool>,
+}
+
+impl Lifter<'_, '_> {
+    fn emit(&mut self, line: impl AsRef<str>) {
+        self.lines
+            .push(format!("{}{}", "    ".repeat(self.depth), line.as_ref()));
+    }
+
+    fn pop(&mut self) -> Item {
+        self.stack.pop().unwrap_or_else(|| Item::atom("<?>"))
+    }
+
+    /// Pops the arguments of a call, down to and including the argument marker
+    fn pop_args(&mut self) -> Vec<String> {
+        let mut args = Vec::new();
+
+        while let Some(item) = self.stack.pop() {
+            match item {
+                Item::Marker => break,
+                item => args.push(item.text()),
+            }
+        }
+
+        args.reverse();
+
+        args
+    }
+
+    /// Writes an instruction that couldn't be lifted as a comment, so that nothing is lost
+    fn emit_raw(&mut self, instr: &LiftInstr) {
+        let mnemonic: &str = instr.opcode.into();
+        let operands: Vec<String> = instr
+            .operands
+            .iter()
+            .map(|value| match value {
+                Some(value) => value_text(value),
+                None => String::from("<?>"),
+            })
+            .collect();
+
+        let target = instr
+            .target
+            .and_then(|target| self.instrs.get(target))
+            .map(|target| format!(" -> {}", target.label))
+            .unwrap_or_default();
+
+        self.emit(format!("// {} {}{}", mnemonic, operands.join(", "), target));
+    }
+
+    fn lift(&mut self, i: usize, instr: &LiftInstr, structure: &Structure) {
+        let first = instr.operands.first().copied().flatten();
+
+        if let Some(opener) = structure.openers.get(&i) {
+            let condition = self.pop();
+            let condition = if opener.negate {
+                format!("not {}", condition.operand())
+            } else {
+                condition.text()
+            };
+
+            self.emit(format!("{} {} {{", opener.keyword, condition));
+            self.depth += 1;
+
+            return;
+        }
+
+        if structure.consumed.contains(&i) {
+            return;
+        }
+
+        if let Some(operator) = binary_operator(instr.opcode) {
+            let b = self.pop();
+            let a = self.pop();
+
+            self.stack.push(Item::compound(format!(
+                "{} {} {}",
+                a.operand(),
+                operator,
+                b.operand()
+            )));
+
+            return;
+        }
+
+        match instr.opcode {
+            Opcode::Push => self.stack.push(match first {
+                Some(KOSValue::ArgMarker) => Item::Marker,
+                Some(value) => Item::atom(value_text(value)),
+                None => Item::atom("<?>"),
+            }),
+            Opcode::Pop => match self.stack.pop() {
+                Some(Item::Expr {
+                    call: Some((name, args)),
+                    ..
+                }) if name == "print" && args.len() == 1 => {
+                    self.emit(format!("print {}.", args[0]));
+                }
+                Some(item @ Item::Expr { .. }) => self.emit(format!("{}.", item.text())),
+                _ => {}
+            },
+            Opcode::Call => {
+                let name = match &instr.callee {
+                    Some(callee) => callee.clone(),
+                    None => name_text(first),
+                };
+
+                let args = self.pop_args();
+
+                // Calls with no destination take the function from below their arguments
+                let name = if name.is_empty() {
+                    self.pop().operand()
+                } else {
+                    name
+                };
+
+                self.stack.push(Item::Expr {
+                    text: format!("{}({})", name, args.join(", ")),
+                    atomic: true,
+                    call: Some((name, args)),
+                });
+            }
+            Opcode::Sto | Opcode::Stoe | Opcode::Stol | Opcode::Stog => {
+                let name = name_text(first);
+
+                match self.stack.pop() {
+                    Some(value) => {
+                        let statement = match instr.opcode {
+                            Opcode::Stol => format!("local {} is {}.", name, value.text()),
+                            Opcode::Stog => format!("global {} is {}.", name, value.text()),
+                            _ => format!("set {} to {}.", name, value.text()),
+                        };
+
+                        self.emit(statement);
+                    }
+                    // Nothing in this section pushed it, so the caller did
+                    None => self.emit(format!("parameter {}.", name)),
+                }
+            }
+            Opcode::Uns => {
+                let name = self.pop();
+
+                self.emit(format!("unset {}.", name.text()));
+            }
+            Opcode::Gmb | Opcode::Gmet => {
+                let object = self.pop();
+
+                self.stack.push(Item::atom(format!(
+                    "{}:{}",
+                    object.operand(),
+                    name_text(first)
+                )));
+            }
+            Opcode::Smb => {
+                let value = self.pop();
+                let object = self.pop();
+
+                self.emit(format!(
+                    "set {}:{} to {}.",
+                    object.operand(),
+                    name_text(first),
+                    value.text()
+                ));
+            }
+            Opcode::Gidx => {
+                let index = self.pop();
+                let object = self.pop();
+
+                self.stack.push(Item::atom(format!(
+                    "{}[{}]",
+                    object.operand(),
+                    index.text()
+                )));
+            }
+            Opcode::Sidx => {
+                let value = self.pop();
+                let index = self.pop();
+                let object = self.pop();
+
+                self.emit(format!(
+                    "set {}[{}] to {}.",
+                    object.operand(),
+                    index.text(),
+                    value.text()
+                ));
+            }
+            Opcode::Neg => {
+                let value = self.pop();
+
+                self.stack
+                    .push(Item::compound(format!("-{}", value.operand())));
+            }
+            Opcode::Not => {
+                let value = self.pop();
+
+                self.stack
+                    .push(Item::compound(format!("not {}", value.operand())));
+            }
+            // These only change how a value is held, not what it is
+            Opcode::Bool | Opcode::Eval => {}
+            Opcode::Dup => {
+                let top = self.pop();
+
+                self.stack.push(top.clone());
+                self.stack.push(top);
+            }
+            Opcode::Swap => {
+                let b = self.pop();
+                let a = self.pop();
+
+                self.stack.push(b);
+                self.stack.push(a);
+            }
+            Opcode::Ret => match self.stack.pop() {
+                Some(value @ Item::Expr { .. }) => self.emit(format!("return {}.", value.text())),
+                _ => self.emit("return."),
+            },
+            Opcode::Wait => {
+                let value = self.pop();
+
+                self.emit(format!("wait {}.", value.text()));
+            }
+            Opcode::Prl | Opcode::Pdrl | Opcode::Phdl => {
+                let name = match &instr.callee {
+                    Some(callee) => callee.clone(),
+                    None => name_text(first),
+                };
+
+                self.stack.push(Item::atom(format!("{}@", name)));
+            }
+            Opcode::Bscp => {
+                // The scope of a block's body is the block's own braces
+                let merged = self.lines.last().is_some_and(|line| line.ends_with('{'));
+
+                if !merged {
+                    self.emit("{");
+                    self.depth += 1;
+                }
+
+                self.scopes.push(merged);
+            }
+            Opcode::Escp => {
+                if self.scopes.pop() == Some(false) {
+                    self.depth = self.depth.saturating_sub(1);
+                    self.emit("}");
+                }
+            }
+            // None of these do anything a script would show
+            Opcode::Argb | Opcode::Lbrt | Opcode::Nop | Opcode::Eop => {}
+            Opcode::Bfa | Opcode::Btr => {
+                let condition = self.pop();
+                let target = instr
+                    .target
+                    .and_then(|target| self.instrs.get(target))
+                    .map(|target| target.label.as_str())
+                    .unwrap_or("<?>");
+                let when = if instr.opcode == Opcode::Bfa {
+                    "unless"
+                } else {
+                    "if"
+                };
+
+                self.emit(format!("// goto {} {} {}", target, when, condition.text()));
+            }
+            _ => self.emit_raw(instr),
+        }
+    }
+
+    /// Closes the blocks that end at, and begins the elses that begin at, an instruction
+    fn close_blocks(&mut self, i: usize, blocks: &mut Vec<OpenBlock>) {
+        while let Some(block) = blocks.last_mut() {
+            if block.else_at == Some(i) {
+                block.else_at = None;
+
+                self.depth = self.depth.saturating_sub(1);
+                self.emit("} else {");
+                self.depth += 1;
+            } else if block.end <= i {
+                blocks.pop();
+
+                self.depth = self.depth.saturating_sub(1);
+                self.emit("}");
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+/// Lifts the instructions of one code section into pseudo-kerboscript, indented by the given
+/// number of levels
+///
+/// Calls, assignments, suffixes, indexing, operators, scopes, ifs, and until loops are lifted.
+/// Anything else, including branches that don't form a block, is kept as a comment with the raw
+/// instruction, so that no instruction is ever left out. Lifting gives up once the deadline has
+/// passed.
+pub fn decompile(
+    instrs: &[LiftInstr],
+    depth: usize,
+    deadline: &Deadline,
+) -> Result<Vec<String>, StopReason> {
+    let structure = find_structure(instrs);
+
+    let mut lifter = Lifter {
+        instrs,
+        lines: Vec::new(),
+        depth,
+        stack: Vec::new(),
+        scopes: Vec::new(),
+    };
+    let mut blocks: Vec<OpenBlock> = Vec::new();
+
+    for (i, instr) in instrs.iter().enumerate() {
+        if let Some(reason) = deadline.check_at(i) {
+            return Err(reason);
+        }
+
+        lifter.close_blocks(i, &mut blocks);
+
+        if structure.labelled.contains(&i) {
+            lifter.emit(format!("// {}:", instr.label));
+        }
+
+        lifter.lift(i, instr, &structure);
+
+        if let Some(opener) = structure.openers.get(&i) {
+            blocks.push(OpenBlock {
+                else_at: opener.else_at,
+                end: opener.end,
+            });
+        }
+    }
+
+    lifter.close_blocks(instrs.len(), &mut blocks);
+
+    Ok(lifter.lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instr<'a>(
+        opcode: Opcode,
+        operand: Option<&'a KOSValue>,
+        target: Option<usize>,
+    ) -> LiftInstr<'a> {
+        LiftInstr {
+            opcode,
+            operands: operand.into_iter().map(Some).collect(),
+            label: String::new(),
+            target,
+            callee: None,
+        }
+    }
+
+    #[test]
+    fn lifts_if_else() {
+        let x = KOSValue::String(String::from("$x"));
+        let y = KOSValue::String(String::from("$y"));
+        let one = KOSValue::Int32(1);
+        let two = KOSValue::Int32(2);
+
+        let instrs = [
+            instr(Opcode::Push, Some(&x), None),
+            instr(Opcode::Bfa, None, Some(5)),
+            instr(Opcode::Push, Some(&one), None),
+            instr(Opcode::Sto, Some(&y), None),
+            instr(Opcode::Jmp, None, Some(7)),
+            instr(Opcode::Push, Some(&two), None),
+            instr(Opcode::Sto, Some(&y), None),
+        ];
+
+        assert_eq!(
+            decompile(&instrs, 0, &Deadline::default()).unwrap(),
+            [
+                "if x {",
+                "    set y to 1.",
+                "} else {",
+                "    set y to 2.",
+                "}"
+            ]
+        );
+    }
+
+    #[test]
+    fn lifts_until_loop() {
+        let done = KOSValue::String(String::from("$done"));
+        let one = KOSValue::Int32(1);
+
+        let instrs = [
+            instr(Opcode::Push, Some(&done), None),
+            instr(Opcode::Btr, None, Some(5)),
+            instr(Opcode::Push, Some(&one), None),
+            instr(Opcode::Wait, None, None),
+            instr(Opcode::Jmp, None, Some(0)),
+        ];
+
+        assert_eq!(
+            decompile(&instrs, 0, &Deadline::default()).unwrap(),
+            ["until done {", "    wait 1.", "}"]
+        );
+    }
+
+    #[test]
+    fn leaves_branch_past_end_of_section_as_goto() {
+        let x = KOSValue::String(String::from("$x"));
+        let one = KOSValue::Int32(1);
+        let y = KOSValue::String(String::from("$y"));
+
+        // The section ends with a branch whose destination is well past its last instruction
+        let instrs = [
+            instr(Opcode::Push, Some(&one), None),
+            instr(Opcode::Sto, Some(&y), None),
+            instr(Opcode::Push, Some(&x), None),
+            instr(Opcode::Bfa, None, Some(40)),
+        ];
+
+        assert_eq!(
+            decompile(&instrs, 0, &Deadline::default()).unwrap(),
+            ["set y to 1.", "// goto <?> unless x"]
+        );
+    }
+
+    #[test]
+    fn takes_parameters_from_an_empty_stack() {
+        let a = KOSValue::String(String::from("$a"));
+
+        let instrs = [
+            instr(Opcode::Stol, Some(&a), None),
+            instr(Opcode::Argb, None, None),
+        ];
+
+        assert_eq!(
+            decompile(&instrs, 0, &Deadline::default()).unwrap(),
+            ["parameter a."]
+        );
+    }
+}
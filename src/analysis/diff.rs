@@ -0,0 +1,55 @@
+/// One step of turning one sequence of lines into another
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffOp<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Finds the smallest set of lines to remove from and add to the old lines to get the new ones,
+/// using the longest common subsequence between them
+pub fn diff_lines<'a>(old: &'a [String], new: &'a [String]) -> Vec<DiffOp<'a>> {
+    diff_lines_by(old, new, |a, b| a == b)
+}
+
+/// The same as diff_lines, but with lines counted as the same whenever same_line says so
+pub fn diff_lines_by<'a>(
+    old: &'a [String],
+    new: &'a [String],
+    same_line: impl Fn(&str, &str) -> bool,
+) -> Vec<DiffOp<'a>> {
+    // lengths[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if same_line(&old[i], &new[j]) {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < old.len() && j < new.len() {
+        if same_line(&old[i], &new[j]) {
+            ops.push(DiffOp::Same(&old[i]));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            ops.push(DiffOp::Removed(&old[i]));
+            i += 1;
+        } else {
+            ops.push(DiffOp::Added(&new[j]));
+            j += 1;
+        }
+    }
+
+    ops.extend(old[i..].iter().map(|line| DiffOp::Removed(line)));
+    ops.extend(new[j..].iter().map(|line| DiffOp::Added(line)));
+
+    ops
+}
@@ -0,0 +1,30 @@
+//! Passes that find higher level structure in decoded instruction streams
+
+mod boilerplate;
+pub use boilerplate::detect_preamble;
+
+mod boundaries;
+pub use boundaries::{find_function_starts, FunctionStart};
+
+mod compiler;
+pub use compiler::{attribute_ksm, Attribution, Evidence};
+
+mod cfg;
+pub use cfg::{branch_distance, build_cfg, is_branch, BasicBlock, FlowInstr};
+
+mod decompile;
+pub use decompile::{decompile, LiftInstr};
+
+mod diff;
+pub use diff::{diff_lines, diff_lines_by, DiffOp};
+
+mod stack;
+pub use stack::{stack_depths, stack_effect, StackEffect, StackStep};
+
+mod verify;
+pub use verify::{check_ko_sections, check_ksm_sections, SectionCheck};
+
+mod callgraph;
+pub use callgraph::{
+    anonymous_name, is_call_like, is_opaque_name, AnonymousKind, CallGraph, CallNode,
+};
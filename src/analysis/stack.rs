@@ -0,0 +1,259 @@
+use kerbalobjects::{KOSValue, Opcode};
+
+use super::cfg::{build_cfg, FlowInstr};
+
+/// How an instruction changes the stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackEffect {
+    /// Pops a fixed number of values, then pushes a fixed number of values
+    Fixed { pops: usize, pushes: usize },
+    /// Pushes the argument marker that a call's arguments are stacked on top of
+    PushMarker,
+    /// Pops every argument down to and including the argument marker, along with the function
+    /// itself for indirect calls, then pushes the return value
+    Call { indirect: bool },
+    /// Pops the argument marker under a function's parameters, once they have all been taken
+    ArgBottom,
+}
+
+const fn fixed(pops: usize, pushes: usize) -> StackEffect {
+    StackEffect::Fixed { pops, pushes }
+}
+
+/// How each opcode changes the stack, following what each one does in kOS
+///
+/// Calls with an empty destination call whatever delegate is under the argument marker.
+pub fn stack_effect(opcode: Opcode, first_operand: Option<&KOSValue>) -> StackEffect {
+    match opcode {
+        Opcode::Push if first_operand == Some(&KOSValue::ArgMarker) => StackEffect::PushMarker,
+        Opcode::Call => StackEffect::Call {
+            indirect: matches!(
+                first_operand,
+                Some(KOSValue::String(s) | KOSValue::StringValue(s)) if s.is_empty()
+            ),
+        },
+        Opcode::Argb => StackEffect::ArgBottom,
+        Opcode::Push | Opcode::Prl | Opcode::Pdrl | Opcode::Phdl | Opcode::Targ | Opcode::Tcan => {
+            fixed(0, 1)
+        }
+        Opcode::Dup => fixed(1, 2),
+        Opcode::Swap => fixed(2, 2),
+        Opcode::Sto
+        | Opcode::Stol
+        | Opcode::Stog
+        | Opcode::Stoe
+        | Opcode::Uns
+        | Opcode::Pop
+        | Opcode::Bfa
+        | Opcode::Btr
+        | Opcode::Ret
+        | Opcode::Addt
+        | Opcode::Rmvt
+        | Opcode::Wait => fixed(1, 0),
+        Opcode::Gmb
+        | Opcode::Gmet
+        | Opcode::Neg
+        | Opcode::Not
+        | Opcode::Bool
+        | Opcode::Eval
+        | Opcode::Exst => fixed(1, 1),
+        Opcode::Add
+        | Opcode::Sub
+        | Opcode::Mul
+        | Opcode::Div
+        | Opcode::Pow
+        | Opcode::Cgt
+        | Opcode::Clt
+        | Opcode::Cgte
+        | Opcode::Clte
+        | Opcode::Ceq
+        | Opcode::Cne
+        | Opcode::And
+        | Opcode::Or
+        | Opcode::Gidx => fixed(2, 1),
+        Opcode::Smb => fixed(2, 0),
+        Opcode::Sidx => fixed(3, 0),
+        Opcode::Eof
+        | Opcode::Eop
+        | Opcode::Nop
+        | Opcode::Jmp
+        | Opcode::Bscp
+        | Opcode::Escp
+        | Opcode::Lbrt
+        | Opcode::Bogus => fixed(0, 0),
+    }
+}
+
+/// The stack after one instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackStep {
+    /// How many values the instruction left on the stack, less how many it took off
+    pub net: isize,
+    /// The estimated depth of the stack after the instruction, counted from the start of the
+    /// section
+    pub depth: isize,
+    /// If the instruction took more values off the stack than the section had put on it
+    pub underflow: bool,
+}
+
+/// The stack as it is at the start or end of a basic block
+#[derive(Debug, Clone, Default)]
+struct StackState {
+    depth: isize,
+    /// The depth under each argument marker that is still on the stack, innermost last
+    markers: Vec<isize>,
+    /// Whether the arguments a caller passed in are still under the bottom of the stack
+    taking_args: bool,
+}
+
+/// Works out the net stack effect of each instruction in a section, and a running estimate of
+/// the stack depth, which is carried from one basic block into the blocks that follow it
+///
+/// Sections that take parameters start with them already on the stack, so until the argument
+/// marker under them is popped, going below the start of the section isn't an underflow.
+pub fn stack_depths(instrs: &[(Opcode, Option<&KOSValue>)]) -> Vec<StackStep> {
+    let flow_instrs: Vec<FlowInstr> = instrs
+        .iter()
+        .map(|(opcode, first_operand)| FlowInstr::new(*opcode, *first_operand))
+        .collect();
+
+    let blocks = build_cfg(&flow_instrs);
+    let takes_args = instrs.iter().any(|(opcode, _)| *opcode == Opcode::Argb);
+
+    // A block starts with the stack its first predecessor left, which is only a guess when
+    // predecessors disagree, or when the only ones come later through a loop
+    let mut entry_states: Vec<Option<StackState>> = vec![None; blocks.len()];
+    let mut steps = Vec::with_capacity(instrs.len());
+
+    for (block_index, block) in blocks.iter().enumerate() {
+        let mut state = entry_states[block_index]
+            .take()
+            .unwrap_or_else(|| StackState {
+                taking_args: takes_args && block_index == 0,
+                ..StackState::default()
+            });
+
+        for &(opcode, first_operand) in &instrs[block.start..block.end] {
+            let before = state.depth;
+
+            let (depth, net) = match stack_effect(opcode, first_operand) {
+                StackEffect::Fixed { pops, pushes } => {
+                    let net = pushes as isize - pops as isize;
+                    (before + net, net)
+                }
+                StackEffect::PushMarker => {
+                    state.markers.push(before);
+                    (before + 1, 1)
+                }
+                StackEffect::Call { indirect } => {
+                    // Without a marker, the call is assumed to have taken no arguments
+                    let bottom = state.markers.pop().unwrap_or(before);
+                    let depth = bottom - indirect as isize + 1;
+                    (depth, depth - before)
+                }
+                StackEffect::ArgBottom => match state.markers.pop() {
+                    Some(bottom) => (bottom, bottom - before),
+                    None => {
+                        // Everything the caller passed in has now been taken, so the depth is
+                        // counted from here on
+                        state.taking_args = false;
+                        (0, -1)
+                    }
+                },
+            };
+
+            state.depth = depth;
+
+            steps.push(StackStep {
+                net,
+                depth,
+                underflow: depth < 0 && !state.taking_args,
+            });
+        }
+
+        for &successor in &block.successors {
+            if successor > block_index && entry_states[successor].is_none() {
+                entry_states[successor] = Some(state.clone());
+            }
+        }
+    }
+
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> KOSValue {
+        KOSValue::String(String::from(s))
+    }
+
+    fn depths(steps: &[StackStep]) -> Vec<isize> {
+        steps.iter().map(|step| step.depth).collect()
+    }
+
+    #[test]
+    fn calls_take_their_arguments_down_to_the_marker() {
+        let one = KOSValue::Int16(1);
+        let print = string("print()");
+
+        let steps = stack_depths(&[
+            (Opcode::Push, Some(&KOSValue::ArgMarker)),
+            (Opcode::Push, Some(&one)),
+            (Opcode::Push, Some(&one)),
+            (Opcode::Call, Some(&print)),
+            (Opcode::Pop, None),
+        ]);
+
+        assert_eq!(depths(&steps), vec![1, 2, 3, 1, 0]);
+        assert_eq!(steps[3].net, -2);
+        assert!(steps.iter().all(|step| !step.underflow));
+    }
+
+    #[test]
+    fn indirect_calls_also_take_the_delegate() {
+        let one = KOSValue::Int16(1);
+        let delegate = string("$f");
+        let empty = string("");
+
+        assert_eq!(
+            stack_effect(Opcode::Call, Some(&empty)),
+            StackEffect::Call { indirect: true }
+        );
+
+        let steps = stack_depths(&[
+            (Opcode::Push, Some(&delegate)),
+            (Opcode::Push, Some(&KOSValue::ArgMarker)),
+            (Opcode::Push, Some(&one)),
+            (Opcode::Call, Some(&empty)),
+        ]);
+
+        assert_eq!(depths(&steps), vec![1, 2, 3, 1]);
+    }
+
+    #[test]
+    fn popping_an_empty_stack_is_an_underflow() {
+        let steps = stack_depths(&[(Opcode::Pop, None)]);
+
+        assert_eq!(depths(&steps), vec![-1]);
+        assert!(steps[0].underflow);
+    }
+
+    #[test]
+    fn parameters_are_taken_until_the_argument_bottom() {
+        let x = string("$x");
+
+        let steps = stack_depths(&[
+            (Opcode::Sto, Some(&x)),
+            (Opcode::Argb, None),
+            (Opcode::Pop, None),
+        ]);
+
+        assert_eq!(depths(&steps), vec![-1, 0, -1]);
+        assert_eq!(
+            steps.iter().map(|step| step.underflow).collect::<Vec<_>>(),
+            vec![false, false, true]
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use kerbalobjects::ko::KOFile;
+use kerbalobjects::Opcode;
+
+use crate::fio::layout;
+use crate::index::{instr_opcode, IndexedKsm};
+
+/// Every code section begins with a two byte marker, like %F
+const CODE_MARKER_SIZE: usize = 2;
+
+/// How the bytes of one code section in the file compare with the instructions decoded from it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionCheck {
+    /// The position of the section among the file's code sections
+    pub section: usize,
+    /// The size in bytes the section takes up in the file, if it could be found there
+    pub encoded_size: Option<usize>,
+    /// The number of instructions found by stepping over the section's bytes, which KO files
+    /// don't record
+    pub encoded_count: Option<usize>,
+    /// The size in bytes of the instructions that were decoded
+    pub decoded_size: usize,
+    pub decoded_count: usize,
+    /// The positions in the section of the instructions with opcodes that don't exist
+    pub unknown: Vec<usize>,
+}
+
+impl SectionCheck {
+    /// How many more bytes the section takes up in the file than its decoded instructions account
+    /// for, which is negative when operand bytes are missing
+    pub fn extra_bytes(&self) -> Option<isize> {
+        self.encoded_size
+            .map(|size| size as isize - self.decoded_size as isize)
+    }
+
+    /// The number of problems found in the section
+    pub fn num_mismatches(&self) -> usize {
+        self.unknown.len()
+            + usize::from(self.encoded_size.is_none())
+            + usize::from(self.extra_bytes().is_some_and(|extra| extra != 0))
+            + usize::from(
+                self.encoded_count
+                    .is_some_and(|count| count != self.decoded_count),
+            )
+    }
+}
+
+/// Compares each code section of a KSM file with the bytes it was decoded from
+///
+/// The bytes are stepped over with the opcode table and the file's index width, the same way a
+/// reader has to, so that an encoder that wrote extra or missing operand bytes shows up as
+/// sections that take up a different amount of space than what was decoded from them.
+pub fn check_ksm_sections(index: &IndexedKsm, payload: Option<&[u8]>) -> Vec<SectionCheck> {
+    let layout = payload.map(|payload| layout::scan_ksm(payload, None));
+    let mut regions = layout
+        .iter()
+        .flat_map(|layout| layout.regions.iter())
+        .filter(|region| region.kind.is_code());
+
+    index
+        .ksmfile()
+        .code_sections()
+        .enumerate()
+        .map(|(section, code_section)| {
+            let region = regions.next();
+            let locations = index.locations(section);
+
+            SectionCheck {
+                section,
+                encoded_size: region.map(|region| region.size),
+                encoded_count: region.map(|region| region.count),
+                decoded_size: CODE_MARKER_SIZE
+                    + locations
+                        .iter()
+                        .map(|location| location.size)
+                        .sum::<usize>(),
+                decoded_count: locations.len(),
+                unknown: code_section
+                    .instructions()
+                    .enumerate()
+                    .filter(|(_, instr)| instr_opcode(instr) == Opcode::Bogus)
+                    .map(|(i, _)| i)
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// Compares each function section of a KO file with the size its section header gives it
+pub fn check_ko_sections(kofile: &KOFile) -> Vec<SectionCheck> {
+    kofile
+        .func_sections()
+        .enumerate()
+        .map(|(section, func_section)| SectionCheck {
+            section,
+            encoded_size: kofile
+                .get_section_header(func_section.section_index())
+                .map(|header| header.size as usize),
+            encoded_count: None,
+            decoded_size: func_section.instructions().map(layout::ko_instr_size).sum(),
+            decoded_count: func_section.instructions().count(),
+            unknown: func_section
+                .instructions()
+                .enumerate()
+                .filter(|(_, instr)| instr.opcode() == Opcode::Bogus)
+                .map(|(i, _)| i)
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kerbalobjects::KOSValue;
+
+    use crate::testing::{ksm_from, written};
+
+    #[test]
+    fn finds_nothing_wrong_with_a_well_encoded_file() {
+        let ksmfile = ksm_from(&[(Opcode::Push, &[KOSValue::ArgMarker]), (Opcode::Pop, &[])]);
+        let contents = written(&ksmfile);
+        let payload = crate::fio::ksm_payload(&contents).unwrap();
+        let checks = check_ksm_sections(&IndexedKsm::new(&ksmfile), Some(&payload));
+
+        assert_eq!(checks.len(), 3);
+        assert!(checks.iter().all(|check| check.extra_bytes() == Some(0)));
+        assert!(checks.iter().all(|check| check.num_mismatches() == 0));
+    }
+
+    #[test]
+    fn finds_a_section_missing_operand_bytes() {
+        // A push written without its operand, the way a broken encoder might
+        let ksmfile = ksm_from(&[(Opcode::Push, &[]), (Opcode::Pop, &[]), (Opcode::Pop, &[])]);
+        let contents = written(&ksmfile);
+        let payload = crate::fio::ksm_payload(&contents).unwrap();
+        let checks = check_ksm_sections(&IndexedKsm::new(&ksmfile), Some(&payload));
+
+        assert_ne!(checks[2].num_mismatches(), 0);
+    }
+
+    #[test]
+    fn reports_sections_it_could_not_find() {
+        let ksmfile = ksm_from(&[(Opcode::Pop, &[])]);
+        let checks = check_ksm_sections(&IndexedKsm::new(&ksmfile), None);
+
+        assert_eq!(checks[2].encoded_size, None);
+        assert_eq!(checks[2].num_mismatches(), 1);
+    }
+}
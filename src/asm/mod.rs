@@ -0,0 +1,84 @@
+//! Encoding of single KSM instructions to the exact bytes they have in a file, for the features
+//! that write instructions back out
+
+use kerbalobjects::Opcode;
+use std::mem::size_of;
+
+/// Encodes an instruction from its opcode and argument indices, where each index is written
+/// big-endian in the given number of bytes
+pub fn encode(opcode: Opcode, arg_indices: &[usize], index_width: usize) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(1 + arg_indices.len() * index_width);
+
+    bytes.push(u8::from(opcode));
+
+    for arg_index in arg_indices {
+        bytes.extend(&arg_index.to_be_bytes()[size_of::<usize>() - index_width..]);
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kerbalobjects::ksm::KSMFile;
+    use kerbalobjects::KOSValue;
+
+    use crate::index::{instr_opcode, instr_operands, IndexedKsm};
+    use crate::testing::{ksm_from, written};
+
+    /// print("Hello"). followed by pushing a scalar, which has operands of every arity
+    fn hello_file() -> KSMFile {
+        ksm_from(&[
+            (Opcode::Push, &[KOSValue::ArgMarker]),
+            (
+                Opcode::Push,
+                &[KOSValue::StringValue(String::from("Hello"))],
+            ),
+            (
+                Opcode::Call,
+                &[
+                    KOSValue::String(String::from("print()")),
+                    KOSValue::String(String::new()),
+                ],
+            ),
+            (Opcode::Pop, &[]),
+            (Opcode::Push, &[KOSValue::ScalarDouble(0.5)]),
+        ])
+    }
+
+    #[test]
+    fn encodes_to_the_file_bytes() {
+        let ksmfile = hello_file();
+        let index = IndexedKsm::new(&ksmfile);
+        let main_code = ksmfile.code_sections().last().unwrap();
+        let contents = written(&ksmfile);
+        let payload = crate::fio::ksm_payload(&contents).unwrap();
+        let encoded: Vec<u8> = main_code
+            .instructions()
+            .flat_map(|instr| {
+                let arg_indices: Vec<usize> =
+                    instr_operands(instr).into_iter().map(usize::from).collect();
+
+                encode(instr_opcode(instr), &arg_indices, index.index_width())
+            })
+            .collect();
+
+        assert!(payload
+            .windows(encoded.len())
+            .any(|window| window == encoded.as_slice()));
+    }
+
+    #[test]
+    fn encodes_indices_big_endian() {
+        assert_eq!(encode(Opcode::Pop, &[], 2), vec![0x4f]);
+        assert_eq!(
+            encode(Opcode::Call, &[0x0102, 0x0304], 2),
+            vec![0x4c, 0x01, 0x02, 0x03, 0x04]
+        );
+        assert_eq!(
+            encode(Opcode::Push, &[0x1f], 3),
+            vec![0x4e, 0x00, 0x00, 0x1f]
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use std::path::PathBuf;
+
+use crate::{
+    AddressBound, ArgIndexPlacement, AssumedType, CLIConfig, ColorRole, Command, GrepPattern,
+    OutputStyle, SectionOrder, SectionSelector, SizeFormat, StringEncoding,
+};
+
+/// Builds a CLIConfig without going through clap, for programs that use kDump as a library
+///
+/// Every setting starts out the same as if its flag was not given on the command line.
+#[derive(Debug, Default)]
+pub struct CLIConfigBuilder {
+    config: CLIConfig,
+}
+
+/// Generates a setter for each flag that is either on or off
+macro_rules! flag_setters {
+    ($($field:ident),* $(,)?) => {
+        $(
+            pub fn $field(mut self, value: bool) -> Self {
+                self.config.$field = value;
+                self
+            }
+        )*
+    };
+}
+
+/// Generates a setter for each optional setting, which takes the value the flag would have been given
+macro_rules! value_setters {
+    ($($field:ident: $ty:ty),* $(,)?) => {
+        $(
+            pub fn $field(mut self, value: impl Into<$ty>) -> Self {
+                self.config.$field = Some(value.into());
+                self
+            }
+        )*
+    };
+}
+
+impl CLIConfigBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    flag_setters!(
+        disassemble,
+        file_headers,
+        argument_section,
+        line_numbers,
+        section_headers,
+        data,
+        full_contents,
+        stabs,
+        syms,
+        reloc,
+        all_headers,
+        info,
+        demangle,
+        show_no_raw_instr,
+        show_no_labels,
+        mem_stats,
+        provenance_symbols,
+        verify,
+        annotate_data_symbols,
+        align_columns,
+        collapse_repeats,
+        local_labels,
+        csv,
+        symbol_index,
+        hide_boilerplate,
+        cfg_dot,
+        spec_report,
+        outline,
+        hex_dump,
+        ndjson,
+        gzip_members,
+        version_json,
+        help_json,
+        callgraph_dot,
+        opcode_costs,
+        schema,
+        try_widths,
+        json,
+        pretty,
+        rpc,
+        summary,
+        show_addr,
+        strict_flags,
+        check_args,
+        line_numbers_plain,
+        ascii_art,
+        line_sizes,
+        hide_lbrt,
+        line_costs,
+        decompile,
+        stack_effect,
+    );
+
+    value_setters!(
+        disassemble_symbol: String,
+        human_readable: SizeFormat,
+        simulate_growth: usize,
+        assume: AssumedType,
+        html: PathBuf,
+        style: OutputStyle,
+        renumber_from: usize,
+        show_arg_indices: ArgIndexPlacement,
+        hex_dump_section: String,
+        extract_section: String,
+        output: PathBuf,
+        template: PathBuf,
+        color_html: PathBuf,
+        ctags: PathBuf,
+        linemap: PathBuf,
+        csv_summary: PathBuf,
+        html_interactive: PathBuf,
+        binary_dump: PathBuf,
+        latex: PathBuf,
+        typst: PathBuf,
+        parquet: PathBuf,
+        assert: PathBuf,
+        start_address: AddressBound,
+        stop_address: AddressBound,
+        section: SectionSelector,
+        source: PathBuf,
+        grep: GrepPattern,
+        emit_kasm: PathBuf,
+        float_epsilon: f64,
+        timeout: u64,
+    );
+
+    /// The order KSM code sections are disassembled in
+    pub fn order(mut self, order: SectionOrder) -> Self {
+        self.config.order = order;
+        self
+    }
+
+    /// How the bytes of strings in the file are decoded
+    pub fn encoding(mut self, encoding: StringEncoding) -> Self {
+        self.config.encoding = encoding;
+        self
+    }
+
+    /// The number of instructions to show before and after each one that matches --grep
+    pub fn grep_context(mut self, before: usize, after: usize) -> Self {
+        self.config.before_context = before;
+        self.config.after_context = after;
+        self
+    }
+
+    /// The kinds of text to print without color
+    pub fn no_color_for(mut self, roles: impl IntoIterator<Item = ColorRole>) -> Self {
+        self.config.no_color_for = roles.into_iter().collect();
+        self
+    }
+
+    /// Two builds to check are the same instead of dumping a file
+    pub fn repro_check(mut self, a: impl Into<PathBuf>, b: impl Into<PathBuf>) -> Self {
+        self.config.repro_check = vec![a.into(), b.into()];
+        self
+    }
+
+    /// Runs a subcommand instead of dumping a file
+    pub fn command(mut self, command: Command) -> Self {
+        self.config.command = Some(command);
+        self
+    }
+
+    /// Finishes the configuration for dumping the file at the given path
+    pub fn build(mut self, file_path: impl Into<PathBuf>) -> CLIConfig {
+        self.config.file_path = Some(file_path.into());
+        self.config
+    }
+
+    /// Finishes the configuration without an input file, which is only useful along with a subcommand
+    pub fn build_command(self) -> CLIConfig {
+        self.config
+    }
+}
@@ -0,0 +1,132 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Once;
+use std::time::{Duration, Instant};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+/// The number of guards being held. While there are any, Ctrl-C stops the analyses instead of
+/// ending the program
+static GUARDS: AtomicUsize = AtomicUsize::new(0);
+static HANDLER: Once = Once::new();
+
+/// The exit code a program ended by Ctrl-C has
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// How many instructions an analysis gets through between checks inside of a section
+const CHECK_INTERVAL: usize = 1024;
+
+/// Why an analysis stopped before it got through the whole file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    TimedOut,
+    Interrupted,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::TimedOut => write!(f, "the --timeout ran out"),
+            StopReason::Interrupted => write!(f, "it was interrupted"),
+        }
+    }
+}
+
+/// When the analyses of a file have to stop by, which they check for between sections and every so
+/// often inside of one, so that whatever they have written so far is kept
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Deadline {
+    at: Option<Instant>,
+}
+
+impl Deadline {
+    /// A deadline the given time from now, or none at all
+    pub fn after(timeout: Option<Duration>) -> Self {
+        Deadline {
+            at: timeout.map(|timeout| Instant::now() + timeout),
+        }
+    }
+
+    /// Why the analysis has to stop now, if it does
+    pub fn check(&self) -> Option<StopReason> {
+        if INTERRUPTED.load(Ordering::Relaxed) {
+            Some(StopReason::Interrupted)
+        } else if self.at.is_some_and(|at| Instant::now() >= at) {
+            Some(StopReason::TimedOut)
+        } else {
+            None
+        }
+    }
+
+    /// The same as check(), but only looks every CHECK_INTERVAL instructions, so that checking
+    /// inside of a section doesn't slow it down
+    pub fn check_at(&self, instr_index: usize) -> Option<StopReason> {
+        if instr_index.is_multiple_of(CHECK_INTERVAL) {
+            self.check()
+        } else {
+            None
+        }
+    }
+}
+
+/// Makes Ctrl-C stop the analyses at their next check instead of ending the program, until the
+/// returned guard is dropped
+///
+/// A second Ctrl-C ends the program the way it always has, in case an analysis never checks.
+pub fn catch_interrupts() -> InterruptGuard {
+    // The handler can only be set once, so it is left in place and only does anything while a
+    // guard is held. If it can't be set, Ctrl-C just keeps ending the program.
+    HANDLER.call_once(|| {
+        let _ = ctrlc::set_handler(on_interrupt);
+    });
+
+    GUARDS.fetch_add(1, Ordering::Relaxed);
+
+    InterruptGuard
+}
+
+fn on_interrupt() {
+    if GUARDS.load(Ordering::Relaxed) > 0 && !INTERRUPTED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+
+    std::process::exit(INTERRUPTED_EXIT_CODE);
+}
+
+/// Puts Ctrl-C back to ending the program when it is dropped, unless another guard is still held
+pub struct InterruptGuard;
+
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        GUARDS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stops_once_the_timeout_runs_out() {
+        assert_eq!(
+            Deadline::after(Some(Duration::ZERO)).check(),
+            Some(StopReason::TimedOut)
+        );
+        assert_eq!(
+            Deadline::after(Some(Duration::from_secs(3600))).check(),
+            None
+        );
+        assert_eq!(Deadline::after(None).check(), None);
+    }
+
+    #[test]
+    fn only_checks_every_so_often_inside_a_section() {
+        let deadline = Deadline::after(Some(Duration::ZERO));
+
+        assert_eq!(deadline.check_at(0), Some(StopReason::TimedOut));
+        assert_eq!(deadline.check_at(1), None);
+        assert_eq!(
+            deadline.check_at(CHECK_INTERVAL),
+            Some(StopReason::TimedOut)
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use flate2::bufread::GzDecoder;
+use kerbalobjects::ko::KOFile;
+use kerbalobjects::BufferIterator;
+use std::error::Error;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use termcolor::{ColorSpec, WriteColor};
+
+use crate::fio::{self, KO_HEADER_SIZE, KO_MAGIC, KO_SECTION_HEADER_SIZE, KSM_MAGIC};
+use crate::output::format_size;
+use crate::{CLIConfig, CarveArgs, GREEN_COLOR, PURPLE_COLOR};
+
+/// The most a gzip stream is decompressed to before it is given up on, which is far larger than
+/// any KSM file kOS could load, so that a stream which expands without end can't use up memory
+const MAX_KSM_SIZE: u64 = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CandidateKind {
+    Ksm,
+    Ko,
+}
+
+/// A file that was found embedded inside of a larger blob
+#[derive(Debug, Clone, Copy)]
+pub struct Candidate {
+    pub kind: CandidateKind,
+    pub offset: usize,
+    pub size: usize,
+}
+
+impl Candidate {
+    fn extension(&self) -> &'static str {
+        match self.kind {
+            CandidateKind::Ksm => "ksm",
+            CandidateKind::Ko => "ko",
+        }
+    }
+}
+
+/// Scans an arbitrary blob for gzip streams that decompress into KSM files, and for KO files
+pub fn find_candidates(blob: &[u8]) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    let mut offset = 0;
+
+    while offset + 4 <= blob.len() {
+        let rest = &blob[offset..];
+
+        let found = if fio::is_gzip(rest) {
+            carve_ksm(rest)
+        } else if rest.starts_with(&KO_MAGIC) {
+            carve_ko(rest)
+        } else {
+            None
+        };
+
+        match found {
+            Some((kind, size)) => {
+                candidates.push(Candidate { kind, offset, size });
+
+                // Anything inside of a file we found is part of that file
+                offset += size.max(1);
+            }
+            None => {
+                offset += 1;
+            }
+        }
+    }
+
+    candidates
+}
+
+/// Tries to decompress a gzip stream, returning the number of compressed bytes if it contains a KSM file
+///
+/// Streams that don't begin with the KSM magic are given up on as soon as that is known, and ones
+/// that decompress to more than MAX_KSM_SIZE bytes are skipped.
+fn carve_ksm(contents: &[u8]) -> Option<(CandidateKind, usize)> {
+    let mut decoder = GzDecoder::new(contents);
+    let mut magic = [0; KSM_MAGIC.len()];
+
+    decoder.read_exact(&mut magic).ok()?;
+
+    if magic != KSM_MAGIC {
+        return None;
+    }
+
+    let rest_size = (&mut decoder)
+        .take(MAX_KSM_SIZE + 1 - KSM_MAGIC.len() as u64)
+        .read_to_end(&mut Vec::new())
+        .ok()?;
+
+    if (KSM_MAGIC.len() + rest_size) as u64 > MAX_KSM_SIZE {
+        return None;
+    }
+
+    // The buffered decoder only consumes the bytes that belong to the gzip member
+    let remaining = decoder.into_inner().len();
+
+    Some((CandidateKind::Ksm, contents.len() - remaining))
+}
+
+/// Tries to parse a KO file, returning its size as described by its section header table
+fn carve_ko(contents: &[u8]) -> Option<(CandidateKind, usize)> {
+    let mut iter = BufferIterator::new(contents);
+    let kofile = KOFile::parse(&mut iter).ok()?;
+
+    let sections_size: usize = kofile
+        .section_headers()
+        .map(|header| header.size as usize)
+        .sum();
+
+    let size = KO_HEADER_SIZE
+        + kofile.header().num_headers as usize * KO_SECTION_HEADER_SIZE
+        + sections_size;
+
+    Some((CandidateKind::Ko, size.min(contents.len())))
+}
+
+/// Runs the carve subcommand: lists every embedded file found, and extracts them if requested
+pub fn carve(
+    stream: &mut dyn WriteColor,
+    args: &CarveArgs,
+    config: &CLIConfig,
+) -> Result<(), Box<dyn Error>> {
+    let mut type_color = ColorSpec::new();
+    type_color.set_fg(Some(GREEN_COLOR));
+    let mut offset_color = ColorSpec::new();
+    offset_color.set_fg(Some(PURPLE_COLOR));
+    let regular_color = ColorSpec::new();
+
+    let blob = fs::read(&args.blob_path)?;
+    let candidates = find_candidates(&blob);
+
+    stream.set_color(&regular_color)?;
+    writeln!(stream, "\nCarved candidates:")?;
+
+    if candidates.is_empty() {
+        writeln!(stream, "None.")?;
+        return Ok(());
+    }
+
+    writeln!(stream, "  {:<12}{:<6}{:<12}", "Offset", "Type", "Size")?;
+
+    for candidate in candidates.iter() {
+        stream.set_color(&offset_color)?;
+        write!(stream, "  {:0>8x}    ", candidate.offset)?;
+        stream.set_color(&type_color)?;
+        write!(stream, "{:<6}", candidate.extension().to_uppercase())?;
+        stream.set_color(&regular_color)?;
+        writeln!(
+            stream,
+            "{}",
+            format_size(candidate.size, config.human_readable)
+        )?;
+    }
+
+    if let Some(extract_dir) = &args.extract {
+        fs::create_dir_all(extract_dir)?;
+
+        for candidate in candidates.iter() {
+            let path = extract_path(extract_dir, candidate);
+            let bytes = &blob[candidate.offset..candidate.offset + candidate.size];
+
+            fs::write(&path, bytes)?;
+
+            writeln!(stream, "Extracted {}", path.display())?;
+        }
+    }
+
+    Ok(())
+}
+
+fn extract_path(dir: &Path, candidate: &Candidate) -> std::path::PathBuf {
+    dir.join(format!(
+        "carved_{:0>8x}.{}",
+        candidate.offset,
+        candidate.extension()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compressed_ksm(payload_size: usize) -> Vec<u8> {
+        let mut payload = KSM_MAGIC.to_vec();
+        payload.resize(payload_size, 0);
+
+        fio::compress_ksm(&payload).unwrap()
+    }
+
+    #[test]
+    fn finds_a_ksm_stream_inside_a_blob() {
+        let ksm = compressed_ksm(64);
+        let mut blob = vec![0xaa; 10];
+        blob.extend(&ksm);
+        blob.extend([0xbb; 10]);
+
+        let candidates = find_candidates(&blob);
+
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].kind, CandidateKind::Ksm);
+        assert_eq!(candidates[0].offset, 10);
+        assert_eq!(candidates[0].size, ksm.len());
+    }
+
+    #[test]
+    fn skips_streams_that_decompress_past_the_limit() {
+        let too_big = compressed_ksm(MAX_KSM_SIZE as usize + 1);
+
+        assert!(carve_ksm(&too_big).is_none());
+        assert!(carve_ksm(&compressed_ksm(MAX_KSM_SIZE as usize)).is_some());
+    }
+
+    #[test]
+    fn skips_streams_that_are_not_ksm_files() {
+        let not_ksm = fio::compress_ksm(&[0; 64]).unwrap();
+
+        assert!(carve_ksm(&not_ksm).is_none());
+    }
+}
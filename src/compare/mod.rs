@@ -0,0 +1,549 @@
+use serde_json::Value;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use termcolor::{ColorSpec, WriteColor};
+
+use crate::analysis::{self, DiffOp};
+use crate::fio;
+use crate::model::{self, Record};
+use crate::pipeline::{self, ParsedFile};
+use crate::{DiffArgs, StringEncoding, GREEN_COLOR, LIGHT_RED_COLOR, ORANGE_COLOR};
+
+type CompareResult<T> = Result<T, Box<dyn Error>>;
+
+/// The contents of one area of a file, written as lines that only change when the area does
+///
+/// Labels, addresses, and argument indices are left out, since they shift whenever anything
+/// earlier in the file changes.
+struct SectionLines {
+    name: String,
+    lines: Vec<String>,
+}
+
+/// Finds the section a record belongs to, and the line it is written as
+fn record_line(record: &Record) -> Option<(String, String)> {
+    let joined = |values: &[Value]| {
+        values
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    match record {
+        Record::File { .. } | Record::Section { .. } => None,
+        Record::Argument {
+            value_type, value, ..
+        } => Some((
+            String::from("arguments"),
+            format!("{} {}", value_type, value),
+        )),
+        Record::Instruction {
+            section,
+            mnemonic,
+            operands,
+            ..
+        } => Some((
+            section.to_string(),
+            format!("{:<6}{}", mnemonic, joined(operands)),
+        )),
+        Record::DebugEntry { line, ranges } => Some((
+            String::from("debug"),
+            format!("line {}: {:?}", line, ranges),
+        )),
+        Record::Symbol {
+            table,
+            name,
+            value,
+            size,
+            binding,
+            sym_type,
+            section,
+        } => Some((
+            table.to_string(),
+            format!(
+                "{} {} {} value {} size {} section {}",
+                name, binding, sym_type, value, size, section
+            ),
+        )),
+        Record::Data {
+            section,
+            value_type,
+            value,
+            ..
+        } => Some((section.to_string(), format!("{} {}", value_type, value))),
+    }
+}
+
+/// Reads a KSM or KO file and splits its contents up by section
+fn read_sections(path: &Path) -> CompareResult<Vec<SectionLines>> {
+    file_sections(&crate::load_file(path, None)?)
+}
+
+/// Splits the contents of a parsed file up by section
+fn file_sections(file: &ParsedFile) -> CompareResult<Vec<SectionLines>> {
+    let mut sections: Vec<SectionLines> = Vec::new();
+
+    let mut add = |record: Record| {
+        if let Some((name, line)) = record_line(&record) {
+            match sections.iter_mut().find(|section| section.name == name) {
+                Some(section) => section.lines.push(line),
+                None => sections.push(SectionLines {
+                    name,
+                    lines: vec![line],
+                }),
+            }
+        }
+
+        Ok(())
+    };
+
+    model::visit_file(file, StringEncoding::default(), &mut add)?;
+
+    Ok(sections)
+}
+
+/// A piece of a line, which is either a floating point number or the text around one
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Float(f64),
+    Text(&'a str),
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+/// Splits a line up into its floating point numbers and the text between them
+///
+/// Numbers are only floating point if they are written with a decimal point or an exponent, the
+/// way JSON writes them, and anything inside of a string is left as text.
+fn tokens(line: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut text_start = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut prev = None;
+    let mut chars = line.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if (c.is_ascii_digit() || c == '-') && !prev.is_some_and(is_word_char) {
+            let end = line[i..]
+                .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '-' | '+')))
+                .map_or(line.len(), |len| i + len);
+            let number = &line[i..end];
+
+            if let (true, Ok(value)) = (number.contains(['.', 'e', 'E']), number.parse::<f64>()) {
+                tokens.push(Token::Text(&line[text_start..i]));
+                tokens.push(Token::Float(value));
+                text_start = end;
+
+                // The number is only made of single byte characters
+                for _ in 1..number.len() {
+                    chars.next();
+                }
+
+                prev = number.chars().last();
+                continue;
+            }
+        }
+
+        prev = Some(c);
+    }
+
+    tokens.push(Token::Text(&line[text_start..]));
+
+    tokens
+}
+
+/// Whether two lines are the same, where floating point numbers only have to be within epsilon
+/// of each other if one is given
+fn same_line(a: &str, b: &str, epsilon: Option<f64>) -> bool {
+    match epsilon {
+        _ if a == b => true,
+        None => false,
+        Some(epsilon) => {
+            let (a, b) = (tokens(a), tokens(b));
+
+            a.len() == b.len()
+                && a.iter().zip(&b).all(|pair| match pair {
+                    (Token::Float(a), Token::Float(b)) => (a - b).abs() <= epsilon,
+                    (a, b) => a == b,
+                })
+        }
+    }
+}
+
+/// Whether a section has the same lines in both files
+fn same_section(a: Option<&SectionLines>, b: Option<&SectionLines>, epsilon: Option<f64>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            a.lines.len() == b.lines.len()
+                && a.lines
+                    .iter()
+                    .zip(&b.lines)
+                    .all(|(a, b)| same_line(a, b, epsilon))
+        }
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn find_section<'a>(sections: &'a [SectionLines], name: &str) -> Option<&'a SectionLines> {
+    sections.iter().find(|section| section.name == name)
+}
+
+/// A 64 bit FNV-1a hash of a section's lines, which is all that's needed to tell if it changed
+fn checksum(lines: &[String]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+
+    for byte in lines.iter().flat_map(|line| line.bytes().chain([b'\n'])) {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    hash
+}
+
+/// Compares two builds of a file, first by a checksum of each section, and then line by line
+/// for only the sections whose checksums differ
+///
+/// With --float-epsilon, a section whose checksum differs only because of floating point values
+/// that are close enough is shown as within epsilon, and isn't compared line by line.
+pub fn compare(stream: &mut dyn WriteColor, args: &DiffArgs) -> CompareResult<()> {
+    let regular = ColorSpec::new();
+    let mut added = ColorSpec::new();
+    added.set_fg(Some(GREEN_COLOR));
+    let mut removed = ColorSpec::new();
+    removed.set_fg(Some(LIGHT_RED_COLOR));
+    let mut changed = ColorSpec::new();
+    changed.set_fg(Some(ORANGE_COLOR));
+
+    let old_sections = read_sections(&args.old)?;
+    let new_sections = read_sections(&args.new)?;
+
+    // Every section name from either file, in the order they first appear
+    let mut names: Vec<&str> = old_sections.iter().map(|s| s.name.as_str()).collect();
+
+    for section in &new_sections {
+        if !names.contains(&section.name.as_str()) {
+            names.push(&section.name);
+        }
+    }
+
+    let name_width = names
+        .iter()
+        .map(|name| name.len())
+        .max()
+        .unwrap_or(0)
+        .max(7);
+
+    writeln!(stream, "\nSection checksums:")?;
+    writeln!(
+        stream,
+        "  {:<width$}  {:<16}  {:<16}",
+        "Section",
+        "Old",
+        "New",
+        width = name_width
+    )?;
+
+    let mut differing = Vec::new();
+
+    for name in &names {
+        let old = find_section(&old_sections, name);
+        let new = find_section(&new_sections, name);
+
+        let old_sum = old.map(|section| checksum(&section.lines));
+        let new_sum = new.map(|section| checksum(&section.lines));
+
+        let format_sum = |sum: Option<u64>| match sum {
+            Some(sum) => format!("{:016x}", sum),
+            None => String::from("-"),
+        };
+
+        let (status, color) = match (old_sum, new_sum) {
+            (Some(old_sum), Some(new_sum)) if old_sum == new_sum => ("same", &regular),
+            (Some(_), Some(_)) if same_section(old, new, args.float_epsilon) => {
+                ("within epsilon", &regular)
+            }
+            (Some(_), Some(_)) => ("changed", &changed),
+            (Some(_), None) => ("removed", &removed),
+            _ => ("added", &added),
+        };
+
+        write!(
+            stream,
+            "  {:<width$}  {:<16}  {:<16}  ",
+            name,
+            format_sum(old_sum),
+            format_sum(new_sum),
+            width = name_width
+        )?;
+        stream.set_color(color)?;
+        writeln!(stream, "{}", status)?;
+        stream.set_color(&regular)?;
+
+        if old_sum != new_sum && !same_section(old, new, args.float_epsilon) {
+            differing.push((*name, old, new));
+        }
+    }
+
+    if differing.is_empty() {
+        writeln!(stream, "\nNo sections changed")?;
+        return Ok(());
+    }
+
+    let no_lines = Vec::new();
+
+    for (name, old, new) in differing {
+        writeln!(stream, "\nChanges in {}:", name)?;
+
+        let old_lines = old.map(|section| &section.lines).unwrap_or(&no_lines);
+        let new_lines = new.map(|section| &section.lines).unwrap_or(&no_lines);
+
+        for op in analysis::diff_lines_by(old_lines, new_lines, |a, b| {
+            same_line(a, b, args.float_epsilon)
+        }) {
+            match op {
+                DiffOp::Same(_) => {}
+                DiffOp::Removed(line) => {
+                    stream.set_color(&removed)?;
+                    writeln!(stream, "  - {}", line)?;
+                }
+                DiffOp::Added(line) => {
+                    stream.set_color(&added)?;
+                    writeln!(stream, "  + {}", line)?;
+                }
+            }
+        }
+
+        stream.set_color(&regular)?;
+    }
+
+    Ok(())
+}
+
+fn read_file(path: &Path) -> CompareResult<Vec<u8>> {
+    Ok(fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?)
+}
+
+/// Parses a file that has already been read
+fn parse_contents(contents: Vec<u8>) -> CompareResult<ParsedFile> {
+    let file_type = pipeline::detect_file_type(&contents, None)?;
+
+    pipeline::parse_file(contents, file_type)
+}
+
+/// Checks whether two builds of a file are the same, for verifying that a build is reproducible
+///
+/// The gzip header's timestamp and the compression level are ignored, since neither changes what
+/// kOS loads. Payloads that differ can still be semantically identical, when only labels,
+/// addresses, or argument indices moved, or when floating point values are only as far apart as
+/// the epsilon, if one is given. Builds that are different are an error, so that a script can
+/// tell from the exit code.
+pub fn repro_check(
+    stream: &mut dyn WriteColor,
+    a: &Path,
+    b: &Path,
+    float_epsilon: Option<f64>,
+) -> CompareResult<()> {
+    let regular = ColorSpec::new();
+    let mut same = ColorSpec::new();
+    same.set_fg(Some(GREEN_COLOR));
+    let mut different = ColorSpec::new();
+    different.set_fg(Some(LIGHT_RED_COLOR));
+
+    let a_contents = read_file(a)?;
+    let b_contents = read_file(b)?;
+
+    let raw_identical = a_contents == b_contents;
+    // The payload of each file with any gzip compression taken off, which is all that matters to kOS
+    let a_payload = fio::ksm_payload(&a_contents)?.into_owned();
+    let b_payload = fio::ksm_payload(&b_contents)?.into_owned();
+
+    writeln!(stream, "\nReproducibility check:")?;
+    writeln!(
+        stream,
+        "  {}: {} bytes decompressed",
+        a.display(),
+        a_payload.len()
+    )?;
+    writeln!(
+        stream,
+        "  {}: {} bytes decompressed",
+        b.display(),
+        b_payload.len()
+    )?;
+
+    if a_payload == b_payload {
+        writeln!(stream)?;
+        stream.set_color(&same)?;
+        writeln!(stream, "byte-identical")?;
+        stream.set_color(&regular)?;
+
+        if !raw_identical {
+            writeln!(
+                stream,
+                "  The compressed files differ, but only in their gzip header or compression level"
+            )?;
+        }
+
+        return Ok(());
+    }
+
+    let first_difference = a_payload
+        .iter()
+        .zip(&b_payload)
+        .position(|(a, b)| a != b)
+        .unwrap_or(a_payload.len().min(b_payload.len()));
+
+    let a_sections = file_sections(&parse_contents(a_contents)?)?;
+    let b_sections = file_sections(&parse_contents(b_contents)?)?;
+
+    let mut names: Vec<&str> = a_sections.iter().map(|s| s.name.as_str()).collect();
+
+    for section in &b_sections {
+        if !names.contains(&section.name.as_str()) {
+            names.push(&section.name);
+        }
+    }
+
+    let differing: Vec<&str> = names
+        .into_iter()
+        .filter(|name| {
+            !same_section(
+                find_section(&a_sections, name),
+                find_section(&b_sections, name),
+                float_epsilon,
+            )
+        })
+        .collect();
+
+    if differing.is_empty() {
+        writeln!(stream)?;
+        stream.set_color(&same)?;
+        writeln!(stream, "semantically-identical")?;
+        stream.set_color(&regular)?;
+        writeln!(
+            stream,
+            "  The payloads first differ at 0x{:x}, but every section has the same contents once labels, addresses, and argument indices are left out",
+            first_difference
+        )?;
+
+        return Ok(());
+    }
+
+    writeln!(stream)?;
+    stream.set_color(&different)?;
+    writeln!(stream, "different")?;
+    stream.set_color(&regular)?;
+    writeln!(
+        stream,
+        "  The payloads first differ at 0x{:x}",
+        first_difference
+    )?;
+
+    for name in &differing {
+        writeln!(stream, "  {} differs", name)?;
+    }
+
+    Err(format!("The builds differ in {} section(s)", differing.len()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kerbalobjects::{KOSValue, Opcode};
+    use termcolor::NoColor;
+
+    use crate::testing::{ksm_from, written, TempFile};
+
+    fn written_ksm(value: KOSValue) -> Vec<u8> {
+        written(&ksm_from(&[(Opcode::Push, &[value]), (Opcode::Pop, &[])]))
+    }
+
+    fn check(
+        name: &str,
+        a: &[u8],
+        b: &[u8],
+        float_epsilon: Option<f64>,
+    ) -> (CompareResult<()>, String) {
+        let a_file = TempFile::new(&format!("{}-a.ksm", name), a);
+        let b_file = TempFile::new(&format!("{}-b.ksm", name), b);
+
+        let mut out = NoColor::new(Vec::new());
+        let result = repro_check(&mut out, a_file.path(), b_file.path(), float_epsilon);
+
+        (result, String::from_utf8(out.into_inner()).unwrap())
+    }
+
+    #[test]
+    fn ignores_the_compression() {
+        let written = written_ksm(KOSValue::Int16(1));
+        let payload = fio::ksm_payload(&written).unwrap();
+        let recompressed = fio::compress_canonical(&payload).unwrap();
+
+        let (result, out) = check("same", &written, &recompressed, None);
+
+        assert!(result.is_ok());
+        assert!(out.contains("byte-identical"), "{}", out);
+        assert!(out.contains("only in their gzip header"), "{}", out);
+    }
+
+    #[test]
+    fn fails_on_different_builds() {
+        let (result, out) = check(
+            "different",
+            &written_ksm(KOSValue::Int16(1)),
+            &written_ksm(KOSValue::Int16(2)),
+            None,
+        );
+
+        assert!(result.is_err());
+        assert!(out.contains("different"), "{}", out);
+    }
+
+    #[test]
+    fn floats_within_epsilon_are_the_same() {
+        let epsilon = Some(1e-6);
+
+        assert!(same_line("push  1.0000001", "push  1.0", epsilon));
+        assert!(same_line("push  -2.5e-7, 1", "push  0.0, 1", epsilon));
+        assert!(!same_line("push  1.0000001", "push  1.0", None));
+        assert!(!same_line("push  1.1", "push  1.0", epsilon));
+    }
+
+    #[test]
+    fn only_floats_get_an_epsilon() {
+        let epsilon = Some(5.0);
+
+        assert!(!same_line("push  1", "push  2", epsilon));
+        assert!(!same_line("push  1.0, 1", "push  1.0, 2", epsilon));
+        assert!(!same_line(r#"push  "1.5""#, r#"push  "1.6""#, epsilon));
+        assert!(!same_line("x1.5 global", "x1.6 global", epsilon));
+    }
+
+    #[test]
+    fn repro_check_takes_an_epsilon() {
+        let a = written_ksm(KOSValue::ScalarDouble(0.1));
+        let b = written_ksm(KOSValue::ScalarDouble(0.1 + 1e-12));
+
+        let (result, out) = check("close", &a, &b, None);
+        assert!(result.is_err(), "{}", out);
+
+        let (result, out) = check("epsilon", &a, &b, Some(1e-9));
+        assert!(result.is_ok(), "{}", out);
+        assert!(out.contains("semantically-identical"), "{}", out);
+    }
+}
@@ -0,0 +1,244 @@
+use kerbalobjects::ksm::sections::CodeType;
+use kerbalobjects::KOSValue;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::path::PathBuf;
+use termcolor::WriteColor;
+
+use crate::analysis;
+use crate::fio::layout;
+use crate::index::{instr_opcode, IndexedKsm};
+use crate::{model, output, pipeline, CorpusFormat, CorpusStatsArgs, ParsedFile};
+
+type CorpusResult<T> = Result<T, Box<dyn Error>>;
+
+/// Everything counted across every file in a corpus
+#[derive(Debug, Default)]
+struct Tally {
+    files: usize,
+    unreadable: usize,
+    file_types: BTreeMap<&'static str, usize>,
+    opcodes: BTreeMap<&'static str, usize>,
+    num_functions: usize,
+    function_instructions: usize,
+    function_bytes: usize,
+    compilers: BTreeMap<String, usize>,
+    /// The number of files each string constant appears in
+    strings: BTreeMap<String, usize>,
+}
+
+impl Tally {
+    fn add_function(&mut self, num_instructions: usize, num_bytes: usize) {
+        self.num_functions += 1;
+        self.function_instructions += num_instructions;
+        self.function_bytes += num_bytes;
+    }
+
+    /// Counts each string once per file, no matter how many times it appears in it
+    fn add_strings<'a>(&mut self, values: impl Iterator<Item = &'a KOSValue>) {
+        let strings: BTreeSet<&str> = values
+            .filter_map(|value| match value {
+                KOSValue::String(s) | KOSValue::StringValue(s) if is_constant(s) => {
+                    Some(s.as_str())
+                }
+                _ => None,
+            })
+            .collect();
+
+        for s in strings {
+            *self.strings.entry(s.to_string()).or_default() += 1;
+        }
+    }
+
+    fn add_file(&mut self, file: &ParsedFile) {
+        self.files += 1;
+
+        if let Some(ksm) = file.ksm() {
+            *self.file_types.entry("ksm").or_default() += 1;
+
+            let index = IndexedKsm::new(ksm);
+
+            for code_section in ksm.code_sections() {
+                let mut num_bytes = 0;
+
+                for instr in code_section.instructions() {
+                    *self.opcodes.entry(instr_opcode(instr).into()).or_default() += 1;
+                    num_bytes += index.instr_size(instr);
+                }
+
+                if code_section.section_type == CodeType::Function {
+                    self.add_function(code_section.instructions().count(), num_bytes);
+                }
+            }
+
+            let toolchain = analysis::attribute_ksm(&index).toolchain;
+            *self
+                .compilers
+                .entry(toolchain.unwrap_or_else(|| String::from("unknown")))
+                .or_default() += 1;
+
+            self.add_strings(ksm.arg_section.arguments());
+        } else if let Some(kofile) = file.ko() {
+            *self.file_types.entry("ko").or_default() += 1;
+
+            for func_section in kofile.func_sections() {
+                let mut num_bytes = 0;
+
+                for instr in func_section.instructions() {
+                    *self.opcodes.entry(instr.opcode().into()).or_default() += 1;
+
+                    num_bytes += layout::ko_instr_size(instr);
+                }
+
+                self.add_function(func_section.instructions().count(), num_bytes);
+            }
+
+            // Object files don't carry anything that says what compiled them
+            *self.compilers.entry(String::from("unknown")).or_default() += 1;
+
+            self.add_strings(
+                kofile
+                    .data_sections()
+                    .flat_map(|data_section| data_section.data()),
+            );
+        }
+    }
+}
+
+/// Whether a string is a constant written in the source, instead of a label or variable name
+/// that the compiler made up
+fn is_constant(s: &str) -> bool {
+    !s.is_empty() && !s.starts_with('@') && !s.starts_with('$')
+}
+
+#[derive(Debug, Serialize)]
+struct CountEntry<'a> {
+    name: &'a str,
+    count: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct FunctionStats {
+    count: usize,
+    average_instructions: f64,
+    average_bytes: f64,
+}
+
+/// The whole report, as it is written as JSON
+#[derive(Debug, Serialize)]
+struct CorpusReport<'a> {
+    format_version: u32,
+    files: usize,
+    unreadable: usize,
+    file_types: Vec<CountEntry<'a>>,
+    opcodes: Vec<CountEntry<'a>>,
+    functions: FunctionStats,
+    compilers: Vec<CountEntry<'a>>,
+    strings: Vec<CountEntry<'a>>,
+}
+
+/// Sorts counts from most to least common, keeping at most the given number of them
+fn top<'a, K: AsRef<str> + 'a>(
+    counts: impl IntoIterator<Item = (&'a K, &'a usize)>,
+    limit: usize,
+) -> Vec<CountEntry<'a>> {
+    let mut entries: Vec<CountEntry> = counts
+        .into_iter()
+        .map(|(name, &count)| CountEntry {
+            name: name.as_ref(),
+            count,
+        })
+        .collect();
+
+    // The sort is stable, so ties stay in name order
+    entries.sort_by_key(|entry| Reverse(entry.count));
+    entries.truncate(limit);
+
+    entries
+}
+
+fn average(total: usize, count: usize) -> f64 {
+    if count == 0 {
+        0.0
+    } else {
+        total as f64 / count as f64
+    }
+}
+
+fn write_csv_report(stream: &mut dyn WriteColor, report: &CorpusReport) -> CorpusResult<()> {
+    output::write_csv_row(stream, &["metric", "name", "value"])?;
+
+    let totals = [
+        ("files", report.files.to_string()),
+        ("unreadable", report.unreadable.to_string()),
+        ("functions", report.functions.count.to_string()),
+        (
+            "average_function_instructions",
+            format!("{:.2}", report.functions.average_instructions),
+        ),
+        (
+            "average_function_bytes",
+            format!("{:.2}", report.functions.average_bytes),
+        ),
+    ];
+
+    for (name, value) in &totals {
+        output::write_csv_row(stream, &["total", name, value])?;
+    }
+
+    for (metric, entries) in [
+        ("file_type", &report.file_types),
+        ("opcode", &report.opcodes),
+        ("compiler", &report.compilers),
+        ("string", &report.strings),
+    ] {
+        for entry in entries {
+            output::write_csv_row(stream, &[metric, entry.name, &entry.count.to_string()])?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads every KSM and KO file under a directory, and reports how often each opcode, compiler,
+/// and string constant is used, along with how big functions are on average
+pub fn corpus_stats(stream: &mut dyn WriteColor, args: &CorpusStatsArgs) -> CorpusResult<()> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    crate::summary::find_files(&args.dir, &mut files)?;
+
+    let mut tally = Tally::default();
+
+    for path in &files {
+        match pipeline::load_file(path, None) {
+            Ok(file) => tally.add_file(&file),
+            Err(_) => tally.unreadable += 1,
+        }
+    }
+
+    let report = CorpusReport {
+        format_version: model::FORMAT_VERSION,
+        files: tally.files,
+        unreadable: tally.unreadable,
+        file_types: top(&tally.file_types, usize::MAX),
+        opcodes: top(&tally.opcodes, usize::MAX),
+        functions: FunctionStats {
+            count: tally.num_functions,
+            average_instructions: average(tally.function_instructions, tally.num_functions),
+            average_bytes: average(tally.function_bytes, tally.num_functions),
+        },
+        compilers: top(&tally.compilers, usize::MAX),
+        strings: top(&tally.strings, args.top_strings),
+    };
+
+    match args.format {
+        CorpusFormat::Json => {
+            serde_json::to_writer_pretty(&mut *stream, &report)?;
+            writeln!(stream)?;
+        }
+        CorpusFormat::Csv => write_csv_report(stream, &report)?,
+    }
+
+    Ok(())
+}
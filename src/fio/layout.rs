@@ -0,0 +1,382 @@
+use kerbalobjects::ko::{Instr, KOFile};
+use kerbalobjects::Opcode;
+use std::ops::Range;
+
+use crate::index::ko_instr_operand_count;
+
+use super::{KO_HEADER_SIZE, KO_OPERAND_SIZE, KO_SECTION_HEADER_SIZE};
+
+/// The kind of a region of a decompressed KSM file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    Header,
+    Arguments,
+    Function,
+    Initialization,
+    Main,
+    Debug,
+}
+
+impl RegionKind {
+    /// The two byte marker that begins a region, if it has one
+    pub fn marker(&self) -> Option<&'static str> {
+        match self {
+            RegionKind::Header => None,
+            RegionKind::Arguments => Some("%A"),
+            RegionKind::Function => Some("%F"),
+            RegionKind::Initialization => Some("%I"),
+            RegionKind::Main => Some("%M"),
+            RegionKind::Debug => Some("%D"),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RegionKind::Header => "Header",
+            RegionKind::Arguments => "Arguments",
+            RegionKind::Function => "Function",
+            RegionKind::Initialization => "Init",
+            RegionKind::Main => "Main",
+            RegionKind::Debug => "Debug",
+        }
+    }
+
+    /// Whether this region holds instructions
+    pub fn is_code(&self) -> bool {
+        matches!(
+            self,
+            RegionKind::Function | RegionKind::Initialization | RegionKind::Main
+        )
+    }
+}
+
+/// Where one region of a KSM file is, and how many things are in it
+#[derive(Debug, Clone)]
+pub struct Region {
+    pub kind: RegionKind,
+    /// The offset of the region's first byte, including its marker
+    pub offset: usize,
+    /// The size of the region in bytes, including its marker
+    pub size: usize,
+    /// The number of arguments, instructions, or debug entries in the region
+    pub count: usize,
+    /// The number of instructions with opcodes that don't exist. Only ever set for code sections.
+    pub num_unknown: usize,
+}
+
+/// Where the scan of a file had to stop, and why
+#[derive(Debug, Clone)]
+pub struct LayoutError {
+    pub offset: usize,
+    pub message: String,
+}
+
+/// The byte layout of a decompressed KSM file, found by decoding only what is needed to find
+/// where each region ends
+#[derive(Debug, Clone)]
+pub struct KsmLayout {
+    /// The number of bytes each instruction operand takes up
+    pub index_width: usize,
+    pub regions: Vec<Region>,
+    /// Set if the file couldn't be scanned all the way to the end, in which case the regions are
+    /// everything that was found before that point
+    pub error: Option<LayoutError>,
+}
+
+impl KsmLayout {
+    /// The number of instructions with unknown opcodes in every code section together
+    pub fn num_unknown(&self) -> usize {
+        self.regions.iter().map(|region| region.num_unknown).sum()
+    }
+
+    /// Whether the whole file was scanned without finding anything that doesn't decode
+    pub fn is_clean(&self) -> bool {
+        self.error.is_none() && self.num_unknown() == 0
+    }
+
+    /// Finds a region by name, which is its kind, optionally followed by a colon and which one of
+    /// that kind it is counting from 0, like main or func:2
+    pub fn find_region(&self, name: &str) -> Option<&Region> {
+        let (kind_name, nth) = match name.split_once(':') {
+            Some((kind_name, nth)) => (kind_name, nth.parse().ok()?),
+            None => (name, 0),
+        };
+
+        let kind = match kind_name.to_lowercase().as_str() {
+            "header" => RegionKind::Header,
+            "arguments" | "args" => RegionKind::Arguments,
+            "func" | "function" => RegionKind::Function,
+            "init" => RegionKind::Initialization,
+            "main" => RegionKind::Main,
+            "debug" => RegionKind::Debug,
+            _ => return None,
+        };
+
+        self.regions
+            .iter()
+            .filter(|region| region.kind == kind)
+            .nth(nth)
+    }
+}
+
+/// The index widths that a file can be given
+pub const INDEX_WIDTHS: [usize; 4] = [1, 2, 3, 4];
+
+/// Finds the index widths other than the one the file says it uses that would decode the whole
+/// file cleanly, which is a sign that the width in the argument section header is wrong
+pub fn clean_alternate_widths(payload: &[u8], current: usize) -> Vec<usize> {
+    INDEX_WIDTHS
+        .into_iter()
+        .filter(|&width| width != current && scan_ksm(payload, Some(width)).is_clean())
+        .collect()
+}
+
+/// The size of the header every KSM file begins with, which is just the magic
+const KSM_HEADER_SIZE: usize = 4;
+
+/// Scans the layout of a decompressed KSM file
+///
+/// The operand width is normally read from the argument section, but can be given to see how the
+/// code sections would be decoded with a different one.
+pub fn scan_ksm(payload: &[u8], width_override: Option<usize>) -> KsmLayout {
+    let mut layout = KsmLayout {
+        index_width: 0,
+        regions: Vec::new(),
+        error: None,
+    };
+
+    if let Err(error) = scan_regions(payload, width_override, &mut layout) {
+        layout.error = Some(error);
+    }
+
+    layout
+}
+
+fn scan_regions(
+    payload: &[u8],
+    width_override: Option<usize>,
+    layout: &mut KsmLayout,
+) -> Result<(), LayoutError> {
+    if payload.len() < KSM_HEADER_SIZE {
+        return Err(LayoutError {
+            offset: 0,
+            message: format!("the file is only {} bytes long", payload.len()),
+        });
+    }
+
+    layout.regions.push(Region {
+        kind: RegionKind::Header,
+        offset: 0,
+        size: KSM_HEADER_SIZE,
+        count: 0,
+        num_unknown: 0,
+    });
+
+    let mut pos = KSM_HEADER_SIZE;
+
+    expect_marker(payload, pos, "%A")?;
+
+    let width = *payload.get(pos + 2).ok_or(LayoutError {
+        offset: pos + 2,
+        message: String::from("the argument section ends before its index width"),
+    })? as usize;
+
+    layout.index_width = width_override.unwrap_or(width);
+
+    let (size, count) = scan_arguments(payload, pos)?;
+    layout.regions.push(Region {
+        kind: RegionKind::Arguments,
+        offset: pos,
+        size,
+        count,
+        num_unknown: 0,
+    });
+    pos += size;
+
+    while pos < payload.len() {
+        let kind = match payload.get(pos..pos + 2) {
+            Some(b"%F") => RegionKind::Function,
+            Some(b"%I") => RegionKind::Initialization,
+            Some(b"%M") => RegionKind::Main,
+            Some(b"%D") => RegionKind::Debug,
+            _ => {
+                return Err(LayoutError {
+                    offset: pos,
+                    message: format!(
+                        "expected a section marker, found byte {:0>2x}",
+                        payload[pos]
+                    ),
+                })
+            }
+        };
+
+        let (size, count, num_unknown) = if kind == RegionKind::Debug {
+            let (size, count) = scan_debug(payload, pos)?;
+
+            (size, count, 0)
+        } else {
+            scan_code(payload, pos, layout.index_width)?
+        };
+
+        layout.regions.push(Region {
+            kind,
+            offset: pos,
+            size,
+            count,
+            num_unknown,
+        });
+        pos += size;
+    }
+
+    Ok(())
+}
+
+fn expect_marker(payload: &[u8], pos: usize, marker: &str) -> Result<(), LayoutError> {
+    if payload.get(pos..pos + 2) == Some(marker.as_bytes()) {
+        Ok(())
+    } else {
+        Err(LayoutError {
+            offset: pos,
+            message: format!("expected the {} marker", marker),
+        })
+    }
+}
+
+/// The number of bytes the value of an argument with the given type byte takes up, not counting
+/// the type byte, given the bytes that follow the type byte
+fn argument_size(type_byte: u8, rest: &[u8]) -> Option<usize> {
+    match type_byte {
+        // Null and ArgMarker
+        0 | 8 => Some(0),
+        // Bool, Byte, and BoolValue
+        1 | 2 | 11 => Some(1),
+        // Int16
+        3 => Some(2),
+        // Int32, Float, and ScalarInt
+        4 | 5 | 9 => Some(4),
+        // Double and ScalarDouble
+        6 | 10 => Some(8),
+        // String and StringValue, which are prefixed with their length
+        7 | 12 => rest.first().map(|len| 1 + *len as usize),
+        _ => None,
+    }
+}
+
+fn scan_arguments(payload: &[u8], start: usize) -> Result<(usize, usize), LayoutError> {
+    // The marker and the index width
+    let mut pos = start + 3;
+    let mut count = 0;
+
+    while pos < payload.len() && payload[pos] != b'%' {
+        let size = argument_size(payload[pos], &payload[pos + 1..]).ok_or(LayoutError {
+            offset: pos,
+            message: format!("unknown argument type {}", payload[pos]),
+        })?;
+
+        pos += 1 + size;
+        count += 1;
+    }
+
+    if pos > payload.len() {
+        return Err(LayoutError {
+            offset: payload.len(),
+            message: String::from("the last argument runs past the end of the file"),
+        });
+    }
+
+    Ok((pos - start, count))
+}
+
+/// Walks the instructions of a code section, only decoding their opcodes so that each one can be stepped over
+fn scan_code(
+    payload: &[u8],
+    start: usize,
+    index_width: usize,
+) -> Result<(usize, usize, usize), LayoutError> {
+    let mut pos = start + 2;
+    let mut count = 0;
+    let mut num_unknown = 0;
+
+    while pos < payload.len() && payload[pos] != b'%' {
+        let opcode = Opcode::from(payload[pos]);
+
+        if opcode == Opcode::Bogus {
+            num_unknown += 1;
+        }
+
+        pos += 1 + opcode.num_operands() * index_width;
+        count += 1;
+    }
+
+    if pos > payload.len() {
+        return Err(LayoutError {
+            offset: payload.len(),
+            message: String::from("the last instruction runs past the end of the file"),
+        });
+    }
+
+    Ok((pos - start, count, num_unknown))
+}
+
+fn scan_debug(payload: &[u8], start: usize) -> Result<(usize, usize), LayoutError> {
+    let range_size = *payload.get(start + 2).ok_or(LayoutError {
+        offset: start + 2,
+        message: String::from("the debug section ends before its range size"),
+    })? as usize;
+
+    let mut pos = start + 3;
+    let mut count = 0;
+
+    // Each entry is a 2 byte line number, a 1 byte range count, and then the ranges
+    while pos < payload.len() {
+        let num_ranges = *payload.get(pos + 2).ok_or(LayoutError {
+            offset: pos,
+            message: String::from("a debug entry is cut off by the end of the file"),
+        })? as usize;
+
+        pos += 3 + num_ranges * range_size * 2;
+        count += 1;
+    }
+
+    if pos > payload.len() {
+        return Err(LayoutError {
+            offset: payload.len(),
+            message: String::from("the last debug entry runs past the end of the file"),
+        });
+    }
+
+    Ok((pos - start, count))
+}
+
+/// Finds the byte range each KO section takes up in the file, in section header order
+///
+/// Sections are stored right after the section header table, back to back in the same order as their headers.
+pub fn ko_section_ranges(kofile: &KOFile) -> Vec<Range<usize>> {
+    let num_headers = kofile.section_headers().count();
+    let mut offset = KO_HEADER_SIZE + num_headers * KO_SECTION_HEADER_SIZE;
+
+    kofile
+        .section_headers()
+        .map(|header| {
+            let range = offset..offset + header.size as usize;
+
+            offset = range.end;
+
+            range
+        })
+        .collect()
+}
+
+/// Finds the byte range of the KO section with the given name
+pub fn ko_section_range(kofile: &KOFile, name: &str) -> Option<Range<usize>> {
+    kofile
+        .section_headers()
+        .zip(ko_section_ranges(kofile))
+        .find(|(header, _)| kofile.get_header_name(header).map(|s| s.as_str()) == Some(name))
+        .map(|(_, range)| range)
+}
+
+/// The number of bytes a KO instruction takes up in its function section
+pub fn ko_instr_size(instr: &Instr) -> usize {
+    1 + KO_OPERAND_SIZE * ko_instr_operand_count(instr)
+}
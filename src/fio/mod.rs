@@ -1,11 +1,30 @@
+use std::borrow::Cow;
 use std::error::Error;
 use std::io::prelude::*;
+use std::ops::Range;
 
 use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::{Compression, GzBuilder};
+
+pub mod layout;
+
+/// The magic every KSM file begins with once decompressed, the second byte of which is the format version
+pub const KSM_MAGIC: [u8; 4] = [0x6b, 0x03, 0x58, 0x45];
+pub const KO_MAGIC: [u8; 4] = [0x6b, 0x01, 0x6f, 0x66];
+
+/// The size of the KO file header: magic, version, number of headers, and the .shstrtab index
+pub const KO_HEADER_SIZE: usize = 9;
+/// The size of each entry in the KO section header table: name index, kind, and size
+pub const KO_SECTION_HEADER_SIZE: usize = 9;
+/// The size of each KO instruction operand, which is an index into the data section
+pub const KO_OPERAND_SIZE: usize = 4;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FileType {
     KerbalMachineCode,
+    /// A KSM file that was written without being gzip compressed
+    UncompressedKerbalMachineCode,
     KerbalObject,
     Unknown,
 }
@@ -13,32 +32,390 @@ pub enum FileType {
 /// Determines the type of a file using the raw bytes
 pub fn determine_file_type(contents: &[u8]) -> Result<FileType, Box<dyn Error>> {
     if is_gzip(contents) {
-        let mut decoder = GzDecoder::new(contents);
-        let mut decompressed = [0, 0, 0, 0];
+        let mut decompressed = Vec::new();
 
-        decoder.read_exact(&mut decompressed)?;
+        GzDecoder::new(contents)
+            .take(KSM_MAGIC.len() as u64)
+            .read_to_end(&mut decompressed)
+            .map_err(|e| {
+                format!(
+                    "The file is gzip compressed, but can't be decompressed: {}",
+                    e
+                )
+            })?;
 
         if is_ksm(&decompressed) {
             return Ok(FileType::KerbalMachineCode);
         }
+
+        if decompressed.len() < KSM_MAGIC.len() {
+            return Err(format!(
+                "The file is gzip compressed, but only decompresses to {} byte(s), which is too short to be a KSM file",
+                decompressed.len()
+            )
+            .into());
+        }
+    } else if is_ksm(contents) {
+        return Ok(FileType::UncompressedKerbalMachineCode);
     } else if is_ko(contents) {
         return Ok(FileType::KerbalObject);
     }
 
+    // The magic bytes didn't match anything, but the rest of the file may still be intact
+    sniff_file_type(contents)
+}
+
+/// Explains why a file wasn't recognized, telling apart the usual mistakes: an empty file, a
+/// text file like a script given instead of what it compiled to, and a file that was cut short
+pub fn describe_unrecognized(contents: &[u8]) -> String {
+    if contents.is_empty() {
+        return String::from("The file is empty, so it isn't a KSM or KO file");
+    }
+
+    if let Some(first_line) = first_text_line(contents) {
+        let text = String::from_utf8_lossy(contents);
+
+        if looks_like_kerboscript(&text) {
+            return format!(
+                "This looks like uncompiled KerboScript source ({} line(s), {} character(s)), not a KSM file. Compile it first, in kOS with: COMPILE \"script.ks\" TO \"script.ksm\".",
+                text.lines().count(),
+                text.chars().count()
+            );
+        }
+
+        return format!(
+            "The file is text, not a KSM or KO file. Its first line is: {:?}",
+            first_line
+        );
+    }
+
+    let magic = &contents[..contents.len().min(KSM_MAGIC.len())];
+
+    if contents.len() < KSM_MAGIC.len() {
+        return format!(
+            "The file is only {} byte(s) long, which is too short to be a KSM or KO file. Its bytes are {}",
+            contents.len(),
+            hex_bytes(magic)
+        );
+    }
+
+    format!(
+        "File type not recognized. It begins with {}, but a KSM file begins with {}, or {} when compressed, and a KO file begins with {}",
+        hex_bytes(magic),
+        hex_bytes(&KSM_MAGIC),
+        hex_bytes(&[0x1f, 0x8b]),
+        hex_bytes(&KO_MAGIC)
+    )
+}
+
+/// Whether text is a KerboScript script, which is decided by whether any of its statements begin
+/// with one of the keywords that scripts are mostly made of
+fn looks_like_kerboscript(text: &str) -> bool {
+    const KEYWORDS: &[&str] = &[
+        "print",
+        "lock",
+        "unlock",
+        "set",
+        "wait",
+        "until",
+        "when",
+        "on",
+        "function",
+        "parameter",
+        "declare",
+        "local",
+        "global",
+        "run",
+        "runpath",
+        "runoncepath",
+        "clearscreen",
+        "if",
+        "for",
+        "from",
+        "return",
+        "stage",
+        "toggle",
+    ];
+
+    text.lines()
+        .map(str::trim_start)
+        .filter(|line| !line.starts_with("//"))
+        .filter_map(|line| line.split(|c: char| !c.is_ascii_alphabetic()).next())
+        .any(|word| KEYWORDS.contains(&word.to_ascii_lowercase().as_str()))
+}
+
+/// The first line of a file that isn't blank, if the start of the file is text
+fn first_text_line(contents: &[u8]) -> Option<String> {
+    let prefix = &contents[..contents.len().min(512)];
+
+    let text = match std::str::from_utf8(prefix) {
+        Ok(text) => text,
+        // The prefix can end in the middle of a character
+        Err(e) if e.error_len().is_none() => {
+            std::str::from_utf8(&prefix[..e.valid_up_to()]).ok()?
+        }
+        Err(_) => return None,
+    };
+
+    if text.chars().any(|c| c.is_control() && !c.is_whitespace()) {
+        return None;
+    }
+
+    let line = text
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("");
+
+    Some(line.chars().take(80).collect())
+}
+
+/// Writes bytes as space separated hex, like 6b 03 58 45
+pub fn hex_bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Guesses the type of a file whose magic bytes are damaged by looking for structures
+/// that only appear in one kind of file
+pub fn sniff_file_type(contents: &[u8]) -> Result<FileType, Box<dyn Error>> {
+    if is_gzip(contents) {
+        let decompressed = decompress(contents)?;
+
+        if looks_like_ksm(&decompressed) {
+            return Ok(FileType::KerbalMachineCode);
+        }
+    } else if looks_like_ksm(contents) {
+        return Ok(FileType::UncompressedKerbalMachineCode);
+    } else if looks_like_ko(contents) {
+        return Ok(FileType::KerbalObject);
+    }
+
     Ok(FileType::Unknown)
 }
 
-/// Checks if the file is in proper GZIP format
-fn is_gzip(contents: &[u8]) -> bool {
-    contents[0] == 0x1f && contents[1] == 0x8b && contents[2] == 0x08 && contents[3] == 0x00
+/// Determines which kind of KSM file these contents are, for when the user has told us it is a KSM file
+pub fn assume_ksm(contents: &[u8]) -> FileType {
+    if is_gzip(contents) {
+        FileType::KerbalMachineCode
+    } else {
+        FileType::UncompressedKerbalMachineCode
+    }
+}
+
+/// Decompresses the entire contents of a gzip stream
+pub fn decompress(contents: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut decoder = GzDecoder::new(contents);
+    let mut decompressed = Vec::new();
+
+    decoder.read_to_end(&mut decompressed)?;
+
+    Ok(decompressed)
+}
+
+/// One gzip member of a file, which is a complete gzip stream of its own
+#[derive(Debug, Clone)]
+pub struct GzipMember {
+    pub offset: usize,
+    pub compressed_size: usize,
+    pub contents: Vec<u8>,
+}
+
+/// Everything in a file that was read as a series of concatenated gzip members
+#[derive(Debug, Clone, Default)]
+pub struct GzipScan {
+    pub members: Vec<GzipMember>,
+    /// The bytes after the last member that could be read
+    pub trailing: Option<Range<usize>>,
+    /// Why the trailing bytes could not be read as another member, if they started like one
+    pub error: Option<String>,
+}
+
+impl GzipScan {
+    /// Whether the file is anything other than exactly one gzip member
+    pub fn has_anomalies(&self) -> bool {
+        self.members.len() > 1 || self.trailing.is_some()
+    }
+}
+
+/// Reads every gzip member in a file one after another, stopping at the first bytes that aren't one
+///
+/// The KSM parser only reads the first member and ignores anything after it, so this is how
+/// anything hidden after it is found.
+pub fn scan_gzip(contents: &[u8]) -> GzipScan {
+    let mut scan = GzipScan::default();
+    let mut offset = 0;
+
+    while offset < contents.len() {
+        let rest = &contents[offset..];
+
+        if !is_gzip(rest) {
+            scan.trailing = Some(offset..contents.len());
+            break;
+        }
+
+        // The bufread decoder is used because it leaves everything after the member unread
+        let mut decoder = flate2::bufread::GzDecoder::new(rest);
+        let mut decompressed = Vec::new();
+
+        if let Err(e) = decoder.read_to_end(&mut decompressed) {
+            scan.trailing = Some(offset..contents.len());
+            scan.error = Some(e.to_string());
+            break;
+        }
+
+        let compressed_size = rest.len() - decoder.into_inner().len();
+
+        scan.members.push(GzipMember {
+            offset,
+            compressed_size,
+            contents: decompressed,
+        });
+
+        offset += compressed_size;
+    }
+
+    scan
+}
+
+/// Gets the raw KSM payload out of a file, decompressing it if it is compressed
+pub fn ksm_payload(contents: &[u8]) -> std::io::Result<Cow<'_, [u8]>> {
+    if is_gzip(contents) {
+        Ok(Cow::Owned(decompress(contents)?))
+    } else {
+        Ok(Cow::Borrowed(contents))
+    }
+}
+
+/// Produces a buffer that the KSM parser will accept: a gzip stream with an intact magic number
+///
+/// Well formed files are passed through untouched. Uncompressed files are compressed, and files with
+/// damaged magic bytes are repaired, so that files that were forced to be read as KSM can still be parsed.
+pub fn ksm_parser_input(contents: &[u8]) -> Result<Cow<'_, [u8]>, Box<dyn Error>> {
+    let mut payload = if is_gzip(contents) {
+        let mut decoder = GzDecoder::new(contents);
+        let mut magic = [0, 0, 0, 0];
+
+        decoder.read_exact(&mut magic)?;
+
+        if is_ksm(&magic) {
+            return Ok(Cow::Borrowed(contents));
+        }
+
+        decompress(contents)?
+    } else {
+        contents.to_vec()
+    };
+
+    repair_magic(&mut payload, &KSM_MAGIC);
+
+    Ok(Cow::Owned(compress_ksm(&payload)?))
+}
+
+/// Produces a buffer that the KO parser will accept, repairing the magic bytes if they are damaged
+pub fn ko_parser_input(contents: &[u8]) -> Cow<'_, [u8]> {
+    if is_ko(contents) {
+        Cow::Borrowed(contents)
+    } else {
+        let mut repaired = contents.to_vec();
+
+        repair_magic(&mut repaired, &KO_MAGIC);
+
+        Cow::Owned(repaired)
+    }
+}
+
+fn repair_magic(contents: &mut [u8], magic: &[u8; 4]) {
+    let len = contents.len().min(magic.len());
+
+    contents[..len].copy_from_slice(&magic[..len]);
+}
+
+/// Compresses a raw KSM payload so that it can be given to the KSM parser, which only reads gzip streams
+pub fn compress_ksm(contents: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+
+    encoder.write_all(contents)?;
+
+    encoder.finish()
+}
+
+/// Compresses a raw KSM payload the same way every time, with a zero timestamp, no file name, an
+/// unknown operating system, and the best compression level
+pub fn compress_canonical(contents: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzBuilder::new()
+        .mtime(0)
+        .operating_system(255)
+        .write(Vec::new(), Compression::best());
+
+    encoder.write_all(contents)?;
+
+    encoder.finish()
+}
+
+/// Checks if the file has a valid GZIP header using the deflate method
+///
+/// Any combination of the defined header flags is accepted, only the reserved bits must be unset
+pub fn is_gzip(contents: &[u8]) -> bool {
+    match contents {
+        [0x1f, 0x8b, 0x08, flags, ..] => flags & 0xe0 == 0,
+        _ => false,
+    }
 }
 
 /// Checks the first 4 bytes of the file to tell if the contents are a KSM file or someone's compressed homework
 fn is_ksm(contents: &[u8]) -> bool {
-    contents[0] == 0x6b && contents[1] == 0x03 && contents[2] == 0x58 && contents[3] == 0x45
+    contents.starts_with(&KSM_MAGIC)
 }
 
 /// Checks the first 4 bytes of the file to tell if the contents are a KO file
 fn is_ko(contents: &[u8]) -> bool {
-    contents[0] == 0x6b && contents[1] == 0x01 && contents[2] == 0x6f && contents[3] == 0x66
+    contents.starts_with(&KO_MAGIC)
+}
+
+/// A KSM payload always begins its argument section directly after the magic, and contains at least
+/// one code section marker after that
+fn looks_like_ksm(contents: &[u8]) -> bool {
+    contents.get(4..6) == Some(b"%A")
+        && [b"%F", b"%I", b"%M"]
+            .iter()
+            .any(|marker| contents.windows(2).any(|window| window == *marker))
+}
+
+/// Every KO file needs a section header string table, and they are always given the same name
+fn looks_like_ko(contents: &[u8]) -> bool {
+    contents.windows(10).any(|window| window == b".shstrtab\0")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_any_defined_gzip_flags() {
+        assert!(is_gzip(&[0x1f, 0x8b, 0x08, 0x00]));
+        assert!(is_gzip(&[0x1f, 0x8b, 0x08, 0x1f]));
+        assert!(!is_gzip(&[0x1f, 0x8b, 0x08, 0x20]));
+        assert!(!is_gzip(&[0x1f, 0x8b, 0x07, 0x00]));
+        assert!(!is_gzip(&[0x1f, 0x8b, 0x08]));
+    }
+
+    #[test]
+    fn tells_compressed_and_uncompressed_ksm_apart() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&KSM_MAGIC).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        assert_eq!(
+            determine_file_type(&compressed).unwrap(),
+            FileType::KerbalMachineCode
+        );
+        assert_eq!(
+            determine_file_type(&KSM_MAGIC).unwrap(),
+            FileType::UncompressedKerbalMachineCode
+        );
+    }
 }
@@ -0,0 +1,130 @@
+use clap::{Arg, Command, CommandFactory};
+use serde::Serialize;
+use std::error::Error;
+use termcolor::WriteColor;
+
+use crate::CLIConfig;
+
+/// One flag or positional argument, as a program building its own options panel needs it
+#[derive(Debug, Serialize)]
+struct HelpArg {
+    id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    long: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    short: Option<char>,
+    help: String,
+    positional: bool,
+    required: bool,
+    takes_value: bool,
+    /// Whether the argument can be given more than once, or take more than one value
+    multiple: bool,
+    value_names: Vec<String>,
+    possible_values: Vec<String>,
+    default_values: Vec<String>,
+}
+
+/// The whole program or one of its subcommands, along with everything under it
+#[derive(Debug, Serialize)]
+struct HelpCommand {
+    name: String,
+    about: String,
+    args: Vec<HelpArg>,
+    subcommands: Vec<HelpCommand>,
+}
+
+/// Everything --help-json writes
+#[derive(Debug, Serialize)]
+struct HelpTree {
+    format_version: u32,
+    kdump: &'static str,
+    command: HelpCommand,
+}
+
+/// The values of one of an argument's settings as strings
+///
+/// Flags that are only on or off still have value names and defaults inside clap, which would
+/// only be noise here, so they are left out.
+fn values<T: ToString>(takes_value: bool, values: impl IntoIterator<Item = T>) -> Vec<String> {
+    if takes_value {
+        values.into_iter().map(|value| value.to_string()).collect()
+    } else {
+        Vec::new()
+    }
+}
+
+fn help_arg(arg: &Arg) -> HelpArg {
+    let takes_value = arg.get_action().takes_values();
+
+    HelpArg {
+        id: arg.get_id().to_string(),
+        long: arg.get_long().map(String::from),
+        short: arg.get_short(),
+        help: arg.get_help().map(ToString::to_string).unwrap_or_default(),
+        positional: arg.is_positional(),
+        required: arg.is_required_set(),
+        takes_value,
+        multiple: matches!(
+            arg.get_action(),
+            clap::ArgAction::Append | clap::ArgAction::Count
+        ) || arg
+            .get_num_args()
+            .is_some_and(|num_args| num_args.max_values() > 1),
+        value_names: values(takes_value, arg.get_value_names().unwrap_or_default()),
+        possible_values: values(
+            takes_value,
+            arg.get_possible_values()
+                .iter()
+                .filter(|value| !value.is_hide_set())
+                .map(|value| value.get_name()),
+        ),
+        default_values: values(
+            takes_value,
+            arg.get_default_values()
+                .iter()
+                .map(|value| value.to_string_lossy()),
+        ),
+    }
+}
+
+fn help_command(command: &Command) -> HelpCommand {
+    HelpCommand {
+        name: command.get_name().to_string(),
+        about: command
+            .get_about()
+            .map(ToString::to_string)
+            .unwrap_or_default(),
+        args: command
+            .get_arguments()
+            .filter(|arg| !arg.is_hide_set())
+            .map(help_arg)
+            .collect(),
+        subcommands: command
+            .get_subcommands()
+            .filter(|subcommand| !subcommand.is_hide_set())
+            .map(help_command)
+            .collect(),
+    }
+}
+
+/// Writes every flag and subcommand, with their descriptions, as a JSON document
+///
+/// This is built from the same definitions the command line is parsed with, so it can't fall out
+/// of step with them.
+pub fn write_help_json(stream: &mut dyn WriteColor) -> Result<(), Box<dyn Error>> {
+    let mut command = CLIConfig::command();
+
+    // Building fills in everything clap adds by itself, like --help and --version
+    command.build();
+
+    let tree = HelpTree {
+        format_version: crate::model::FORMAT_VERSION,
+        kdump: crate::VERSION,
+        command: help_command(&command),
+    };
+
+    serde_json::to_writer_pretty(&mut *stream, &tree)?;
+    writeln!(stream)?;
+
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use std::error::Error;
+use std::path::Path;
+use termcolor::{ColorSpec, WriteColor};
+
+use crate::analysis::{self, DiffOp};
+use crate::index::{instr_opcode, instr_operands, IndexedKsm};
+use crate::output::format_size;
+use crate::{pipeline, CLIConfig, HistoryArgs, GREEN_COLOR, LIGHT_RED_COLOR};
+
+/// What one build of a function looks like
+struct Snapshot {
+    /// Each instruction as its mnemonic and operand values, leaving out labels since they shift
+    /// whenever anything before the function changes
+    lines: Vec<String>,
+    size_bytes: usize,
+}
+
+/// Reads a KSM file and finds the function with the given name in it
+fn snapshot(
+    path: &Path,
+    symbol: &str,
+    config: &CLIConfig,
+) -> Result<Option<Snapshot>, Box<dyn Error>> {
+    let file = pipeline::load_file(path, config.assume)?;
+    let ksm = file
+        .ksm()
+        .ok_or_else(|| format!("{} is not a KSM file", path.display()))?;
+    let index = IndexedKsm::new(ksm);
+
+    for (section_index, code_section) in ksm.code_sections().enumerate() {
+        if index.section_name(code_section)? != symbol {
+            continue;
+        }
+
+        let mut lines = Vec::new();
+        let mut size_bytes = 0;
+
+        for (instr, location) in code_section
+            .instructions()
+            .zip(index.locations(section_index))
+        {
+            let mnemonic: &str = instr_opcode(instr).into();
+
+            let operands: Vec<String> = instr_operands(instr)
+                .into_iter()
+                .map(|op| match index.value(op) {
+                    Some(value) => crate::output::kosvalue_quoted(value, config.encoding),
+                    None => format!("<invalid {:x}>", usize::from(op)),
+                })
+                .collect();
+
+            lines.push(format!("{:<6}{}", mnemonic, operands.join(", ")));
+            size_bytes += location.size;
+        }
+
+        return Ok(Some(Snapshot { lines, size_bytes }));
+    }
+
+    Ok(None)
+}
+
+/// Formats the change in a number since the previous build, if there was one
+fn change(current: usize, previous: Option<usize>) -> String {
+    match previous {
+        Some(previous) if current >= previous => format!(" (+{})", current - previous),
+        Some(previous) => format!(" (-{})", previous - current),
+        None => String::new(),
+    }
+}
+
+/// Tracks a single function through several builds of a program, showing how its size and
+/// instructions change from each build to the next
+pub fn history(
+    stream: &mut dyn WriteColor,
+    args: &HistoryArgs,
+    config: &CLIConfig,
+) -> Result<(), Box<dyn Error>> {
+    let regular = ColorSpec::new();
+    let mut added = ColorSpec::new();
+    added.set_fg(Some(GREEN_COLOR));
+    let mut removed = ColorSpec::new();
+    removed.set_fg(Some(LIGHT_RED_COLOR));
+
+    writeln!(stream, "\nHistory of {}:", args.symbol)?;
+
+    let mut previous: Option<Snapshot> = None;
+
+    for path in &args.files {
+        let current = snapshot(path, &args.symbol, config)?;
+
+        write!(stream, "\n  {}: ", path.display())?;
+
+        let current = match current {
+            Some(current) => current,
+            None => {
+                writeln!(stream, "not found")?;
+                previous = None;
+                continue;
+            }
+        };
+
+        writeln!(
+            stream,
+            "{} instructions{}, {}{}",
+            current.lines.len(),
+            change(
+                current.lines.len(),
+                previous.as_ref().map(|p| p.lines.len())
+            ),
+            format_size(current.size_bytes, config.human_readable),
+            change(current.size_bytes, previous.as_ref().map(|p| p.size_bytes))
+        )?;
+
+        if let Some(previous) = &previous {
+            let ops = analysis::diff_lines(&previous.lines, &current.lines);
+
+            if ops.iter().all(|op| matches!(op, DiffOp::Same(_))) {
+                writeln!(stream, "    no instruction changes")?;
+            }
+
+            for op in ops {
+                match op {
+                    DiffOp::Same(_) => {}
+                    DiffOp::Removed(line) => {
+                        stream.set_color(&removed)?;
+                        writeln!(stream, "    - {}", line)?;
+                    }
+                    DiffOp::Added(line) => {
+                        stream.set_color(&added)?;
+                        writeln!(stream, "    + {}", line)?;
+                    }
+                }
+            }
+
+            stream.set_color(&regular)?;
+        }
+
+        previous = Some(current);
+    }
+
+    Ok(())
+}
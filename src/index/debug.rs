@@ -0,0 +1,140 @@
+use kerbalobjects::ksm::sections::{DebugEntry, DebugRange, DebugSection};
+
+/// One debug range, along with the entry it belongs to
+#[derive(Debug, Clone, Copy)]
+struct IndexedRange<'a> {
+    entry: &'a DebugEntry,
+    range: &'a DebugRange,
+    /// The position of this range in the debug section, so that overlapping ranges resolve
+    /// to the same entry a linear scan would find
+    order: usize,
+}
+
+/// A lookup structure that finds the debug entry containing a given address without scanning
+/// every entry
+///
+/// Ranges are sorted by their start address, and each position also remembers the largest end
+/// address seen up to that point, so overlapping ranges are still found.
+#[derive(Debug, Clone)]
+pub struct DebugRangeIndex<'a> {
+    ranges: Vec<IndexedRange<'a>>,
+    max_ends: Vec<usize>,
+}
+
+impl<'a> DebugRangeIndex<'a> {
+    pub fn new(debug_section: &'a DebugSection) -> Self {
+        let mut ranges = Vec::new();
+
+        for entry in debug_section.debug_entries() {
+            for range in entry.ranges() {
+                let order = ranges.len();
+
+                ranges.push(IndexedRange {
+                    entry,
+                    range,
+                    order,
+                });
+            }
+        }
+
+        ranges.sort_by_key(|indexed| indexed.range.start);
+
+        let mut max_ends = Vec::with_capacity(ranges.len());
+        let mut max_end = 0;
+
+        for indexed in ranges.iter() {
+            max_end = max_end.max(indexed.range.end);
+            max_ends.push(max_end);
+        }
+
+        DebugRangeIndex { ranges, max_ends }
+    }
+
+    /// Finds the debug entry and range that contain the given address, if any
+    pub fn find(&self, addr: usize) -> Option<(&'a DebugEntry, &'a DebugRange)> {
+        let end = self
+            .ranges
+            .partition_point(|indexed| indexed.range.start <= addr);
+
+        let mut found: Option<&IndexedRange<'a>> = None;
+
+        for i in (0..end).rev() {
+            // No range at or before this point reaches far enough
+            if self.max_ends[i] < addr {
+                break;
+            }
+
+            let indexed = &self.ranges[i];
+
+            if indexed.range.end >= addr && found.is_none_or(|f| indexed.order < f.order) {
+                found = Some(indexed);
+            }
+        }
+
+        found.map(|indexed| (indexed.entry, indexed.range))
+    }
+
+    /// The total number of ranges in the index
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn debug_section(entries: &[(isize, usize, usize)]) -> DebugSection {
+        let entry = |&(line, start, end): &(isize, usize, usize)| {
+            DebugEntry::new(line).with_range(DebugRange::new(start, end))
+        };
+
+        let mut debug_section = DebugSection::new(entry(&entries[0]));
+
+        for other in &entries[1..] {
+            debug_section.add(entry(other));
+        }
+
+        debug_section
+    }
+
+    fn line(index: &DebugRangeIndex, addr: usize) -> Option<isize> {
+        index.find(addr).map(|(entry, _)| entry.line_number)
+    }
+
+    #[test]
+    fn finds_the_range_an_address_is_in() {
+        let debug_section = debug_section(&[(2, 0x0a, 0x0d), (1, 0x06, 0x09)]);
+        let index = DebugRangeIndex::new(&debug_section);
+
+        assert_eq!(index.len(), 2);
+        assert_eq!(line(&index, 0x06), Some(1));
+        assert_eq!(line(&index, 0x09), Some(1));
+        assert_eq!(line(&index, 0x0b), Some(2));
+        assert_eq!(line(&index, 0x05), None);
+        assert_eq!(line(&index, 0x0e), None);
+    }
+
+    #[test]
+    fn overlapping_ranges_resolve_to_the_first_in_the_file() {
+        let debug_section = debug_section(&[(1, 0x10, 0x12), (2, 0x06, 0x20), (3, 0x08, 0x0a)]);
+        let index = DebugRangeIndex::new(&debug_section);
+
+        assert_eq!(line(&index, 0x11), Some(1));
+        assert_eq!(line(&index, 0x09), Some(2));
+        assert_eq!(line(&index, 0x1f), Some(2));
+    }
+
+    #[test]
+    fn entries_without_ranges_find_nothing() {
+        let debug_section = DebugSection::new(DebugEntry::new(1));
+        let index = DebugRangeIndex::new(&debug_section);
+
+        assert!(index.is_empty());
+        assert_eq!(line(&index, 0x06), None);
+    }
+}
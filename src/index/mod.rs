@@ -0,0 +1,301 @@
+use kerbalobjects::ko::{self, sections::DataIdx};
+use kerbalobjects::ksm::sections::{ArgIndex, CodeSection, CodeType};
+use kerbalobjects::ksm::{Instr, KSMFile};
+use kerbalobjects::{KOSValue, Opcode};
+use std::collections::HashMap;
+use std::error::Error;
+
+mod debug;
+pub use debug::DebugRangeIndex;
+
+/// The size of the magic number that every decompressed KSM file begins with
+const KSM_MAGIC_SIZE: usize = 4;
+/// The size of the argument section's header, which argument indices count from the start of
+const ARG_SECTION_HEADER_SIZE: usize = 3;
+
+/// Where an instruction lives, and what it is called, computed once for the whole file
+#[derive(Debug, Clone)]
+pub struct InstrLocation {
+    /// The label this instruction is displayed with
+    pub label: String,
+    /// The number of labelled instructions before this one in the same code section
+    pub local_index: usize,
+    /// The byte address of this instruction, counted the same way as the debug section ranges
+    pub addr: usize,
+    /// The size of this instruction in bytes
+    pub size: usize,
+    /// If this instruction is a label reset (lbrt), which gives the next instruction its label
+    pub is_label_reset: bool,
+}
+
+/// A KSM file along with lookup tables for everything that disassembly, symbol search, and
+/// comparisons need to look up for each instruction
+///
+/// This is built once per file, so that none of these have to be recomputed per instruction.
+pub struct IndexedKsm<'a> {
+    ksmfile: &'a KSMFile,
+    index_width: usize,
+    max_operands: usize,
+    code_start: usize,
+    arguments_end: usize,
+    values: HashMap<usize, &'a KOSValue>,
+    sections: Vec<Vec<InstrLocation>>,
+    labels: HashMap<usize, String>,
+    debug_ranges: DebugRangeIndex<'a>,
+}
+
+impl<'a> IndexedKsm<'a> {
+    pub fn new(ksmfile: &'a KSMFile) -> Self {
+        let index_width = ksmfile.arg_section.num_index_bytes() as usize;
+
+        let values: HashMap<usize, &KOSValue> = indexed_arguments(ksmfile).collect();
+        let arguments_end = indexed_arguments(ksmfile)
+            .last()
+            .map_or(ARG_SECTION_HEADER_SIZE, |(index, value)| {
+                index + value.size_bytes()
+            });
+
+        let max_operands = ksmfile
+            .code_sections()
+            .flat_map(|code_section| code_section.instructions())
+            .map(instr_operand_count)
+            .max()
+            .unwrap_or(0);
+
+        let mut indexed = IndexedKsm {
+            ksmfile,
+            index_width,
+            max_operands,
+            // The code sections come right after the magic and the argument section
+            code_start: KSM_MAGIC_SIZE + arguments_end,
+            arguments_end,
+            values,
+            sections: Vec::new(),
+            labels: HashMap::new(),
+            debug_ranges: DebugRangeIndex::new(&ksmfile.debug_section),
+        };
+
+        let mut index = 1;
+        let mut addr = 0;
+
+        for code_section in ksmfile.code_sections() {
+            let (locations, new_index, new_addr) =
+                indexed.index_code_section(code_section, index, addr);
+
+            for location in locations.iter().filter(|l| !l.is_label_reset) {
+                indexed.labels.insert(location.addr, location.label.clone());
+            }
+
+            indexed.sections.push(locations);
+
+            index = new_index;
+            addr = new_addr;
+        }
+
+        indexed
+    }
+
+    /// Computes the label and address of every instruction in a code section, the same way
+    /// the kOS compiler numbers them
+    fn index_code_section(
+        &self,
+        code_section: &CodeSection,
+        start_index: i32,
+        start_addr: usize,
+    ) -> (Vec<InstrLocation>, i32, usize) {
+        let mut locations = Vec::with_capacity(code_section.instructions().len());
+
+        let mut label = String::from("@000001");
+        let mut index = start_index;
+        let mut addr = start_addr + 2; // Offsets for the header bytes
+        let mut local_index = 0;
+
+        for instr in code_section.instructions() {
+            let size = self.instr_size(instr);
+            let is_label_reset = instr_opcode(instr) == Opcode::Lbrt;
+
+            locations.push(InstrLocation {
+                label: label.clone(),
+                local_index,
+                addr,
+                size,
+                is_label_reset,
+            });
+
+            if is_label_reset {
+                if let &Instr::OneOp(_, op) = instr {
+                    if let Some(KOSValue::String(s)) = self.value(op) {
+                        label = s.clone();
+
+                        if label.starts_with('@') {
+                            // Makes @0013 @000013
+                            label.insert_str(1, "00");
+                        }
+                    }
+
+                    label.truncate(7);
+                }
+            }
+            // If it isn't a label reset
+            else {
+                index += 1;
+                local_index += 1;
+                label = format!("@{:>06}", index);
+            }
+
+            addr += size;
+        }
+
+        (locations, index, addr)
+    }
+
+    /// The KSM file this index was built for
+    pub fn ksmfile(&self) -> &'a KSMFile {
+        self.ksmfile
+    }
+
+    /// The number of bytes each argument index takes up
+    pub fn index_width(&self) -> usize {
+        self.index_width
+    }
+
+    /// The offset in the decompressed file of the byte at an instruction address, since addresses
+    /// are counted from the start of the first code section
+    pub fn file_offset(&self, addr: usize) -> usize {
+        self.code_start + addr
+    }
+
+    /// The largest number of operands that any instruction in the file has
+    pub fn max_operands(&self) -> usize {
+        self.max_operands
+    }
+
+    /// Looks up the value an instruction operand refers to
+    pub fn value(&self, op: ArgIndex) -> Option<&'a KOSValue> {
+        self.values.get(&usize::from(op)).copied()
+    }
+
+    /// Every argument along with its index, in the order they appear in the argument section
+    pub fn arguments(&self) -> impl Iterator<Item = (usize, &'a KOSValue)> + 'a {
+        indexed_arguments(self.ksmfile)
+    }
+
+    /// The index just past the last argument, which is where another argument would go
+    pub fn arguments_end(&self) -> usize {
+        self.arguments_end
+    }
+
+    /// Gets the display name of a code section, which for functions is the name given by its first label reset
+    pub fn section_name(&self, code_section: &CodeSection) -> Result<&'a str, Box<dyn Error>> {
+        let name = match code_section.section_type {
+            CodeType::Main => "MAIN",
+            CodeType::Initialization => "INIT",
+            CodeType::Function => {
+                match code_section.instructions().next() {
+                    Some(&Instr::OneOp(opcode, op1)) => {
+                        if opcode == Opcode::Lbrt {
+                            let operand = self.value(op1).ok_or(format!(
+                                "Instruction number {} references invalid argument index: {:x}",
+                                0,
+                                usize::from(op1)
+                            ))?;
+
+                            match operand {
+                                KOSValue::String(s) | KOSValue::StringValue(s) => {
+                                    // If this is a kOS-compiled function
+                                    if s.contains('`') {
+                                        s.split('`').next().unwrap()
+                                    } else {
+                                        s
+                                    }
+                                }
+                                _ => "FUNC",
+                            }
+                        } else {
+                            "FUNC"
+                        }
+                    }
+                    _ => "FUNC",
+                }
+            }
+        };
+
+        Ok(name)
+    }
+
+    /// The locations of each instruction in the code section at the given position in the file
+    pub fn locations(&self, section: usize) -> &[InstrLocation] {
+        &self.sections[section]
+    }
+
+    /// Finds the label of the instruction that starts at the given address
+    pub fn label_at(&self, addr: usize) -> Option<&str> {
+        self.labels.get(&addr).map(|label| label.as_str())
+    }
+
+    /// The lookup structure for the debug section's address ranges
+    pub fn debug_ranges(&self) -> &DebugRangeIndex<'a> {
+        &self.debug_ranges
+    }
+
+    /// The size in bytes of a single instruction, including its operands
+    pub fn instr_size(&self, instr: &Instr) -> usize {
+        1 + instr_operand_count(instr) * self.index_width
+    }
+}
+
+/// Pairs every argument with its index, which is counted from the start of the argument section
+fn indexed_arguments(ksmfile: &KSMFile) -> impl Iterator<Item = (usize, &KOSValue)> {
+    let mut arg_index = ARG_SECTION_HEADER_SIZE;
+
+    ksmfile.arg_section.arguments().map(move |value| {
+        let indexed = (arg_index, value);
+        arg_index += value.size_bytes();
+        indexed
+    })
+}
+
+/// Gets the opcode of any KSM instruction
+pub fn instr_opcode(instr: &Instr) -> Opcode {
+    match instr {
+        Instr::ZeroOp(opcode) => *opcode,
+        Instr::OneOp(opcode, _) => *opcode,
+        Instr::TwoOp(opcode, _, _) => *opcode,
+    }
+}
+
+/// Gets the argument indices of any KSM instruction's operands, in order
+pub fn instr_operands(instr: &Instr) -> Vec<ArgIndex> {
+    match instr {
+        Instr::ZeroOp(_) => vec![],
+        Instr::OneOp(_, op1) => vec![*op1],
+        Instr::TwoOp(_, op1, op2) => vec![*op1, *op2],
+    }
+}
+
+/// Gets the number of operands of any KSM instruction
+pub fn instr_operand_count(instr: &Instr) -> usize {
+    match instr {
+        Instr::ZeroOp(_) => 0,
+        Instr::OneOp(_, _) => 1,
+        Instr::TwoOp(_, _, _) => 2,
+    }
+}
+
+/// Gets the data section indices of any KO instruction's operands, in order
+pub fn ko_instr_operands(instr: &ko::Instr) -> Vec<DataIdx> {
+    match instr {
+        ko::Instr::ZeroOp(_) => vec![],
+        ko::Instr::OneOp(_, op1) => vec![*op1],
+        ko::Instr::TwoOp(_, op1, op2) => vec![*op1, *op2],
+    }
+}
+
+/// Gets the number of operands of any KO instruction
+pub fn ko_instr_operand_count(instr: &ko::Instr) -> usize {
+    match instr {
+        ko::Instr::ZeroOp(_) => 0,
+        ko::Instr::OneOp(_, _) => 1,
+        ko::Instr::TwoOp(_, _, _) => 2,
+    }
+}
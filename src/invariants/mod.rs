@@ -0,0 +1,256 @@
+//! Checks a file against a TOML list of things that are expected to be true about it, so that
+//! tests of a compiler's output don't have to grep through a dump
+
+use kerbalobjects::KOSValue;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use termcolor::{ColorSpec, WriteColor};
+
+use crate::index::IndexedKsm;
+use crate::{ParsedFile, GREEN_COLOR, LIGHT_RED_COLOR};
+
+type InvariantResult<T> = Result<T, Box<dyn Error>>;
+
+/// Everything an invariants file can ask for
+///
+/// ```toml
+/// symbols = ["main", "helper"]
+/// strings = ["Hello, world"]
+///
+/// [[sections]]
+/// name = "MAIN"
+/// max_size = 4096
+///
+/// [[instructions]]
+/// section = "MAIN"
+/// min = 10
+/// max = 500
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Invariants {
+    /// Symbols that have to exist, which for KSM files are the names of code sections
+    #[serde(default)]
+    symbols: Vec<String>,
+    /// Strings that have to appear in the arguments, data, or string tables
+    #[serde(default)]
+    strings: Vec<String>,
+    #[serde(default)]
+    sections: Vec<SectionInvariant>,
+    #[serde(default)]
+    instructions: Vec<InstructionInvariant>,
+}
+
+/// A section that has to exist, and optionally be within a range of sizes in bytes
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SectionInvariant {
+    name: String,
+    min_size: Option<usize>,
+    max_size: Option<usize>,
+}
+
+/// A range that the number of instructions in one section, or in the whole file, has to be in
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InstructionInvariant {
+    section: Option<String>,
+    min: Option<usize>,
+    max: Option<usize>,
+}
+
+/// What is known about the file, gathered once so that every invariant can be checked against it
+#[derive(Debug, Default)]
+struct Facts {
+    symbols: BTreeSet<String>,
+    strings: BTreeSet<String>,
+    /// The size in bytes of each section
+    section_sizes: BTreeMap<String, usize>,
+    /// The number of instructions in each code section
+    instruction_counts: BTreeMap<String, usize>,
+}
+
+impl Facts {
+    fn add_strings<'a>(&mut self, values: impl Iterator<Item = &'a KOSValue>) {
+        for value in values {
+            if let KOSValue::String(s) | KOSValue::StringValue(s) = value {
+                self.strings.insert(s.clone());
+            }
+        }
+    }
+
+    fn gather(file: &ParsedFile) -> InvariantResult<Self> {
+        let mut facts = Facts::default();
+
+        if let Some(ksm) = file.ksm() {
+            let index = IndexedKsm::new(ksm);
+
+            for (section_index, code_section) in ksm.code_sections().enumerate() {
+                let name = index.section_name(code_section)?.to_string();
+                let locations = index.locations(section_index);
+
+                // Every code section begins with a two byte marker
+                let size = 2 + locations
+                    .iter()
+                    .map(|location| location.size)
+                    .sum::<usize>();
+
+                *facts.section_sizes.entry(name.clone()).or_default() += size;
+                *facts.instruction_counts.entry(name.clone()).or_default() += locations.len();
+                facts.symbols.insert(name);
+            }
+
+            facts.add_strings(ksm.arg_section.arguments());
+        } else if let Some(kofile) = file.ko() {
+            for header in kofile.section_headers() {
+                if let Some(name) = kofile.get_header_name(header) {
+                    facts
+                        .section_sizes
+                        .insert(name.clone(), header.size as usize);
+                }
+            }
+
+            for func_section in kofile.func_sections() {
+                let name = kofile
+                    .get_section_header(func_section.section_index())
+                    .and_then(|header| kofile.get_header_name(header))
+                    .cloned()
+                    .unwrap_or_default();
+
+                *facts.instruction_counts.entry(name).or_default() +=
+                    func_section.instructions().count();
+            }
+
+            if let Some(symstrtab) = kofile.str_tab_by_name(".symstrtab") {
+                for symbol_table in kofile.sym_tabs() {
+                    for symbol in symbol_table.symbols() {
+                        if let Some(name) = symstrtab.get(symbol.name_idx) {
+                            facts.symbols.insert(name.clone());
+                        }
+                    }
+                }
+            }
+
+            facts.add_strings(
+                kofile
+                    .data_sections()
+                    .flat_map(|data_section| data_section.data()),
+            );
+
+            for strtab in kofile.str_tabs() {
+                facts.strings.extend(strtab.strings().cloned());
+            }
+        }
+
+        Ok(facts)
+    }
+}
+
+/// Describes a range for a message, like "between 10 and 500" or "at most 4096"
+fn describe_range(min: Option<usize>, max: Option<usize>) -> String {
+    match (min, max) {
+        (Some(min), Some(max)) => format!("between {} and {}", min, max),
+        (Some(min), None) => format!("at least {}", min),
+        (None, Some(max)) => format!("at most {}", max),
+        (None, None) => String::from("any number"),
+    }
+}
+
+fn in_range(value: usize, min: Option<usize>, max: Option<usize>) -> bool {
+    min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max)
+}
+
+/// Works out whether each invariant holds, giving a description of each one along with it
+fn evaluate(invariants: &Invariants, facts: &Facts) -> Vec<(bool, String)> {
+    let mut results = Vec::new();
+
+    for symbol in &invariants.symbols {
+        results.push((
+            facts.symbols.contains(symbol),
+            format!("symbol {} exists", symbol),
+        ));
+    }
+
+    for s in &invariants.strings {
+        results.push((
+            facts.strings.contains(s),
+            format!("string {:?} is present", s),
+        ));
+    }
+
+    for section in &invariants.sections {
+        let range = describe_range(section.min_size, section.max_size);
+
+        results.push(match facts.section_sizes.get(&section.name) {
+            Some(&size) => (
+                in_range(size, section.min_size, section.max_size),
+                format!(
+                    "section {} is {} bytes, expected {}",
+                    section.name, size, range
+                ),
+            ),
+            None => (false, format!("section {} exists", section.name)),
+        });
+    }
+
+    for instructions in &invariants.instructions {
+        let range = describe_range(instructions.min, instructions.max);
+
+        let (count, place) = match &instructions.section {
+            Some(name) => match facts.instruction_counts.get(name) {
+                Some(&count) => (count, format!("section {}", name)),
+                None => {
+                    results.push((false, format!("section {} exists", name)));
+                    continue;
+                }
+            },
+            None => (
+                facts.instruction_counts.values().sum(),
+                String::from("the file"),
+            ),
+        };
+
+        results.push((
+            in_range(count, instructions.min, instructions.max),
+            format!("{} has {} instructions, expected {}", place, count, range),
+        ));
+    }
+
+    results
+}
+
+/// Checks the file against every invariant in the TOML file, failing if any of them don't hold
+pub fn check(stream: &mut dyn WriteColor, file: &ParsedFile, path: &Path) -> InvariantResult<()> {
+    let invariants: Invariants = toml::from_str(&fs::read_to_string(path)?)
+        .map_err(|e| format!("Failed to read invariants from {}: {}", path.display(), e))?;
+
+    let facts = Facts::gather(file)?;
+    let results = evaluate(&invariants, &facts);
+
+    let mut pass = ColorSpec::new();
+    pass.set_fg(Some(GREEN_COLOR));
+    let mut fail = ColorSpec::new();
+    fail.set_fg(Some(LIGHT_RED_COLOR));
+
+    writeln!(stream, "\nInvariants from {}:", path.display())?;
+
+    for (holds, description) in &results {
+        stream.set_color(if *holds { &pass } else { &fail })?;
+        write!(stream, "  {}", if *holds { "PASS" } else { "FAIL" })?;
+        stream.reset()?;
+        writeln!(stream, "  {}", description)?;
+    }
+
+    let num_failed = results.iter().filter(|(holds, _)| !holds).count();
+
+    if num_failed > 0 {
+        return Err(format!("{} of {} invariant(s) failed", num_failed, results.len()).into());
+    }
+
+    writeln!(stream, "  All {} invariant(s) hold.", results.len())?;
+
+    Ok(())
+}
@@ -1,18 +1,57 @@
-use clap::Parser;
-use kerbalobjects::ko::KOFile;
-use kerbalobjects::ksm::KSMFile;
-use kerbalobjects::BufferIterator;
-use std::io::Write;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::path::PathBuf;
 use std::{error::Error, fs};
-use termcolor::{Color, ColorSpec, StandardStream};
+use termcolor::{Color, ColorSpec, NoColor, StandardStream, WriteColor};
+
+pub mod analysis;
+
+pub mod asm;
+
+mod builder;
+pub use builder::CLIConfigBuilder;
+
+mod cancel;
+
+mod carve;
+
+mod compare;
+
+mod corpus;
 
 mod fio;
-use fio::{determine_file_type, FileType};
+
+mod help;
+
+mod history;
+
+mod invariants;
+
+pub mod index;
+use fio::FileType;
+
+pub mod mem;
+
+pub mod model;
+
+mod normalize;
+
+mod pipeline;
+pub use pipeline::{analyze, load_file, render, AnalysisOpts, ParsedFile, Report};
 
 mod output;
-use output::KOFileDebug;
-use output::KSMFileDebug;
+
+mod rpc;
+
+mod summary;
+
+mod tags;
+
+#[cfg(test)]
+mod testing;
+
+mod version;
 
 pub static NO_COLOR: Color = Color::Rgb(255, 255, 255);
 
@@ -25,48 +64,625 @@ pub static LIGHT_RED_COLOR: Color = Color::Rgb(255, 147, 147);
 pub static GREEN_COLOR: Color = Color::Rgb(129, 181, 154);
 
 pub fn run(config: &CLIConfig) -> Result<(), Box<dyn Error>> {
+    if config.rpc {
+        return rpc::serve(std::io::stdin().lock(), &mut std::io::stdout().lock());
+    }
+
+    let listing = match (&config.latex, &config.typst) {
+        (Some(path), _) => Some((path, output::ListingFormat::Latex)),
+        (None, Some(path)) => Some((path, output::ListingFormat::Typst)),
+        (None, None) => None,
+    };
+
+    if let Some((listing_path, format)) = listing {
+        let mut stream = output::ListingColorWriter::new(
+            BufWriter::new(File::create(listing_path)?),
+            format,
+            &output::report_title(config),
+        )?;
+
+        let result = run_with(&mut stream, config);
+        stream.finish()?;
+
+        return result;
+    }
+
+    if let Some(html_path) = &config.color_html {
+        let mut stream = output::HtmlColorWriter::new(
+            BufWriter::new(File::create(html_path)?),
+            &output::report_title(config),
+        )?;
+
+        // The page is still finished if dumping fails, so that whatever was written can be read
+        let result = run_with(&mut stream, config);
+        stream.finish()?;
+
+        return result;
+    }
+
+    if let Some(tags_path) = &config.ctags {
+        return tags::write_tags(config, tags_path);
+    }
+
+    // When extracting a section, the output file is where the section's bytes go instead
+    if let (Some(output_path), None) = (&config.output, &config.extract_section) {
+        return run_to(BufWriter::new(File::create(output_path)?), config);
+    }
+
     let mut stream = StandardStream::stdout(termcolor::ColorChoice::Auto);
 
+    run_with(&mut stream, config)
+}
+
+/// Does everything run does, but writes the output without any colors to the given writer,
+/// like a file
+pub fn run_to(sink: impl Write, config: &CLIConfig) -> Result<(), Box<dyn Error>> {
+    let mut stream = NoColor::new(sink);
+
+    run_with(&mut stream, config)?;
+    stream.flush()?;
+
+    Ok(())
+}
+
+/// Does everything run does, but writes the output to the given stream instead of the terminal
+pub fn run_with(stream: &mut dyn WriteColor, config: &CLIConfig) -> Result<(), Box<dyn Error>> {
     let mut no_color = ColorSpec::new();
     no_color.set_fg(Some(NO_COLOR));
 
-    writeln!(stream, "kDump version {}", VERSION)?;
+    if config.version_json {
+        return version::write_version_json(stream);
+    }
+
+    if config.help_json {
+        return help::write_help_json(stream);
+    }
+
+    if config.schema {
+        serde_json::to_writer_pretty(&mut *stream, &model::record_schema())?;
+        writeln!(stream)?;
+
+        return Ok(());
+    }
+
+    // Output that is meant to be read by other programs can't have anything else mixed in
+    if !config.machine_readable() {
+        writeln!(stream, "kDump version {}", VERSION)?;
+    }
+
+    if let Some(command) = &config.command {
+        return match command {
+            Command::Carve(args) => carve::carve(stream, args, config),
+            Command::History(args) => history::history(stream, args, config),
+            Command::Diff(args) => compare::compare(stream, args),
+            Command::Normalize(args) => normalize::normalize(stream, args, config),
+            Command::Corpus(CorpusArgs {
+                command: CorpusCommand::Stats(args),
+            }) => corpus::corpus_stats(stream, args),
+        };
+    }
+
+    if let [a, b] = config.repro_check.as_slice() {
+        return compare::repro_check(stream, a, b, config.float_epsilon);
+    }
+
+    if let Some(csv_path) = &config.csv_summary {
+        return summary::write_csv_summary(stream, config, csv_path);
+    }
+
+    if config.summary {
+        return summary::write_summary_lines(stream, config);
+    }
+
+    let file_path = config
+        .file_path
+        .as_ref()
+        .ok_or("No input file was provided")?;
+
+    let raw_contents = fs::read(file_path)?;
+    let file_type = pipeline::detect_file_type(&raw_contents, config.assume)?;
+
+    let is_ksm = matches!(
+        file_type,
+        FileType::KerbalMachineCode | FileType::UncompressedKerbalMachineCode
+    );
+    let skipped = config.inapplicable_flags(is_ksm);
+    let (this_type, other_type) = if is_ksm { ("KSM", "KO") } else { ("KO", "KSM") };
+
+    if !skipped.is_empty() {
+        let flags = format!(
+            "{} only {} to {} files",
+            skipped.join(", "),
+            if skipped.len() == 1 {
+                "applies"
+            } else {
+                "apply"
+            },
+            other_type
+        );
+
+        if config.strict_flags || config.check_args {
+            return Err(format!("{}, and this is a {} file", flags, this_type).into());
+        }
+
+        let warning = format!(
+            "{}, so {} skipped for this {} file",
+            flags,
+            if skipped.len() == 1 {
+                "it was"
+            } else {
+                "they were"
+            },
+            this_type
+        );
+
+        // Machine readable output keeps stderr to one JSON object per line
+        if config.machine_readable() {
+            output::write_diagnostics(&[model::Diagnostic::warning("inapplicable_flag", warning)])?;
+        } else {
+            eprintln!("Warning: {}", warning);
+        }
+    }
+
+    // Checked before anything is dumped, so that a wrapper script can find out cheaply
+    if config.check_args {
+        writeln!(
+            stream,
+            "Every flag given applies to this {} file",
+            this_type
+        )?;
+        return Ok(());
+    }
+
+    if is_ksm {
+        let scan = if file_type == FileType::KerbalMachineCode {
+            fio::scan_gzip(&raw_contents)
+        } else {
+            fio::GzipScan::default()
+        };
+
+        if scan.has_anomalies() && !config.machine_readable() {
+            output::dump_gzip_anomalies(stream, &scan, config.gzip_members, config.human_readable)?;
+        }
+
+        // The outline is meant for files the parser can't read, so it has to come before parsing
+        if config.outline {
+            let payload = fio::ksm_payload(&raw_contents)?;
+            let layout = fio::layout::scan_ksm(&payload, None);
+
+            return output::dump_ksm_outline(stream, &layout);
+        }
+
+        // Also before parsing, since a wrong index width is usually why parsing fails
+        if config.try_widths {
+            return output::dump_width_trials(stream, &fio::ksm_payload(&raw_contents)?);
+        }
+
+        if config.hex_dump {
+            output::dump_ksm_hex(stream, &fio::ksm_payload(&raw_contents)?)?;
+        }
+
+        if let Some(name) = &config.extract_section {
+            let payload = fio::ksm_payload(&raw_contents)?;
+            let layout = fio::layout::scan_ksm(&payload, None);
+            let bytes = layout
+                .find_region(name)
+                .map(|region| &payload[region.offset..region.offset + region.size]);
+
+            extract_section(stream, config, name, bytes)?;
+        }
+
+        // Packers bundle several scripts into one file this way, so each one is dumped
+        if scan.members.len() > 1 {
+            return dump_gzip_members(stream, config, &scan);
+        }
+    }
+
+    let file = pipeline::parse_file(raw_contents, file_type)?;
+
+    if let (Some(kofile), Some(contents)) = (file.ko(), file.ko_parser_input()) {
+        if config.hex_dump || config.hex_dump_section.is_some() {
+            output::dump_ko_hex(stream, kofile, contents, config.hex_dump_section.as_deref())?;
+        }
+
+        if let Some(name) = &config.extract_section {
+            let bytes =
+                fio::layout::ko_section_range(kofile, name).and_then(|range| contents.get(range));
+
+            extract_section(stream, config, name, bytes)?;
+        }
+    }
+
+    if let Some(path) = &config.assert {
+        return invariants::check(stream, &file, path);
+    }
+
+    let report = analyze(&file, &AnalysisOpts::from(config));
+    let _interrupts = cancel::catch_interrupts();
+
+    render(&report, config, stream)
+}
+
+/// Dumps every member of a KSM file made of several concatenated gzip members as a KSM file of its own
+///
+/// A member that can't be read as a KSM file doesn't stop the rest from being dumped.
+fn dump_gzip_members(
+    stream: &mut dyn WriteColor,
+    config: &CLIConfig,
+    scan: &fio::GzipScan,
+) -> Result<(), Box<dyn Error>> {
+    let _interrupts = cancel::catch_interrupts();
 
-    let raw_contents = fs::read(&config.file_path)?;
-    let mut raw_contents_iter = BufferIterator::new(&raw_contents);
+    for (index, member) in scan.members.iter().enumerate() {
+        if !config.machine_readable() {
+            writeln!(stream, "\nGzip member {} at 0x{:x}:", index, member.offset)?;
+        }
+
+        let file = fio::determine_file_type(&member.contents)
+            .and_then(|file_type| pipeline::parse_file(member.contents.clone(), file_type));
 
-    let file_type = determine_file_type(&raw_contents)?;
+        match file {
+            Ok(file) => {
+                let report = analyze(&file, &AnalysisOpts::from(config));
+
+                render(&report, config, stream)?;
+            }
+            Err(e) => writeln!(stream, "  Could not be read as a KSM file: {}", e)?,
+        }
+    }
+
+    Ok(())
+}
 
-    match file_type {
-        FileType::KerbalMachineCode => {
-            let ksm = KSMFile::parse(&mut raw_contents_iter)?;
-            let ksm_debug = KSMFileDebug::new(ksm);
+/// Writes the bytes of the section given to --extract-section to the file given to --output
+fn extract_section(
+    stream: &mut dyn WriteColor,
+    config: &CLIConfig,
+    name: &str,
+    bytes: Option<&[u8]>,
+) -> Result<(), Box<dyn Error>> {
+    let bytes = bytes.ok_or(format!("No section named {} was found", name))?;
+    let output = config
+        .output
+        .as_ref()
+        .ok_or("--extract-section needs a file to write to, given with -o")?;
+
+    fs::write(output, bytes)?;
+
+    writeln!(
+        stream,
+        "Extracted {} bytes of {} to {}",
+        bytes.len(),
+        name,
+        output.display()
+    )?;
+
+    Ok(())
+}
+
+/// The layouts of other tools that --style can imitate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputStyle {
+    /// GNU objdump's disassembly layout
+    Objdump,
+    /// GNU readelf's header, section header, and symbol table layout
+    Readelf,
+}
+
+/// The order KSM code sections are disassembled in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SectionOrder {
+    /// The order they are stored in the file
+    #[default]
+    File,
+    /// MAIN, then INIT, then the functions, which is the order they run in
+    Logical,
+}
+
+/// How the bytes of strings in a file are turned into text
+///
+/// The KSM reader turns every byte into the character with the same number, which is Latin-1,
+/// so text that was written as UTF-8 has to be decoded again.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StringEncoding {
+    /// Strings are UTF-8, unless their bytes aren't valid UTF-8, in which case they are Latin-1
+    #[default]
+    Utf8,
+    /// Every byte is one character
+    Latin1,
+}
+
+/// The kinds of text that are colored, which --no-color-for can leave uncolored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorRole {
+    /// Instruction labels, and the symbols that begin KO functions
+    Labels,
+    /// Source line numbers next to disassembly
+    Lines,
+    /// Instruction mnemonics
+    Mnemonics,
+    /// Variable names and other operand values
+    Variables,
+    /// Section and symbol names in KO tables
+    Names,
+    /// Value types, symbol types, and bindings
+    Types,
+    /// Indices, sizes, and symbol values in KO tables
+    Numbers,
+}
+
+/// How byte counts should be rendered when --human-readable is given
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SizeFormat {
+    /// Binary units, like 12.4 KiB
+    Units,
+    /// Thousands separators, like 12,700 bytes
+    Separators,
+}
+
+/// Which operands --show-arg-indices shows the argument index of, and where
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ArgIndexPlacement {
+    /// The first operand's, after all of the operands
+    First,
+    /// Every operand's, right before its value
+    Each,
+}
+
+/// Where --start-address or --stop-address is, given either as a label or as a byte offset in the
+/// decompressed file, like the ones --show-addr prints
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressBound {
+    Label(String),
+    Offset(usize),
+}
 
-            ksm_debug.dump(&mut stream, config)?;
+impl std::str::FromStr for AddressBound {
+    type Err = String;
 
-            Ok(())
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(String::from("expected a label or a byte offset"));
         }
-        FileType::KerbalObject => {
-            let kofile = KOFile::parse(&mut raw_contents_iter)?;
-            let ko_debug = KOFileDebug::new(kofile);
 
-            ko_debug.dump(&mut stream, config)?;
+        if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            return usize::from_str_radix(hex, 16)
+                .map(AddressBound::Offset)
+                .map_err(|e| format!("invalid byte offset {}: {}", s, e));
+        }
+
+        if let Ok(offset) = s.parse() {
+            return Ok(AddressBound::Offset(offset));
+        }
+
+        // Labels are shown 7 characters long, but can be given the short way, like @2200
+        let label = match s.strip_prefix('@') {
+            Some(digits) if digits.bytes().all(|b| b.is_ascii_digit()) => {
+                format!("@{:0>6}", digits)
+            }
+            _ => s.to_string(),
+        };
+
+        Ok(AddressBound::Label(label))
+    }
+}
+
+/// A single KSM code section picked with --section
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionSelector {
+    Main,
+    Init,
+    /// The function section at this position among the function sections, counted from 0
+    Function(usize),
+}
 
-            Ok(())
+impl std::str::FromStr for SectionSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let upper = s.to_ascii_uppercase();
+
+        match upper.as_str() {
+            "MAIN" => Ok(SectionSelector::Main),
+            "INIT" => Ok(SectionSelector::Init),
+            _ => match upper.strip_prefix("FUNC:") {
+                Some(n) => n
+                    .parse()
+                    .map(SectionSelector::Function)
+                    .map_err(|e| format!("invalid function number {}: {}", n, e)),
+                None => Err(String::from("expected MAIN, INIT, or FUNC:N")),
+            },
         }
-        // If we have no idea what the heck the file is
-        FileType::Unknown => Err("File type not recognized.".into()),
     }
 }
 
+/// A regular expression given with --grep, which instructions are matched against
+#[derive(Debug, Clone)]
+pub struct GrepPattern(pub regex::Regex);
+
+impl std::str::FromStr for GrepPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        regex::Regex::new(s)
+            .map(GrepPattern)
+            .map_err(|e| format!("invalid pattern: {}", e))
+    }
+}
+
+impl From<regex::Regex> for GrepPattern {
+    fn from(regex: regex::Regex) -> Self {
+        GrepPattern(regex)
+    }
+}
+
+/// The file types that can be forced using --assume
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AssumedType {
+    Ksm,
+    Ko,
+}
+
+/// The subcommands that do something other than dumping a single file
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Scans an arbitrary binary for embedded KSM and KO files
+    Carve(CarveArgs),
+    /// Tracks one function across several builds of a KSM file
+    History(HistoryArgs),
+    /// Compares two builds of a KSM or KO file section by section
+    Diff(DiffArgs),
+    /// Works with a whole directory of KSM and KO files at once
+    Corpus(CorpusArgs),
+    /// Rewrites a KSM file in a canonical form, for reproducible builds
+    Normalize(NormalizeArgs),
+}
+
+/// The settings for the normalize subcommand
+#[derive(Debug, Args)]
+pub struct NormalizeArgs {
+    /// The file to normalize
+    #[arg(value_name = "IN", help = "The KSM file to normalize")]
+    pub input: PathBuf,
+    /// Where to write the normalized file
+    #[arg(
+        short = 'o',
+        long = "output",
+        value_name = "OUT",
+        help = "Where to write the normalized KSM file"
+    )]
+    pub output: PathBuf,
+}
+
+/// The settings for the corpus subcommand
+#[derive(Debug, Args)]
+pub struct CorpusArgs {
+    #[command(subcommand)]
+    pub command: CorpusCommand,
+}
+
+/// What to do with a corpus
+#[derive(Debug, Subcommand)]
+pub enum CorpusCommand {
+    /// Reports opcode frequencies, function sizes, compilers, and common strings across every file
+    Stats(CorpusStatsArgs),
+}
+
+/// The formats a corpus report can be written in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CorpusFormat {
+    #[default]
+    Json,
+    /// One metric,name,value row for each count
+    Csv,
+}
+
+/// The settings for the corpus stats subcommand
+#[derive(Debug, Args)]
+pub struct CorpusStatsArgs {
+    /// The directory to search for KSM and KO files, including every directory inside of it
+    #[arg(
+        value_name = "DIR",
+        help = "The directory of KSM and KO files to gather statistics for"
+    )]
+    pub dir: PathBuf,
+    /// The format to write the report in
+    #[arg(
+        long = "format",
+        value_enum,
+        default_value_t = CorpusFormat::Json,
+        help = "Writes the report as JSON or CSV"
+    )]
+    pub format: CorpusFormat,
+    /// The number of the most common string constants to report
+    #[arg(
+        long = "top-strings",
+        value_name = "COUNT",
+        default_value_t = 50,
+        help = "The number of the most common string constants to include"
+    )]
+    pub top_strings: usize,
+}
+
+/// The settings for the diff subcommand
+#[derive(Debug, Args)]
+pub struct DiffArgs {
+    /// The earlier build
+    #[arg(value_name = "OLD", help = "The earlier build of the file")]
+    pub old: PathBuf,
+    /// The later build
+    #[arg(value_name = "NEW", help = "The later build of the file")]
+    pub new: PathBuf,
+    /// How far apart two floating point values can be and still count as the same
+    #[arg(
+        long = "float-epsilon",
+        value_name = "EPSILON",
+        help = "Counts floating point values at most EPSILON apart as the same, so builds that only differ in the last digit aren't shown as changed"
+    )]
+    pub float_epsilon: Option<f64>,
+}
+
+/// The settings for the history subcommand
+#[derive(Debug, Args)]
+pub struct HistoryArgs {
+    /// The builds to compare, from oldest to newest
+    #[arg(
+        value_name = "FILES",
+        num_args = 2..,
+        required = true,
+        help = "The KSM files to compare, from oldest to newest"
+    )]
+    pub files: Vec<PathBuf>,
+    /// The name of the function to follow
+    #[arg(
+        long = "symbol",
+        value_name = "NAME",
+        help = "The name of the function to track across the files"
+    )]
+    pub symbol: String,
+}
+
+/// The settings for the carve subcommand
+#[derive(Debug, Args)]
+pub struct CarveArgs {
+    /// The file to search through
+    #[arg(
+        value_name = "BLOB",
+        help = "The binary file to search for KSM and KO files"
+    )]
+    pub blob_path: PathBuf,
+    /// Where to write any files that are found
+    #[arg(
+        long = "extract",
+        value_name = "DIR",
+        help = "Extracts every file that is found into the given directory"
+    )]
+    pub extract: Option<PathBuf>,
+}
+
 /// This structure controls all the settings that make this program perform differently
 /// These represent command line arguments read in by clap
-#[derive(Debug, Parser)]
-#[command(name = "kDump Utility", author, version, about, long_about = None)]
+#[derive(Debug, Default, Parser)]
+#[command(
+    name = "kDump Utility",
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 pub struct CLIConfig {
-    /// The input file path, which is required
-    #[arg(value_name = "FILE", help = "Sets the input file to use")]
-    pub file_path: PathBuf,
+    /// The subcommand to run instead of dumping a file, if any
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// The input file path, which is required unless a subcommand, --version-json, --help-json, or --schema is used
+    #[arg(
+        value_name = "FILE",
+        help = "Sets the input file to use",
+        required_unless_present_any = ["version_json", "help_json", "schema", "rpc", "repro_check"]
+    )]
+    pub file_path: Option<PathBuf>,
     /// Whether we should disassemble the file's code sections
     /// Conflicts with disassemble_symbol and full-contents
     #[arg(
@@ -82,7 +698,7 @@ pub struct CLIConfig {
     #[arg(
         short = 'd',
         long = "disassemble-symbol",
-        help = "Disassembles at the symbol provided until the end of the section",
+        help = "Disassembles at the symbol provided until the end of the section, or of the function for functions that begin partway through one",
         require_equals = true,
         value_name = "SYMBOL",
         conflicts_with("disassemble"),
@@ -90,19 +706,20 @@ pub struct CLIConfig {
     )]
     pub disassemble_symbol: Option<String>,
     /// Whether we should dump the file headers
-    /// KO only
+    /// For KSM files this is the magic and the argument section header, along with the file's
+    /// sizes, widths, and number of each kind of code section
     #[arg(
         short = 'f',
         long = "file-headers",
-        help = "Displays summary information of the KO file header"
+        help = "Displays summary information of the KO file header, or the header bytes, sizes, index widths, and section counts of a KSM file"
     )]
     pub file_headers: bool,
     /// Whether we should dump the argument section contents
-    /// KSM only
+    /// For KO files this dumps the data sections, which hold operands the same way
     #[arg(
         short = 'a',
         long = "argument-section",
-        help = "Displays the contents of the argument section of a KSM file"
+        help = "Displays the contents of the argument section of a KSM file, or the data sections of a KO file"
     )]
     pub argument_section: bool,
     /// Whether we should display line numbers in disassembled code
@@ -155,12 +772,12 @@ pub struct CLIConfig {
         help = "Displays each relocation data table of a KO file"
     )]
     pub reloc: bool,
-    /// Whether we should display all of the section headers of the file
-    /// KO only
+    /// Whether we should display all of the headers of the file, which for KO files includes the
+    /// section headers and the symbol table
     #[arg(
         short = 'x',
         long = "all-headers",
-        help = "Displays all available KO file header information including the symbol table"
+        help = "Displays all available file header information, including the symbol table of KO files"
     )]
     pub all_headers: bool,
     /// Displays all available meta info of the object file including compiler comments and version information
@@ -191,4 +808,648 @@ pub struct CLIConfig {
         help = "When disassembling, disables showing the label of each instruction"
     )]
     pub show_no_labels: bool,
+    /// Reports peak heap usage and the estimated memory footprint of each parsed structure
+    #[arg(
+        long = "mem-stats",
+        help = "Reports peak allocations, in builds with the alloc-stats feature, and the memory footprint of each structure of the parsed file"
+    )]
+    pub mem_stats: bool,
+    /// How sizes should be displayed in headers, stats, and summaries
+    #[arg(
+        long = "human-readable",
+        value_enum,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        default_missing_value = "units",
+        help = "Displays sizes in human readable units (12.4 KiB), or with thousands separators if =separators is given"
+    )]
+    pub human_readable: Option<SizeFormat>,
+    /// Whether we should group the symbol table by the FILE symbols that introduce each part of it
+    /// KO only
+    #[arg(
+        long = "provenance-symbols",
+        help = "Displays the sections and symbols contributed by each FILE symbol of a KO file"
+    )]
+    pub provenance_symbols: bool,
+    /// Reports how adding this many arguments would affect the argument index width
+    /// KSM only
+    #[arg(
+        long = "simulate-growth",
+        value_name = "N",
+        help = "Reports whether adding N more arguments would force wider argument indices, and how much the code would grow"
+    )]
+    pub simulate_growth: Option<usize>,
+    /// Whether we should check the decoded instructions for inconsistencies
+    #[arg(
+        long = "verify",
+        help = "Checks that every code section takes up as many bytes as the operands its opcodes require"
+    )]
+    pub verify: bool,
+    /// Overrides file type detection
+    #[arg(
+        long = "assume",
+        value_enum,
+        require_equals = true,
+        value_name = "TYPE",
+        help = "Reads the file as the given type regardless of what its contents look like"
+    )]
+    pub assume: Option<AssumedType>,
+    /// Whether we should name data operands after the symbol that refers to them
+    /// KO only
+    #[arg(
+        long = "annotate-data-symbols",
+        help = "When disassembling, shows the name of the symbol that refers to each data operand, if there is one"
+    )]
+    pub annotate_data_symbols: bool,
+    /// Whether the operands of disassembled instructions should be lined up across the whole file
+    /// KSM only
+    #[arg(
+        long = "align-columns",
+        help = "When disassembling, pads operands so that every column lines up vertically across the whole file"
+    )]
+    pub align_columns: bool,
+    /// Whether runs of identical instructions should be shown as a single line
+    /// KSM only
+    #[arg(
+        long = "collapse-repeats",
+        help = "When disassembling, shows runs of identical instructions as a single line with a repeat count"
+    )]
+    pub collapse_repeats: bool,
+    /// Whether labels should be numbered from the start of each function instead of the start of the file
+    /// KSM only
+    #[arg(
+        long = "local-labels",
+        help = "When disassembling, numbers labels relative to the start of each function (fn+0007)"
+    )]
+    pub local_labels: bool,
+    /// Whether the tables of the file should be written as CSV instead of the regular dump
+    #[arg(
+        long = "csv",
+        help = "Writes the symbol and section header tables of a KO file, or the argument section of a KSM file, as CSV"
+    )]
+    pub csv: bool,
+    /// Whether an alphabetical index of every symbol should be printed at the end of the dump
+    #[arg(
+        long = "symbol-index",
+        help = "Prints an alphabetical index of every function and symbol, and the label where each one begins"
+    )]
+    pub symbol_index: bool,
+    /// Whether the setup code the kOS compiler emits at the start of every program should be hidden
+    /// KSM only
+    #[arg(
+        long = "hide-boilerplate",
+        help = "When disassembling, collapses the standard kOS program preamble into a single line"
+    )]
+    pub hide_boilerplate: bool,
+    /// A file to write the full dump to as a standalone HTML page, with operands and symbols linked
+    /// to the tables they refer to
+    #[arg(
+        long = "html",
+        value_name = "FILE",
+        help = "Writes the full dump as an HTML page with cross-linked operands and symbols"
+    )]
+    pub html: Option<PathBuf>,
+    /// Whether to print the control flow graph of every code section as Graphviz DOT
+    #[arg(
+        long = "cfg-dot",
+        help = "Prints the control flow graph of every code section as a Graphviz DOT graph"
+    )]
+    pub cfg_dot: bool,
+    /// Whether to check a KO file against the invariants documented by the KO format
+    /// KO only
+    #[arg(
+        long = "spec-report",
+        help = "Checks a KO file against the KO format's documented invariants and prints a conformance checklist"
+    )]
+    pub spec_report: bool,
+    /// The layout of another tool to imitate, so that scripts written for that tool can read the output
+    #[arg(
+        long = "style",
+        value_enum,
+        value_name = "STYLE",
+        help = "Lays out disassembly, headers, and symbols the same way as objdump or readelf"
+    )]
+    pub style: Option<OutputStyle>,
+    /// Whether to print only where each region of a KSM file is, found without fully parsing it
+    /// KSM only
+    #[arg(
+        long = "outline",
+        help = "Prints the offsets, sizes, and contents of each KSM section using minimal decoding"
+    )]
+    pub outline: bool,
+    /// The number to start counting labels from in each disassembled section, instead of using the
+    /// labels counted from the start of the file
+    /// KSM only
+    #[arg(
+        long = "renumber-from",
+        require_equals = true,
+        value_name = "N",
+        help = "Restarts label numbering at N for each disassembled section, and shows the real label range in its header"
+    )]
+    pub renumber_from: Option<usize>,
+    /// Whether to show the argument indices of operands, for when the raw instruction bytes are
+    /// too much, and where to show them
+    /// KSM only
+    #[arg(
+        long = "show-arg-indices",
+        value_enum,
+        value_name = "PLACEMENT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "first",
+        help = "Appends the argument index of the first operand after each disassembled instruction's operands, like [idx 0x0cc5], or shows every operand's index before its value if =each is given"
+    )]
+    pub show_arg_indices: Option<ArgIndexPlacement>,
+    /// Whether to print the raw bytes of the file as a hex dump, which for KSM files is the decompressed body
+    #[arg(
+        long = "hex-dump",
+        help = "Prints the raw bytes of the decompressed KSM body or the KO file in hexdump format"
+    )]
+    pub hex_dump: bool,
+
+    /// The name of a KO section to hex dump instead of the whole file
+    /// KO only
+    #[arg(
+        long = "hex-dump-section",
+        value_name = "NAME",
+        help = "Prints the raw bytes of the named KO section in hexdump format"
+    )]
+    pub hex_dump_section: Option<String>,
+    /// The order to disassemble KSM code sections in
+    /// KSM only
+    #[arg(
+        long = "order",
+        value_enum,
+        require_equals = true,
+        value_name = "ORDER",
+        default_value_t = SectionOrder::File,
+        help = "Disassembles KSM code sections in file order, or in logical order with MAIN first"
+    )]
+    pub order: SectionOrder,
+    /// Whether to write one JSON object per line for every argument, instruction, symbol, and
+    /// debug entry, as each one is produced
+    #[arg(
+        long = "ndjson",
+        help = "Streams every argument, instruction, symbol, and debug entry as one JSON object per line"
+    )]
+    pub ndjson: bool,
+    /// The name of a section to write the raw bytes of to the file given to --output
+    ///
+    /// For KO files this is a section name. For KSM files it is one of header, arguments, func,
+    /// init, main, or debug, optionally followed by :N to pick the Nth one of that kind.
+    #[arg(
+        long = "extract-section",
+        value_name = "NAME",
+        help = "Writes the raw bytes of a KO section, or a KSM region like arguments or main:1, to the file given to -o"
+    )]
+    pub extract_section: Option<String>,
+
+    /// The file to write output to instead of the terminal, which is written without colors
+    ///
+    /// Along with --extract-section, this is where the section's bytes are written instead.
+    #[arg(
+        short = 'o',
+        long = "output",
+        value_name = "FILE",
+        help = "Writes the output to a file instead of the terminal, without colors"
+    )]
+    pub output: Option<PathBuf>,
+    /// A minijinja template to render the file's contents with, instead of any of the built in layouts
+    #[arg(
+        long = "template",
+        value_name = "FILE",
+        help = "Renders the file's arguments, instructions, symbols, and sections with a minijinja template"
+    )]
+    pub template: Option<PathBuf>,
+    /// The file to write the output to as an HTML page, in the same colors it would have in the terminal
+    #[arg(
+        long = "color-html",
+        value_name = "FILE",
+        help = "Writes the output to an HTML file, keeping the colors it would have in the terminal"
+    )]
+    pub color_html: Option<PathBuf>,
+    /// Whether to hex dump every gzip member after the first, when a compressed KSM file has more than one
+    ///
+    /// KSM only
+    #[arg(
+        long = "gzip-members",
+        help = "Hex dumps the decompressed contents of every gzip member after the first in a KSM file"
+    )]
+    pub gzip_members: bool,
+    /// A tags file to write, which points editors at the line of the dump written to --output
+    /// that each KSM function section or KO symbol is shown at
+    #[arg(
+        long = "ctags",
+        value_name = "FILE",
+        help = "Writes a ctags file for the functions and symbols in the dump written to -o"
+    )]
+    pub ctags: Option<PathBuf>,
+    /// Whether to print the version and the file formats this build supports as JSON, instead of dumping a file
+    #[arg(
+        long = "version-json",
+        help = "Prints the version and the supported KO and KSM formats as JSON"
+    )]
+    pub version_json: bool,
+    /// Whether to print every flag and subcommand as JSON, instead of dumping a file
+    #[arg(
+        long = "help-json",
+        help = "Prints every flag and subcommand, with their descriptions, as JSON"
+    )]
+    pub help_json: bool,
+    /// A JSON file to write, mapping each source line in the debug section to the addresses
+    /// and labels of the instructions compiled from it
+    ///
+    /// KSM only
+    #[arg(
+        long = "linemap",
+        value_name = "FILE",
+        help = "Writes a JSON map from source lines to instruction addresses and labels"
+    )]
+    pub linemap: Option<PathBuf>,
+    /// A CSV file to write one row to for the input file, or for every KSM and KO file under the
+    /// input directory, with the sizes, counts, compiler, and verification status of each
+    #[arg(
+        long = "csv-summary",
+        value_name = "FILE",
+        help = "Writes a CSV row of sizes, counts, compiler, and verification status for every KSM and KO file under the input"
+    )]
+    pub csv_summary: Option<PathBuf>,
+    /// Whether to write which functions call which others as a DOT digraph
+    #[arg(
+        long = "callgraph-dot",
+        help = "Writes the calls and delegates between functions as a Graphviz DOT call graph"
+    )]
+    pub callgraph_dot: bool,
+    /// Whether to show how many bytes of code each opcode accounts for, including its operands
+    #[arg(
+        long = "opcode-costs",
+        help = "Shows how many bytes each opcode and its operands take up across the whole file"
+    )]
+    pub opcode_costs: bool,
+    /// Whether to print the JSON Schema of the records written by --ndjson, instead of dumping a file
+    #[arg(
+        long = "schema",
+        help = "Prints the JSON Schema of the records that --ndjson writes"
+    )]
+    pub schema: bool,
+    /// A file to write a single page HTML browser of the disassembly to, with a sidebar of code
+    /// sections, a search box, and collapsible functions
+    ///
+    /// KSM only
+    #[arg(
+        long = "html-interactive",
+        value_name = "FILE",
+        help = "Writes a self-contained HTML page for browsing and searching the disassembly"
+    )]
+    pub html_interactive: Option<PathBuf>,
+    /// Whether to decode the code sections with each possible index width and report which ones
+    /// decode cleanly
+    /// KSM only
+    #[arg(
+        long = "try-widths",
+        help = "Retries decoding a KSM file with 1 to 4 byte operands and reports which width decodes cleanly"
+    )]
+    pub try_widths: bool,
+    /// Whether to print the data sections and string tables as JSON, with the byte offset and size
+    /// of each entry
+    /// KO only
+    #[arg(
+        long = "json",
+        requires = "data",
+        help = "With --data, prints each KO data entry and string table string as JSON with its offset and size"
+    )]
+    pub json: bool,
+    /// Whether to pretty-print JSON documents once all of each one is in memory, instead of writing
+    /// them a section at a time as they are worked out
+    #[arg(
+        long = "pretty",
+        help = "Pretty-prints --linemap and --data --json output all at once, instead of writing it a section at a time as compact JSON"
+    )]
+    pub pretty: bool,
+    /// A file to write every record of the file to with bincode, for other Rust programs to load
+    /// with kdump::model::BinaryDump::read
+    #[arg(
+        long = "binary-dump",
+        value_name = "PATH",
+        help = "Writes the parsed file to PATH as bincode, readable with kdump::model::BinaryDump"
+    )]
+    pub binary_dump: Option<PathBuf>,
+    /// The kinds of text to print without color, while everything else keeps its color
+    #[arg(
+        long = "no-color-for",
+        value_enum,
+        value_delimiter = ',',
+        value_name = "ROLES",
+        help = "Leaves the given kinds of text uncolored, like --no-color-for=labels,lines"
+    )]
+    pub no_color_for: Vec<ColorRole>,
+    /// Whether to answer JSON-RPC requests on stdin instead of dumping a file
+    #[arg(
+        long = "rpc",
+        help = "Serves JSON-RPC requests, one per line, on stdin and stdout for editors and GUIs"
+    )]
+    pub rpc: bool,
+    /// Whether to print a single line of key=value fields for the file, or for every KSM and KO
+    /// file in a directory
+    #[arg(
+        long = "summary",
+        help = "Prints one line per file with its type, version, section and instruction counts, sizes, and compiler"
+    )]
+    pub summary: bool,
+    /// A file to write the output to as a LaTeX document, with each kind of colored text wrapped
+    /// in a macro
+    #[arg(
+        long = "latex",
+        value_name = "PATH",
+        conflicts_with = "typst",
+        help = "Writes the output to PATH as a LaTeX listing, with coloring macros like \\kdmnemonic"
+    )]
+    pub latex: Option<PathBuf>,
+    /// A file to write the output to as a typst document, with each kind of colored text wrapped
+    /// in a function
+    #[arg(
+        long = "typst",
+        value_name = "PATH",
+        help = "Writes the output to PATH as a typst listing, with coloring functions like #kdmnemonic"
+    )]
+    pub typst: Option<PathBuf>,
+    /// A file to write every instruction to as a row of a Parquet table, for analyzing many files
+    /// together. Only works if kDump was built with the parquet feature.
+    #[arg(
+        long = "parquet",
+        value_name = "PATH",
+        help = "Writes one row per instruction to PATH as Parquet, with the section, address, opcode, and operands"
+    )]
+    pub parquet: Option<PathBuf>,
+    /// Whether to print the offset of each instruction in the decompressed file before its label
+    /// KSM only
+    #[arg(
+        long = "show-addr",
+        help = "Prints the byte offset of each KSM instruction in the decompressed file, before its label"
+    )]
+    pub show_addr: bool,
+
+    /// Checks the file against the invariants listed in a TOML file, instead of dumping it
+    #[arg(
+        long = "assert",
+        value_name = "FILE",
+        help = "Checks the file against the symbols, strings, section sizes, and instruction counts listed in a TOML file, failing if any don't hold"
+    )]
+    pub assert: Option<PathBuf>,
+
+    /// The label or byte offset to start disassembling at, which skips everything before it
+    /// KSM only
+    #[arg(
+        long = "start-address",
+        value_name = "ADDR",
+        help = "When disassembling, starts at the instruction with this label (@002200) or at this byte offset in the decompressed file"
+    )]
+    pub start_address: Option<AddressBound>,
+    /// The label or byte offset to stop disassembling at, which is not itself disassembled
+    /// KSM only
+    #[arg(
+        long = "stop-address",
+        value_name = "ADDR",
+        help = "When disassembling, stops before the instruction with this label or at this byte offset in the decompressed file"
+    )]
+    pub stop_address: Option<AddressBound>,
+
+    /// The only code section to disassemble
+    /// KSM only
+    #[arg(
+        long = "section",
+        value_name = "SECTION",
+        help = "When disassembling, only shows one code section: MAIN, INIT, or FUNC:N for the Nth function section counted from 0"
+    )]
+    pub section: Option<SectionSelector>,
+
+    /// The kerboscript file the KSM file was compiled from, whose lines are shown above the
+    /// instructions they compiled to
+    /// KSM only
+    #[arg(
+        long = "source",
+        value_name = "FILE",
+        help = "When disassembling, prints each line of the kerboscript source above the instructions it compiled to, using the debug section"
+    )]
+    pub source: Option<PathBuf>,
+
+    /// Whether flags that only work on the other kind of file should be an error instead of a warning
+    #[arg(
+        long = "strict-flags",
+        help = "Fails instead of warning when a flag only applies to the other kind of file, for scripts"
+    )]
+    pub strict_flags: bool,
+
+    /// Whether to only check that every flag given applies to the file, without dumping anything
+    #[arg(
+        long = "check-args",
+        help = "Checks that every flag given applies to the file's type, and exits without dumping it"
+    )]
+    pub check_args: bool,
+
+    /// Two builds of a file to check are the same, for verifying that a build is reproducible
+    #[arg(
+        long = "repro-check",
+        num_args = 2,
+        value_names = ["A", "B"],
+        help = "Checks whether two builds are byte-identical, semantically identical, or different, ignoring gzip timestamps and compression levels"
+    )]
+    pub repro_check: Vec<PathBuf>,
+    /// How far apart two floating point values can be and still count as the same, for --repro-check
+    #[arg(
+        long = "float-epsilon",
+        value_name = "EPSILON",
+        requires = "repro_check",
+        help = "With --repro-check, counts floating point values at most EPSILON apart as the same"
+    )]
+    pub float_epsilon: Option<f64>,
+    /// How many seconds the analyses of the file can take before they stop and keep what they have
+    /// written so far
+    #[arg(
+        long = "timeout",
+        value_name = "SECS",
+        help = "Stops disassembling, decompiling, or graphing after SECS seconds, keeping what was written so far with a note"
+    )]
+    pub timeout: Option<u64>,
+
+    /// Whether to show source line numbers as a plain column, without the box drawing characters
+    /// KSM only
+    #[arg(
+        long = "line-numbers-plain",
+        help = "When disassembling, shows the source line number of every instruction as a plain column, which survives grep and diff"
+    )]
+    pub line_numbers_plain: bool,
+
+    /// Whether to draw the line number brackets with ASCII characters instead of box drawing ones
+    /// KSM only
+    #[arg(
+        long = "ascii-art",
+        help = "Draws the --line-numbers brackets with +-| instead of box drawing characters, which is automatic for locales that aren't UTF-8"
+    )]
+    pub ascii_art: bool,
+
+    /// How the bytes of strings in the file are decoded
+    #[arg(
+        long = "encoding",
+        value_enum,
+        require_equals = true,
+        value_name = "ENCODING",
+        default_value_t = StringEncoding::Utf8,
+        help = "Decodes strings as UTF-8, falling back to Latin-1 when they aren't valid UTF-8, or always as Latin-1"
+    )]
+    pub encoding: StringEncoding,
+
+    /// Whether to show how many bytes of code each source line compiled to, using the debug section
+    /// KSM only
+    #[arg(
+        long = "line-sizes",
+        help = "Shows how many bytes of code each source line compiled to, biggest first, with the source text if --source is given"
+    )]
+    pub line_sizes: bool,
+
+    /// Whether to leave the lbrt pseudo-instructions out of the disassembly
+    /// KSM only
+    #[arg(
+        long = "hide-lbrt",
+        help = "Leaves the label reset (lbrt) instructions out of the disassembly, while still using them to work out labels"
+    )]
+    pub hide_lbrt: bool,
+
+    /// Whether to show how many instructions each source line compiled to, and how much of the
+    /// IPU they use
+    /// KSM only
+    #[arg(
+        long = "line-costs",
+        help = "Shows how many instructions each source line compiled to, and how much of kOS's default IPU of 200 running it once uses"
+    )]
+    pub line_costs: bool,
+
+    /// Only the instructions that match this pattern are disassembled, along with the context
+    /// lines around them
+    /// KSM only
+    #[arg(
+        long = "grep",
+        value_name = "PATTERN",
+        help = "When disassembling, only shows instructions whose mnemonic and operands, like: stog \"$throttle\", match this regular expression"
+    )]
+    pub grep: Option<GrepPattern>,
+    /// The number of instructions to show after each one that matches --grep
+    #[arg(
+        short = 'A',
+        long = "after-context",
+        value_name = "NUM",
+        default_value_t = 0,
+        requires = "grep",
+        help = "Shows this many instructions after each one that matches --grep"
+    )]
+    pub after_context: usize,
+    /// The number of instructions to show before each one that matches --grep
+    #[arg(
+        short = 'B',
+        long = "before-context",
+        value_name = "NUM",
+        default_value_t = 0,
+        requires = "grep",
+        help = "Shows this many instructions before each one that matches --grep"
+    )]
+    pub before_context: usize,
+
+    /// A KASM source file to write, which the KASM assembler can turn back into an equivalent file
+    #[arg(
+        long = "emit-kasm",
+        value_name = "FILE",
+        help = "Writes the file as KASM source, with labels, directives, and every value declared with its type, that can be reassembled into an equivalent file"
+    )]
+    pub emit_kasm: Option<PathBuf>,
+
+    /// Whether to lift the code sections into pseudo-kerboscript
+    /// KSM only
+    #[arg(
+        long = "decompile",
+        help = "Lifts the code into pseudo-kerboscript, experimentally, keeping anything it can't lift as a comment with the raw instruction"
+    )]
+    pub decompile: bool,
+
+    /// Whether to show how each instruction changes the stack, and how deep it is estimated to be
+    /// KSM only
+    #[arg(
+        long = "stack-effect",
+        help = "Shows how many values each instruction pushes or pops, and a running estimate of the stack depth, flagging where it goes below zero"
+    )]
+    pub stack_effect: bool,
+}
+
+impl CLIConfig {
+    /// Starts building a configuration in code, with every setting at its default
+    pub fn builder() -> CLIConfigBuilder {
+        CLIConfigBuilder::new()
+    }
+
+    /// The flags that were given but only work on the other kind of file
+    fn inapplicable_flags(&self, is_ksm: bool) -> Vec<&'static str> {
+        let ksm_only = [
+            ("--line-numbers", self.line_numbers),
+            ("--show-no-raw-instr", self.show_no_raw_instr),
+            ("--show-no-labels", self.show_no_labels),
+            ("--simulate-growth", self.simulate_growth.is_some()),
+            ("--align-columns", self.align_columns),
+            ("--collapse-repeats", self.collapse_repeats),
+            ("--local-labels", self.local_labels),
+            ("--hide-boilerplate", self.hide_boilerplate),
+            ("--outline", self.outline),
+            ("--renumber-from", self.renumber_from.is_some()),
+            ("--show-arg-indices", self.show_arg_indices.is_some()),
+            ("--gzip-members", self.gzip_members),
+            ("--linemap", self.linemap.is_some()),
+            ("--html-interactive", self.html_interactive.is_some()),
+            ("--try-widths", self.try_widths),
+            ("--show-addr", self.show_addr),
+            ("--start-address", self.start_address.is_some()),
+            ("--stop-address", self.stop_address.is_some()),
+            ("--section", self.section.is_some()),
+            ("--source", self.source.is_some()),
+            ("--line-numbers-plain", self.line_numbers_plain),
+            ("--ascii-art", self.ascii_art),
+            ("--line-sizes", self.line_sizes),
+            ("--hide-lbrt", self.hide_lbrt),
+            ("--line-costs", self.line_costs),
+            ("--grep", self.grep.is_some()),
+            ("--decompile", self.decompile),
+            ("--stack-effect", self.stack_effect),
+            ("--order", self.order == SectionOrder::Logical),
+        ];
+        let ko_only = [
+            ("--section-headers", self.section_headers),
+            ("--data", self.data),
+            ("--stabs", self.stabs),
+            ("--syms", self.syms),
+            ("--reloc", self.reloc),
+            ("--provenance-symbols", self.provenance_symbols),
+            ("--annotate-data-symbols", self.annotate_data_symbols),
+            ("--spec-report", self.spec_report),
+            ("--hex-dump-section", self.hex_dump_section.is_some()),
+            ("--json", self.json),
+        ];
+
+        let others: &[(&'static str, bool)] = if is_ksm { &ko_only } else { &ksm_only };
+
+        others
+            .iter()
+            .filter(|(_, given)| *given)
+            .map(|(flag, _)| *flag)
+            .collect()
+    }
+
+    /// Whether the output is meant to be read by another program, in which case nothing else can be mixed into it
+    fn machine_readable(&self) -> bool {
+        self.csv
+            || self.json
+            || self.summary
+            || matches!(self.command, Some(Command::Corpus(_)))
+            || self.cfg_dot
+            || self.callgraph_dot
+            || self.ndjson
+            || self.style.is_some()
+            || self.template.is_some()
+    }
 }
@@ -3,6 +3,10 @@ use std::process;
 
 use kdump::{run, CLIConfig};
 
+#[cfg(feature = "alloc-stats")]
+#[global_allocator]
+static GLOBAL: kdump::mem::CountingAllocator = kdump::mem::CountingAllocator;
+
 fn main() {
     let config = CLIConfig::parse();
 
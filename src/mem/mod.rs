@@ -0,0 +1,163 @@
+use kerbalobjects::ko::symbols::{KOSymbol, ReldEntry};
+use kerbalobjects::ko::KOFile;
+use kerbalobjects::ksm::sections::{DebugEntry, DebugRange};
+use kerbalobjects::ksm::KSMFile;
+use kerbalobjects::KOSValue;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::mem::size_of;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static CURRENT_BYTES: AtomicUsize = AtomicUsize::new(0);
+static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+static TOTAL_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// A global allocator that wraps the system allocator and keeps track of how many bytes
+/// are currently allocated, the peak of that number, and how many allocations were made
+///
+/// This has to be registered by the binary using #[global_allocator] for --mem-stats to report anything,
+/// which kdump only does when it is built with the alloc-stats feature
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+
+        if !ptr.is_null() {
+            record_alloc(layout.size());
+        }
+
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+
+        CURRENT_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+
+        if !new_ptr.is_null() {
+            CURRENT_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+            record_alloc(new_size);
+        }
+
+        new_ptr
+    }
+}
+
+fn record_alloc(size: usize) {
+    let current = CURRENT_BYTES.fetch_add(size, Ordering::Relaxed) + size;
+
+    TOTAL_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    PEAK_BYTES.fetch_max(current, Ordering::Relaxed);
+}
+
+/// A snapshot of the counters kept by the CountingAllocator
+#[derive(Debug, Clone, Copy)]
+pub struct AllocStats {
+    pub current_bytes: usize,
+    pub peak_bytes: usize,
+    pub total_allocations: usize,
+}
+
+/// Returns the current allocation counters. All zeroes if the CountingAllocator isn't registered.
+pub fn alloc_stats() -> AllocStats {
+    AllocStats {
+        current_bytes: CURRENT_BYTES.load(Ordering::Relaxed),
+        peak_bytes: PEAK_BYTES.load(Ordering::Relaxed),
+        total_allocations: TOTAL_ALLOCATIONS.load(Ordering::Relaxed),
+    }
+}
+
+/// The estimated in-memory size of one part of a parsed file
+#[derive(Debug, Clone)]
+pub struct Footprint {
+    pub name: String,
+    pub bytes: usize,
+}
+
+impl Footprint {
+    fn new(name: impl Into<String>, bytes: usize) -> Self {
+        Footprint {
+            name: name.into(),
+            bytes,
+        }
+    }
+}
+
+/// Estimates how many bytes a single KOSValue takes up, including any heap allocated string
+fn kosvalue_footprint(value: &KOSValue) -> usize {
+    size_of::<KOSValue>()
+        + match value {
+            KOSValue::String(s) | KOSValue::StringValue(s) => s.capacity(),
+            _ => 0,
+        }
+}
+
+/// Estimates the memory footprint of each structure in a parsed KSM file
+pub fn ksm_footprint(ksm: &KSMFile) -> Vec<Footprint> {
+    let mut footprints = Vec::new();
+
+    let arguments_bytes = ksm.arg_section.arguments().map(kosvalue_footprint).sum();
+    footprints.push(Footprint::new("Argument section", arguments_bytes));
+
+    let mut instr_bytes = 0;
+
+    for code_section in ksm.code_sections() {
+        instr_bytes += code_section.instructions().len() * size_of::<kerbalobjects::ksm::Instr>();
+    }
+
+    footprints.push(Footprint::new("Instruction vectors", instr_bytes));
+
+    let mut debug_bytes = 0;
+
+    for debug_entry in ksm.debug_section.debug_entries() {
+        debug_bytes +=
+            size_of::<DebugEntry>() + debug_entry.number_ranges() * size_of::<DebugRange>();
+    }
+
+    footprints.push(Footprint::new("Debug entries", debug_bytes));
+
+    footprints
+}
+
+/// Estimates the memory footprint of each structure in a parsed KO file
+pub fn ko_footprint(kofile: &KOFile) -> Vec<Footprint> {
+    let mut footprints = Vec::new();
+
+    let data_bytes = kofile
+        .data_sections()
+        .flat_map(|section| section.data())
+        .map(kosvalue_footprint)
+        .sum();
+    footprints.push(Footprint::new("Data sections", data_bytes));
+
+    let func_bytes = kofile
+        .func_sections()
+        .map(|section| section.instructions().len() * size_of::<kerbalobjects::ko::Instr>())
+        .sum();
+    footprints.push(Footprint::new("Instruction vectors", func_bytes));
+
+    let strtab_bytes = kofile
+        .str_tabs()
+        .flat_map(|strtab| strtab.strings())
+        .map(|s| size_of::<String>() + s.capacity())
+        .sum();
+    footprints.push(Footprint::new("String tables", strtab_bytes));
+
+    let symtab_bytes = kofile
+        .sym_tabs()
+        .map(|symtab| symtab.symbols().count() * size_of::<KOSymbol>())
+        .sum();
+    footprints.push(Footprint::new("Symbol tables", symtab_bytes));
+
+    let reld_bytes = kofile
+        .reld_sections()
+        .map(|reld| reld.entries().count() * size_of::<ReldEntry>())
+        .sum();
+    footprints.push(Footprint::new("Relocation data", reld_bytes));
+
+    footprints
+}
@@ -0,0 +1,199 @@
+//! An owned copy of the records that can be written with bincode, and read back by other Rust
+//! programs that link against kDump
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+use std::io::{Read, Write};
+
+use super::{AttributionRecord, ModelResult, Record, FORMAT_VERSION};
+
+/// Every record of a file, in the order they were produced
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BinaryDump {
+    pub format_version: u32,
+    pub records: Vec<BinaryRecord>,
+}
+
+/// The same as a record, but owning all of its data, and without anything that only a
+/// self-describing format like JSON can read back
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BinaryRecord {
+    File {
+        format: String,
+        attribution: Option<AttributionRecord>,
+    },
+    Argument {
+        index: usize,
+        value_type: String,
+        value: BinaryValue,
+    },
+    Instruction {
+        section: String,
+        label: String,
+        addr: usize,
+        mnemonic: String,
+        operands: Vec<BinaryValue>,
+    },
+    DebugEntry {
+        line: isize,
+        ranges: Vec<[usize; 2]>,
+    },
+    Section {
+        index: usize,
+        name: String,
+        size: u32,
+    },
+    Symbol {
+        table: String,
+        name: String,
+        value: u32,
+        size: u16,
+        binding: String,
+        sym_type: String,
+        section: u16,
+    },
+    Data {
+        section: String,
+        index: usize,
+        value_type: String,
+        value: BinaryValue,
+    },
+}
+
+/// A kOS value, reduced to the same types as in the JSON records
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BinaryValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl From<Value> for BinaryValue {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Null => BinaryValue::Null,
+            Value::Bool(b) => BinaryValue::Bool(b),
+            Value::Number(n) => match n.as_i64() {
+                Some(i) => BinaryValue::Int(i),
+                None => BinaryValue::Float(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            Value::String(s) => BinaryValue::String(s),
+            // Records never hold these, but they still need to become something
+            other => BinaryValue::String(other.to_string()),
+        }
+    }
+}
+
+impl From<Record<'_>> for BinaryRecord {
+    fn from(record: Record) -> Self {
+        match record {
+            Record::File {
+                format,
+                attribution,
+                ..
+            } => BinaryRecord::File {
+                format: format.to_string(),
+                attribution,
+            },
+            Record::Argument {
+                index,
+                value_type,
+                value,
+            } => BinaryRecord::Argument {
+                index,
+                value_type: value_type.to_string(),
+                value: value.into(),
+            },
+            Record::Instruction {
+                section,
+                label,
+                addr,
+                mnemonic,
+                operands,
+            } => BinaryRecord::Instruction {
+                section: section.to_string(),
+                label,
+                addr,
+                mnemonic: mnemonic.to_string(),
+                operands: operands.into_iter().map(BinaryValue::from).collect(),
+            },
+            Record::DebugEntry { line, ranges } => BinaryRecord::DebugEntry { line, ranges },
+            Record::Section { index, name, size } => BinaryRecord::Section {
+                index,
+                name: name.to_string(),
+                size,
+            },
+            Record::Symbol {
+                table,
+                name,
+                value,
+                size,
+                binding,
+                sym_type,
+                section,
+            } => BinaryRecord::Symbol {
+                table: table.to_string(),
+                name: name.to_string(),
+                value,
+                size,
+                binding: binding.to_string(),
+                sym_type: sym_type.to_string(),
+                section,
+            },
+            Record::Data {
+                section,
+                index,
+                value_type,
+                value,
+            } => BinaryRecord::Data {
+                section: section.to_string(),
+                index,
+                value_type: value_type.to_string(),
+                value: value.into(),
+            },
+        }
+    }
+}
+
+impl BinaryDump {
+    /// Gathers every record that a visit produces
+    pub fn collect<'a>(
+        visit: impl FnOnce(&mut dyn FnMut(Record<'a>) -> ModelResult) -> ModelResult,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut records = Vec::new();
+
+        visit(&mut |record| {
+            records.push(record.into());
+            Ok(())
+        })?;
+
+        Ok(BinaryDump {
+            format_version: FORMAT_VERSION,
+            records,
+        })
+    }
+
+    /// Writes the dump with bincode
+    pub fn write(&self, out: impl Write) -> Result<(), Box<dyn Error>> {
+        bincode::serialize_into(out, self)?;
+        Ok(())
+    }
+
+    /// Reads a dump that was written by --binary-dump, checking that it uses the same format version
+    pub fn read(input: impl Read) -> Result<Self, Box<dyn Error>> {
+        let dump: BinaryDump = bincode::deserialize_from(input)?;
+
+        if dump.format_version != FORMAT_VERSION {
+            return Err(format!(
+                "The dump uses format version {}, but this version of kDump reads version {}",
+                dump.format_version, FORMAT_VERSION
+            )
+            .into());
+        }
+
+        Ok(dump)
+    }
+}
@@ -0,0 +1,220 @@
+use kerbalobjects::ko::symbols::OperandIndex;
+use kerbalobjects::ko::KOFile;
+use kerbalobjects::ksm::sections::CodeType;
+use kerbalobjects::{KOSValue, Opcode};
+use serde::Serialize;
+use std::collections::HashSet;
+
+use crate::fio::layout;
+use crate::index::{instr_opcode, instr_operands, ko_instr_operands, IndexedKsm};
+use crate::output;
+
+/// A problem found in a file, kept apart from the records so that a program reading structured
+/// output never has to tell the two apart
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub severity: &'static str,
+    /// What kind of problem this is, which is one of unknown_opcode, dangling_index,
+    /// missing_section, non_ascii_string, or inapplicable_flag
+    pub code: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub section: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub addr: Option<usize>,
+}
+
+impl Diagnostic {
+    pub(crate) fn warning(code: &'static str, message: String) -> Self {
+        Diagnostic {
+            severity: "warning",
+            code,
+            message,
+            section: None,
+            addr: None,
+        }
+    }
+
+    fn info(code: &'static str, message: String) -> Self {
+        Diagnostic {
+            severity: "info",
+            ..Diagnostic::warning(code, message)
+        }
+    }
+
+    fn at(mut self, section: &str, addr: usize) -> Self {
+        self.section = Some(section.to_string());
+        self.addr = Some(addr);
+        self
+    }
+}
+
+/// Finds the unknown opcodes, operands that refer to arguments that don't exist, missing
+/// sections, and strings that aren't ASCII in a KSM file
+///
+/// The section names are given in the same order as the file's code sections.
+pub fn diagnose_ksm(index: &IndexedKsm, section_names: &[&str]) -> Vec<Diagnostic> {
+    let ksmfile = index.ksmfile();
+    let mut diagnostics = Vec::new();
+
+    if !ksmfile
+        .code_sections()
+        .any(|code_section| code_section.section_type == CodeType::Main)
+    {
+        diagnostics.push(Diagnostic::warning(
+            "missing_section",
+            String::from("The file has no main code section"),
+        ));
+    }
+
+    for (arg_index, value) in index.arguments() {
+        if let KOSValue::String(s) | KOSValue::StringValue(s) = value {
+            if let Some(bytes) = output::non_ascii_bytes(s) {
+                let decoding = match String::from_utf8(bytes) {
+                    Ok(decoded) => format!("which decode as UTF-8 to {:?}", decoded),
+                    Err(_) => {
+                        String::from("which aren't valid UTF-8, so they are shown as Latin-1")
+                    }
+                };
+
+                diagnostics.push(Diagnostic::info(
+                    "non_ascii_string",
+                    format!(
+                        "The string at argument index {:x} has non-ASCII bytes, {}",
+                        arg_index, decoding
+                    ),
+                ));
+            }
+        }
+    }
+
+    for (section_index, code_section) in ksmfile.code_sections().enumerate() {
+        let section = section_names.get(section_index).copied().unwrap_or("");
+
+        for (instr, location) in code_section
+            .instructions()
+            .zip(index.locations(section_index))
+        {
+            if instr_opcode(instr) == Opcode::Bogus {
+                diagnostics.push(
+                    Diagnostic::warning(
+                        "unknown_opcode",
+                        format!(
+                            "Unknown opcode at offset 0x{:x} in the decompressed file",
+                            index.file_offset(location.addr)
+                        ),
+                    )
+                    .at(section, location.addr),
+                );
+            }
+
+            let operands = instr_operands(instr);
+
+            for op in operands.into_iter().filter(|op| index.value(*op).is_none()) {
+                diagnostics.push(
+                    Diagnostic::warning(
+                        "dangling_index",
+                        format!(
+                            "Operand refers to argument index {:x}, which no argument starts at",
+                            usize::from(op)
+                        ),
+                    )
+                    .at(section, location.addr),
+                );
+            }
+        }
+    }
+
+    diagnostics
+}
+
+/// Finds the unknown opcodes, operands that refer to data that doesn't exist, and missing
+/// sections in a KO file
+///
+/// KO instructions don't have addresses yet, so each one is given by its index in its section.
+pub fn diagnose_ko(kofile: &KOFile) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    let has_code = kofile.func_sections().next().is_some();
+
+    for name in [".data", ".symtab", ".symstrtab"] {
+        let found = match name {
+            ".data" => kofile.data_section_by_name(name).is_some(),
+            ".symtab" => kofile.sym_tab_by_name(name).is_some(),
+            _ => kofile.str_tab_by_name(name).is_some(),
+        };
+
+        if has_code && !found {
+            diagnostics.push(Diagnostic::warning(
+                "missing_section",
+                format!("The file has function sections, but no {} section", name),
+            ));
+        }
+    }
+
+    // Relocated operands are filled in by the linker, so they don't need to point at any data
+    let mut relocated = HashSet::new();
+
+    for reld_section in kofile.reld_sections() {
+        for entry in reld_section.entries() {
+            let operand = match entry.operand_index {
+                OperandIndex::One => 0,
+                OperandIndex::Two => 1,
+            };
+
+            relocated.insert((
+                u16::from(entry.section_index),
+                u32::from(entry.instr_index),
+                operand,
+            ));
+        }
+    }
+
+    let data_section = kofile.data_section_by_name(".data");
+
+    for func_section in kofile.func_sections() {
+        let sh_index = func_section.section_index();
+        let section = kofile
+            .get_section_header(sh_index)
+            .and_then(|header| kofile.get_header_name(header))
+            .map(|s| s.as_str())
+            .unwrap_or("");
+
+        let mut offset = layout::ko_section_range(kofile, section).map(|range| range.start);
+
+        for (i, instr) in func_section.instructions().enumerate() {
+            if instr.opcode() == Opcode::Bogus {
+                let message = match offset {
+                    Some(offset) => format!("Unknown opcode at offset 0x{:x}", offset),
+                    None => String::from("Unknown opcode"),
+                };
+
+                diagnostics.push(Diagnostic::warning("unknown_opcode", message).at(section, i));
+            }
+
+            offset = offset.map(|offset| offset + layout::ko_instr_size(instr));
+
+            let operands = ko_instr_operands(instr);
+
+            for (n, op) in operands.into_iter().enumerate() {
+                let is_relocated = relocated.contains(&(u16::from(sh_index), i as u32, n));
+                let exists = data_section.is_some_and(|data| data.get(op).is_some());
+
+                if !is_relocated && !exists {
+                    diagnostics.push(
+                        Diagnostic::warning(
+                            "dangling_index",
+                            format!(
+                                "Operand refers to data index {}, which is not in .data",
+                                u32::from(op)
+                            ),
+                        )
+                        .at(section, i),
+                    );
+                }
+            }
+        }
+    }
+
+    diagnostics
+}
@@ -0,0 +1,373 @@
+//! Plain data records describing everything kDump can show about a file, for output formats
+//! that are meant to be read by other programs
+
+use kerbalobjects::ko::symbols::{SymBind, SymType};
+use kerbalobjects::ko::KOFile;
+use kerbalobjects::KOSValue;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::error::Error;
+
+use crate::analysis::{self, Attribution};
+use crate::index::{instr_opcode, instr_operands, ko_instr_operands, IndexedKsm};
+use crate::{ParsedFile, StringEncoding};
+
+mod binary;
+pub use binary::{BinaryDump, BinaryRecord, BinaryValue};
+
+mod diagnostics;
+pub use diagnostics::{diagnose_ko, diagnose_ksm, Diagnostic};
+
+mod schema;
+pub use schema::record_schema;
+
+type ModelResult = Result<(), Box<dyn Error>>;
+
+/// The version of the record format, which goes up whenever a change could break something that
+/// reads it, like a field being removed or renamed
+pub const FORMAT_VERSION: u32 = 1;
+
+/// One thing in a file, tagged with what kind of thing it is
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Record<'a> {
+    File {
+        format_version: u32,
+        format: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        attribution: Option<AttributionRecord>,
+    },
+    Argument {
+        index: usize,
+        #[serde(rename = "type")]
+        value_type: &'static str,
+        value: Value,
+    },
+    Instruction {
+        section: &'a str,
+        label: String,
+        addr: usize,
+        mnemonic: &'static str,
+        operands: Vec<Value>,
+    },
+    DebugEntry {
+        line: isize,
+        ranges: Vec<[usize; 2]>,
+    },
+    Section {
+        index: usize,
+        name: &'a str,
+        size: u32,
+    },
+    Symbol {
+        table: &'a str,
+        name: &'a str,
+        value: u32,
+        size: u16,
+        binding: &'static str,
+        #[serde(rename = "type")]
+        sym_type: &'static str,
+        section: u16,
+    },
+    Data {
+        section: &'a str,
+        index: usize,
+        #[serde(rename = "type")]
+        value_type: &'static str,
+        value: Value,
+    },
+}
+
+/// The toolchain a file was attributed to, and the evidence for it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttributionRecord {
+    pub toolchain: Option<String>,
+    pub confidence: f64,
+    pub evidence: Vec<EvidenceRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvidenceRecord {
+    pub toolchain: String,
+    pub pattern: String,
+    pub weight: f64,
+}
+
+impl From<Attribution> for AttributionRecord {
+    fn from(attribution: Attribution) -> Self {
+        AttributionRecord {
+            toolchain: attribution.toolchain,
+            confidence: attribution.confidence,
+            evidence: attribution
+                .evidence
+                .into_iter()
+                .map(|evidence| EvidenceRecord {
+                    toolchain: evidence.toolchain,
+                    pattern: evidence.pattern,
+                    weight: evidence.weight,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Converts a value into the closest JSON type
+pub fn value_json(value: &KOSValue, encoding: StringEncoding) -> Value {
+    match value {
+        KOSValue::Null | KOSValue::ArgMarker => Value::Null,
+        KOSValue::Bool(b) | KOSValue::BoolValue(b) => Value::from(*b),
+        KOSValue::Byte(b) => Value::from(*b),
+        KOSValue::Int16(i) => Value::from(*i),
+        KOSValue::Int32(i) | KOSValue::ScalarInt(i) => Value::from(*i),
+        KOSValue::Float(f) => Value::from(*f),
+        KOSValue::Double(d) | KOSValue::ScalarDouble(d) => Value::from(*d),
+        KOSValue::String(s) | KOSValue::StringValue(s) => {
+            Value::from(crate::output::decode_string(s, encoding).as_ref())
+        }
+    }
+}
+
+pub fn bind_str(bind: SymBind) -> &'static str {
+    match bind {
+        SymBind::Local => "local",
+        SymBind::Global => "global",
+        SymBind::Extern => "extern",
+    }
+}
+
+pub fn sym_type_str(sym_type: SymType) -> &'static str {
+    match sym_type {
+        SymType::NoType => "notype",
+        SymType::Object => "object",
+        SymType::Func => "func",
+        SymType::Section => "section",
+        SymType::File => "file",
+    }
+}
+
+/// Produces every record of a KSM file in file order, handing each one over as soon as it is made
+///
+/// The section names are given in the same order as the file's code sections.
+pub fn visit_ksm<'a>(
+    index: &'a IndexedKsm,
+    section_names: &[&'a str],
+    encoding: StringEncoding,
+    emit: &mut dyn FnMut(Record<'a>) -> ModelResult,
+) -> ModelResult {
+    let ksmfile = index.ksmfile();
+
+    emit(Record::File {
+        format_version: FORMAT_VERSION,
+        format: "ksm",
+        attribution: Some(analysis::attribute_ksm(index).into()),
+    })?;
+
+    for (arg_index, value) in index.arguments() {
+        emit(Record::Argument {
+            index: arg_index,
+            value_type: crate::output::kosvalue_type_str(value),
+            value: value_json(value, encoding),
+        })?;
+    }
+
+    for (section_index, code_section) in ksmfile.code_sections().enumerate() {
+        let section = section_names.get(section_index).copied().unwrap_or("");
+
+        for (instr, location) in code_section
+            .instructions()
+            .zip(index.locations(section_index))
+        {
+            let operands = instr_operands(instr);
+
+            emit(Record::Instruction {
+                section,
+                label: location.label.clone(),
+                addr: location.addr,
+                mnemonic: instr_opcode(instr).into(),
+                operands: operands
+                    .into_iter()
+                    .map(|op| {
+                        index
+                            .value(op)
+                            .map(|value| value_json(value, encoding))
+                            .unwrap_or(Value::Null)
+                    })
+                    .collect(),
+            })?;
+        }
+    }
+
+    for debug_entry in ksmfile.debug_section.debug_entries() {
+        emit(Record::DebugEntry {
+            line: debug_entry.line_number,
+            ranges: debug_entry
+                .ranges()
+                .map(|range| [range.start, range.end])
+                .collect(),
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Produces every record of a KO file, handing each one over as soon as it is made
+pub fn visit_ko<'a>(
+    kofile: &'a KOFile,
+    encoding: StringEncoding,
+    emit: &mut dyn FnMut(Record<'a>) -> ModelResult,
+) -> ModelResult {
+    let section_name = |sh_index| {
+        kofile
+            .get_section_header(sh_index)
+            .and_then(|header| kofile.get_header_name(header))
+            .map(|s| s.as_str())
+            .unwrap_or("")
+    };
+
+    emit(Record::File {
+        format_version: FORMAT_VERSION,
+        format: "ko",
+        attribution: None,
+    })?;
+
+    for (index, header) in kofile.section_headers().enumerate() {
+        emit(Record::Section {
+            index,
+            name: kofile
+                .get_header_name(header)
+                .map(|s| s.as_str())
+                .unwrap_or(""),
+            size: header.size,
+        })?;
+    }
+
+    let symstrtab_opt = kofile.str_tab_by_name(".symstrtab");
+
+    for symbol_table in kofile.sym_tabs() {
+        let table = section_name(symbol_table.section_index());
+
+        for symbol in symbol_table.symbols() {
+            emit(Record::Symbol {
+                table,
+                name: symstrtab_opt
+                    .and_then(|symstrtab| symstrtab.get(symbol.name_idx))
+                    .map(|s| s.as_str())
+                    .unwrap_or(""),
+                value: u32::from(symbol.value_idx),
+                size: symbol.size,
+                binding: bind_str(symbol.sym_bind),
+                sym_type: sym_type_str(symbol.sym_type),
+                section: u16::from(symbol.sh_idx),
+            })?;
+        }
+    }
+
+    for data_section in kofile.data_sections() {
+        let section = section_name(data_section.section_index());
+
+        for (index, value) in data_section.data().enumerate() {
+            emit(Record::Data {
+                section,
+                index,
+                value_type: crate::output::kosvalue_type_str(value),
+                value: value_json(value, encoding),
+            })?;
+        }
+    }
+
+    for func_section in kofile.func_sections() {
+        let section = section_name(func_section.section_index());
+
+        for (i, instr) in func_section.instructions().enumerate() {
+            let operands = ko_instr_operands(instr);
+
+            // Operands are left as indices into .data, since they may be relocated to a symbol instead
+            emit(Record::Instruction {
+                section,
+                label: format!("{:0>8x}", i + 1),
+                addr: i,
+                mnemonic: instr.opcode().into(),
+                operands: operands
+                    .into_iter()
+                    .map(|op| Value::from(u32::from(op)))
+                    .collect(),
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Produces every record of a parsed KSM or KO file
+pub fn visit_file(
+    file: &ParsedFile,
+    encoding: StringEncoding,
+    emit: &mut dyn FnMut(Record) -> ModelResult,
+) -> ModelResult {
+    if let Some(ksm) = file.ksm() {
+        let index = IndexedKsm::new(ksm);
+        let section_names = ksm
+            .code_sections()
+            .map(|code_section| index.section_name(code_section))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        visit_ksm(&index, &section_names, encoding, &mut |record| emit(record))
+    } else if let Some(kofile) = file.ko() {
+        visit_ko(kofile, encoding, &mut |record| emit(record))
+    } else {
+        Ok(())
+    }
+}
+
+/// Gathers records into one object with a list for each kind of record, named by the kind in
+/// the plural, like arguments or instructions. The fields of the file record are put at the top level.
+pub fn collect_records<'a>(
+    visit: impl FnOnce(&mut dyn FnMut(Record<'a>) -> ModelResult) -> ModelResult,
+) -> Result<Value, Box<dyn Error>> {
+    let mut context = Map::new();
+
+    for kind in [
+        "arguments",
+        "instructions",
+        "debug_entries",
+        "sections",
+        "symbols",
+        "data",
+    ] {
+        context.insert(kind.to_string(), Value::Array(Vec::new()));
+    }
+
+    visit(&mut |record| {
+        let mut value = serde_json::to_value(&record)?;
+
+        let fields = value
+            .as_object_mut()
+            .ok_or("A record was not serialized as an object")?;
+
+        let kind = match fields.remove("kind") {
+            Some(Value::String(kind)) => kind,
+            _ => return Err("A record was serialized without its kind".into()),
+        };
+
+        let list = match kind.as_str() {
+            "file" => {
+                context.extend(std::mem::take(fields));
+                return Ok(());
+            }
+            "argument" => "arguments",
+            "instruction" => "instructions",
+            "debug_entry" => "debug_entries",
+            "section" => "sections",
+            "symbol" => "symbols",
+            _ => "data",
+        };
+
+        if let Some(Value::Array(records)) = context.get_mut(list) {
+            records.push(value);
+        }
+
+        Ok(())
+    })?;
+
+    Ok(Value::Object(context))
+}
@@ -0,0 +1,102 @@
+use serde_json::{json, Value};
+
+use super::FORMAT_VERSION;
+
+/// The schema of a record with the given kind and fields, all of which are required unless
+/// they are listed as optional
+fn record(kind: &str, properties: Value, optional: &[&str]) -> Value {
+    let mut properties = properties;
+    let fields = properties.as_object_mut().unwrap();
+
+    let mut required: Vec<String> = fields
+        .keys()
+        .filter(|field| !optional.contains(&field.as_str()))
+        .cloned()
+        .collect();
+    required.insert(0, String::from("kind"));
+
+    fields.insert(String::from("kind"), json!({ "const": kind }));
+
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required
+    })
+}
+
+/// The JSON Schema that every record in --ndjson output follows
+pub fn record_schema() -> Value {
+    let value = json!({ "type": ["null", "boolean", "number", "string"] });
+    let unsigned = json!({ "type": "integer", "minimum": 0 });
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "kDump record",
+        "description": format!("One record of kDump's machine readable output, format version {}", FORMAT_VERSION),
+        "oneOf": [
+            record("file", json!({
+                "format_version": { "const": FORMAT_VERSION },
+                "format": { "enum": ["ksm", "ko"] },
+                "attribution": {
+                    "type": "object",
+                    "properties": {
+                        "toolchain": { "type": ["string", "null"] },
+                        "confidence": { "type": "number" },
+                        "evidence": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "toolchain": { "type": "string" },
+                                    "pattern": { "type": "string" },
+                                    "weight": { "type": "number" }
+                                },
+                                "required": ["toolchain", "pattern", "weight"]
+                            }
+                        }
+                    },
+                    "required": ["toolchain", "confidence", "evidence"]
+                }
+            }), &["attribution"]),
+            record("argument", json!({
+                "index": unsigned,
+                "type": { "type": "string" },
+                "value": value
+            }), &[]),
+            record("instruction", json!({
+                "section": { "type": "string" },
+                "label": { "type": "string" },
+                "addr": unsigned,
+                "mnemonic": { "type": "string" },
+                "operands": { "type": "array", "items": value }
+            }), &[]),
+            record("debug_entry", json!({
+                "line": { "type": "integer" },
+                "ranges": {
+                    "type": "array",
+                    "items": { "type": "array", "items": unsigned, "minItems": 2, "maxItems": 2 }
+                }
+            }), &[]),
+            record("section", json!({
+                "index": unsigned,
+                "name": { "type": "string" },
+                "size": unsigned
+            }), &[]),
+            record("symbol", json!({
+                "table": { "type": "string" },
+                "name": { "type": "string" },
+                "value": unsigned,
+                "size": unsigned,
+                "binding": { "enum": ["local", "global", "extern"] },
+                "type": { "enum": ["notype", "object", "func", "section", "file"] },
+                "section": unsigned
+            }), &[]),
+            record("data", json!({
+                "section": { "type": "string" },
+                "index": unsigned,
+                "type": { "type": "string" },
+                "value": value
+            }), &[])
+        ]
+    })
+}
@@ -0,0 +1,289 @@
+use kerbalobjects::ksm::sections::{
+    ArgIndex, ArgumentSection, CodeSection, DebugEntry, DebugRange, DebugSection,
+};
+use kerbalobjects::ksm::{Instr, KSMFile};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use termcolor::WriteColor;
+
+use crate::index::IndexedKsm;
+use crate::output::format_size;
+use crate::{fio, pipeline, CLIConfig, NormalizeArgs};
+
+type NormalizeResult<T> = Result<T, Box<dyn Error>>;
+
+/// Rewrites a KSM file in a canonical form, so that builds of the same script from different
+/// machines come out byte-identical
+///
+/// The file is parsed and written back out in canonical order: arguments in the order the code
+/// first uses them, and debug entries sorted by line. Code sections keep
+/// their order, since every label in the file depends on it. The result is compressed with a zero
+/// timestamp, no file name, and the best compression level, so nothing about the machine it was
+/// made on ends up in it.
+pub fn normalize(
+    stream: &mut dyn WriteColor,
+    args: &NormalizeArgs,
+    config: &CLIConfig,
+) -> NormalizeResult<()> {
+    let file = pipeline::load_file(&args.input, None)?;
+    let original = fio::ksm_payload(file.raw_contents())?.into_owned();
+    let ksmfile = file
+        .into_ksm()
+        .ok_or_else(|| format!("{} is not a KSM file", args.input.display()))?;
+
+    let mut written = Vec::new();
+    canonical_ksm(ksmfile)?.write(&mut written);
+
+    // Whatever compression the writer used is replaced with the canonical one
+    let payload = fio::ksm_payload(&written)?;
+    let normalized = fio::compress_canonical(&payload)?;
+
+    fs::write(&args.output, &normalized)
+        .map_err(|e| format!("Failed to write {}: {}", args.output.display(), e))?;
+
+    writeln!(
+        stream,
+        "\nNormalized {} to {} ({})",
+        args.input.display(),
+        args.output.display(),
+        format_size(normalized.len(), config.human_readable)
+    )?;
+
+    if original[..] == payload[..] {
+        writeln!(
+            stream,
+            "  The payload is unchanged, only the compression was rewritten"
+        )?;
+    } else {
+        writeln!(
+            stream,
+            "  The payload was re-serialized, from {} to {}",
+            format_size(original.len(), config.human_readable),
+            format_size(payload.len(), config.human_readable)
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Puts the arguments and debug entries of a KSM file in canonical order, which changes nothing
+/// about how it runs
+///
+/// Arguments are only ever looked up by index, so they are laid out in the order the code first
+/// uses them, and unused ones are kept after those in their original order. Every argument is
+/// kept, even a repeated one, so that the section stays the same size and no instruction moves.
+/// Debug entries are only looked up by address, so they are sorted by line, and their ranges by
+/// address.
+fn canonical_ksm(ksmfile: KSMFile) -> NormalizeResult<KSMFile> {
+    let index = IndexedKsm::new(&ksmfile);
+    let mut arg_section = ArgumentSection::new();
+    let mut moved: HashMap<usize, ArgIndex> = HashMap::new();
+
+    let mut code_sections = Vec::new();
+
+    for code_section in ksmfile.code_sections() {
+        let mut canonical = CodeSection::new(code_section.section_type);
+
+        for instr in code_section.instructions() {
+            let mut move_arg = |op: ArgIndex| -> NormalizeResult<ArgIndex> {
+                let old = usize::from(op);
+
+                if let Some(new) = moved.get(&old) {
+                    return Ok(*new);
+                }
+
+                let value = index.value(op).ok_or_else(|| {
+                    format!("An instruction refers to invalid argument index {:x}", old)
+                })?;
+                let new = arg_section.add(value.clone());
+
+                moved.insert(old, new);
+
+                Ok(new)
+            };
+
+            canonical.add(match *instr {
+                Instr::ZeroOp(opcode) => Instr::ZeroOp(opcode),
+                Instr::OneOp(opcode, op1) => Instr::OneOp(opcode, move_arg(op1)?),
+                Instr::TwoOp(opcode, op1, op2) => {
+                    Instr::TwoOp(opcode, move_arg(op1)?, move_arg(op2)?)
+                }
+            });
+        }
+
+        code_sections.push(canonical);
+    }
+
+    for (old, value) in index.arguments() {
+        if !moved.contains_key(&old) {
+            arg_section.add(value.clone());
+        }
+    }
+
+    let mut entries: Vec<(isize, Vec<(usize, usize)>)> = ksmfile
+        .debug_section
+        .debug_entries()
+        .map(|entry| {
+            let mut ranges: Vec<(usize, usize)> = entry
+                .ranges()
+                .map(|range| (range.start, range.end))
+                .collect();
+            ranges.sort_unstable();
+
+            (entry.line_number, ranges)
+        })
+        .collect();
+    entries.sort();
+
+    let mut entries = entries.into_iter().map(|(line_number, ranges)| {
+        ranges
+            .into_iter()
+            .fold(DebugEntry::new(line_number), |entry, (start, end)| {
+                entry.with_range(DebugRange::new(start, end))
+            })
+    });
+
+    let debug_section = match entries.next() {
+        Some(first) => {
+            let mut debug_section = DebugSection::new(first);
+            entries.for_each(|entry| debug_section.add(entry));
+            debug_section
+        }
+        // With no entries, there is nothing to put in order
+        None => ksmfile.debug_section,
+    };
+
+    Ok(KSMFile::new_from_parts(
+        arg_section,
+        code_sections,
+        debug_section,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kerbalobjects::{KOSValue, Opcode};
+
+    use crate::index::instr_operands;
+    use crate::testing::{ksm_with, written};
+
+    /// set x to 1. print(x)., with the arguments and debug entries laid out in either order
+    fn build(reversed: bool) -> KSMFile {
+        let mut values = [
+            KOSValue::Int16(1),
+            KOSValue::String(String::from("$x")),
+            KOSValue::ArgMarker,
+            KOSValue::String(String::from("print()")),
+            KOSValue::String(String::new()),
+            KOSValue::StringValue(String::from("unused")),
+        ];
+
+        if reversed {
+            values.reverse();
+        }
+
+        let x = [KOSValue::String(String::from("$x"))];
+        let mut ksmfile = ksm_with(
+            &values,
+            &[],
+            &[
+                &[(Opcode::Push, &[KOSValue::Int16(1)]), (Opcode::Stog, &x)],
+                &[
+                    (Opcode::Push, &[KOSValue::ArgMarker]),
+                    (Opcode::Push, &x),
+                    (
+                        Opcode::Call,
+                        &[
+                            KOSValue::String(String::from("print()")),
+                            KOSValue::String(String::new()),
+                        ],
+                    ),
+                    (Opcode::Pop, &[]),
+                ],
+            ],
+        );
+
+        if !reversed {
+            return ksmfile;
+        }
+
+        let mut entries = ksmfile.debug_section.debug_entries().rev().map(|entry| {
+            entry
+                .ranges()
+                .fold(DebugEntry::new(entry.line_number), |reversed, range| {
+                    reversed.with_range(DebugRange::new(range.start, range.end))
+                })
+        });
+        let mut debug_section = DebugSection::new(entries.next().unwrap());
+        entries.for_each(|entry| debug_section.add(entry));
+
+        ksmfile.debug_section = debug_section;
+        ksmfile
+    }
+
+    fn payload(ksmfile: &KSMFile) -> Vec<u8> {
+        fio::ksm_payload(&written(ksmfile)).unwrap().into_owned()
+    }
+
+    /// The value of each operand of each instruction, which is what the file means
+    fn operand_values(ksmfile: &KSMFile) -> Vec<Vec<KOSValue>> {
+        let index = IndexedKsm::new(ksmfile);
+
+        ksmfile
+            .code_sections()
+            .flat_map(|code_section| code_section.instructions())
+            .map(|instr| {
+                let ops = instr_operands(instr);
+
+                ops.into_iter()
+                    .map(|op| index.value(op).unwrap().clone())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn different_layouts_come_out_the_same() {
+        let forwards = build(false);
+        let backwards = build(true);
+
+        assert_ne!(payload(&forwards), payload(&backwards));
+
+        let canonical_forwards = canonical_ksm(build(false)).unwrap();
+        let canonical_backwards = canonical_ksm(build(true)).unwrap();
+
+        assert_eq!(payload(&canonical_forwards), payload(&canonical_backwards));
+        assert_eq!(
+            operand_values(&canonical_forwards),
+            operand_values(&forwards)
+        );
+    }
+
+    #[test]
+    fn keeps_every_argument() {
+        let ksmfile = build(true);
+        let size = payload(&ksmfile).len();
+        let canonical = canonical_ksm(ksmfile).unwrap();
+
+        assert_eq!(canonical.arg_section.arguments().count(), 6);
+        assert_eq!(
+            canonical.arg_section.arguments().last(),
+            Some(&KOSValue::StringValue(String::from("unused")))
+        );
+        assert_eq!(payload(&canonical).len(), size);
+    }
+
+    #[test]
+    fn sorts_debug_entries_by_line() {
+        let canonical = canonical_ksm(build(true)).unwrap();
+        let lines: Vec<isize> = canonical
+            .debug_section
+            .debug_entries()
+            .map(|entry| entry.line_number)
+            .collect();
+
+        assert_eq!(lines, vec![1, 2]);
+    }
+}
@@ -0,0 +1,284 @@
+use kerbalobjects::Opcode;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use termcolor::WriteColor;
+
+use crate::SizeFormat;
+
+use super::DumpResult;
+
+/// Everything one opcode costs across every place it is used in a file
+#[derive(Debug, Clone, Copy)]
+pub struct OpcodeCost {
+    pub opcode: Opcode,
+    pub count: usize,
+    pub operand_bytes: usize,
+}
+
+impl OpcodeCost {
+    /// The opcode bytes and the operand bytes together
+    pub fn total_bytes(&self) -> usize {
+        self.count + self.operand_bytes
+    }
+}
+
+/// Adds up the cost of each opcode, given every instruction as its opcode and number of operands
+pub fn opcode_costs(
+    instrs: impl Iterator<Item = (Opcode, usize)>,
+    operand_size: usize,
+) -> Vec<OpcodeCost> {
+    let mut costs: Vec<OpcodeCost> = Vec::new();
+
+    for (opcode, num_operands) in instrs {
+        let cost = match costs.iter_mut().find(|cost| cost.opcode == opcode) {
+            Some(cost) => cost,
+            None => {
+                costs.push(OpcodeCost {
+                    opcode,
+                    count: 0,
+                    operand_bytes: 0,
+                });
+
+                costs.last_mut().unwrap()
+            }
+        };
+
+        cost.count += 1;
+        cost.operand_bytes += num_operands * operand_size;
+    }
+
+    // The most expensive first, since those are the ones worth doing something about
+    costs.sort_by(|a, b| {
+        b.total_bytes()
+            .cmp(&a.total_bytes())
+            .then(a.count.cmp(&b.count))
+    });
+
+    costs
+}
+
+/// Writes how many bytes of code each opcode accounts for, counting both the opcode byte and the
+/// operands of every instruction that uses it
+pub fn dump_opcode_costs(
+    stream: &mut dyn WriteColor,
+    costs: &[OpcodeCost],
+    size_format: Option<SizeFormat>,
+) -> DumpResult {
+    let total: usize = costs.iter().map(OpcodeCost::total_bytes).sum();
+
+    writeln!(stream, "\nBytes per opcode:")?;
+    writeln!(
+        stream,
+        "  {:<8}{:>8}  {:>16}  {:>16}  {:>16}  {:>6}",
+        "Opcode", "Count", "Opcode bytes", "Operand bytes", "Total", "Share"
+    )?;
+
+    for cost in costs {
+        let mnemonic: &str = cost.opcode.into();
+        let share = (cost.total_bytes() * 1000).checked_div(total).unwrap_or(0);
+
+        writeln!(
+            stream,
+            "  {:<8}{:>8}  {:>16}  {:>16}  {:>16}  {:>3}.{}%",
+            mnemonic,
+            cost.count,
+            super::format_size(cost.count, size_format),
+            super::format_size(cost.operand_bytes, size_format),
+            super::format_size(cost.total_bytes(), size_format),
+            share / 10,
+            share % 10
+        )?;
+    }
+
+    writeln!(
+        stream,
+        "  {:<8}{:>8}  {:>16}  {:>16}  {:>16}",
+        "Total",
+        costs.iter().map(|cost| cost.count).sum::<usize>(),
+        "",
+        "",
+        super::format_size(total, size_format)
+    )?;
+
+    Ok(())
+}
+
+/// The code that one source line compiled to, or the code that no debug range covers
+#[derive(Debug, Clone, Copy)]
+pub struct LineSize {
+    pub line: Option<isize>,
+    pub instructions: usize,
+    pub bytes: usize,
+}
+
+/// The number of instructions kOS runs each physics tick unless the config changes it
+pub const KOS_DEFAULT_IPU: usize = 200;
+
+/// Adds up how much code each source line produced, given every instruction as the line it came
+/// from and its size in bytes
+pub fn line_sizes(instrs: impl Iterator<Item = (Option<isize>, usize)>) -> Vec<LineSize> {
+    let mut by_line: BTreeMap<Option<isize>, LineSize> = BTreeMap::new();
+
+    for (line, size) in instrs {
+        let entry = by_line.entry(line).or_insert(LineSize {
+            line,
+            instructions: 0,
+            bytes: 0,
+        });
+
+        entry.instructions += 1;
+        entry.bytes += size;
+    }
+
+    let mut sizes: Vec<LineSize> = by_line.into_values().collect();
+
+    // The biggest first, and the stable sort keeps ties in line order
+    sizes.sort_by_key(|size| Reverse(size.bytes));
+
+    sizes
+}
+
+/// Adds up how many instructions each source line produced, the most first
+pub fn line_costs(instrs: impl Iterator<Item = (Option<isize>, usize)>) -> Vec<LineSize> {
+    let mut costs = line_sizes(instrs);
+
+    costs.sort_by_key(|cost| Reverse(cost.instructions));
+
+    costs
+}
+
+/// Writes the text of a source line after a row of a table, if the source file was given
+fn write_source_text(
+    stream: &mut dyn WriteColor,
+    line: Option<isize>,
+    source: Option<&[String]>,
+) -> DumpResult {
+    let text = line
+        .and_then(|line| usize::try_from(line - 1).ok())
+        .and_then(|i| source.and_then(|source| source.get(i)));
+
+    if let Some(text) = text {
+        write!(stream, "  {}", text.trim())?;
+    }
+
+    Ok(())
+}
+
+/// Writes how many bytes of code each source line compiled to, along with the text of each line
+/// if the source file was given
+pub fn dump_line_sizes(
+    stream: &mut dyn WriteColor,
+    sizes: &[LineSize],
+    source: Option<&[String]>,
+    size_format: Option<SizeFormat>,
+) -> DumpResult {
+    let total: usize = sizes.iter().map(|size| size.bytes).sum();
+
+    writeln!(stream, "\nBytes per source line:")?;
+    write!(
+        stream,
+        "  {:<8}{:>8}  {:>16}  {:>6}",
+        "Line", "Instrs", "Bytes", "Share"
+    )?;
+
+    if source.is_some() {
+        write!(stream, "  Source")?;
+    }
+
+    writeln!(stream)?;
+
+    for size in sizes {
+        let line = match size.line {
+            Some(line) => line.to_string(),
+            None => String::from("-"),
+        };
+        let share = (size.bytes * 1000).checked_div(total).unwrap_or(0);
+
+        write!(
+            stream,
+            "  {:<8}{:>8}  {:>16}  {:>3}.{}%",
+            line,
+            size.instructions,
+            super::format_size(size.bytes, size_format),
+            share / 10,
+            share % 10
+        )?;
+
+        write_source_text(stream, size.line, source)?;
+        writeln!(stream)?;
+    }
+
+    writeln!(
+        stream,
+        "  {:<8}{:>8}  {:>16}",
+        "Total",
+        sizes.iter().map(|size| size.instructions).sum::<usize>(),
+        super::format_size(total, size_format)
+    )?;
+
+    if sizes.iter().any(|size| size.line.is_none()) {
+        writeln!(stream, "  Line - is code that no debug range covers")?;
+    }
+
+    Ok(())
+}
+
+/// Writes how many instructions each source line compiled to, and how much of a physics tick's
+/// instruction budget running each one once would use at the default IPU
+///
+/// Each instruction counts as one against the IPU, so this is only an estimate: a line in a loop
+/// runs many times, and calls to built in functions can take more than that.
+pub fn dump_line_costs(
+    stream: &mut dyn WriteColor,
+    costs: &[LineSize],
+    source: Option<&[String]>,
+) -> DumpResult {
+    writeln!(
+        stream,
+        "\nInstructions per source line, against an IPU of {}:",
+        KOS_DEFAULT_IPU
+    )?;
+    write!(stream, "  {:<8}{:>8}  {:>8}", "Line", "Instrs", "Of IPU")?;
+
+    if source.is_some() {
+        write!(stream, "  Source")?;
+    }
+
+    writeln!(stream)?;
+
+    for cost in costs {
+        let line = match cost.line {
+            Some(line) => line.to_string(),
+            None => String::from("-"),
+        };
+        let share = cost.instructions * 1000 / KOS_DEFAULT_IPU;
+
+        write!(
+            stream,
+            "  {:<8}{:>8}  {:>5}.{}%",
+            line,
+            cost.instructions,
+            share / 10,
+            share % 10
+        )?;
+
+        write_source_text(stream, cost.line, source)?;
+        writeln!(stream)?;
+    }
+
+    let total: usize = costs.iter().map(|cost| cost.instructions).sum();
+
+    writeln!(
+        stream,
+        "  {:<8}{:>8}  {:>8}",
+        "Total",
+        total,
+        format!("{:.1} ticks", total as f64 / KOS_DEFAULT_IPU as f64)
+    )?;
+    writeln!(
+        stream,
+        "  Each line is counted once, so lines in loops cost that much every time around"
+    )?;
+
+    Ok(())
+}
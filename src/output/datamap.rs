@@ -0,0 +1,134 @@
+use kerbalobjects::ko::sections::{DataSection, StringTable};
+use serde::Serialize;
+use serde_json::Value;
+use std::io::Write;
+
+use crate::model;
+use crate::StringEncoding;
+
+use super::DumpResult;
+
+/// Where every data entry and string in a KO file's data sections and string tables is
+#[derive(Debug, Serialize)]
+struct DataMap<'a> {
+    format_version: u32,
+    file: &'a str,
+    data_sections: Vec<SectionEntry<'a, DataEntry>>,
+    string_tables: Vec<SectionEntry<'a, StringEntry<'a>>>,
+}
+
+#[derive(Debug, Serialize)]
+struct SectionEntry<'a, T> {
+    name: &'a str,
+    /// The size of everything in the section together, which is where the next entry would go
+    size: usize,
+    entries: Vec<T>,
+}
+
+#[derive(Debug, Serialize)]
+struct DataEntry {
+    index: usize,
+    offset: usize,
+    size: usize,
+    #[serde(rename = "type")]
+    value_type: &'static str,
+    value: Value,
+}
+
+/// One string, where the offset is the same as the index that symbols and relocations use for it
+#[derive(Debug, Serialize)]
+struct StringEntry<'a> {
+    offset: usize,
+    /// The size of the string in bytes, including its null terminator
+    size: usize,
+    value: &'a str,
+}
+
+/// Writes the byte offset and size of every entry in a KO file's data sections and string tables
+/// as JSON, so that the layout a linker chose can be checked by a script
+///
+/// Unless the JSON is pretty-printed, each section is written as soon as it is worked out, and the
+/// JSON is compact.
+pub fn write_ko_data_json(
+    out: &mut dyn Write,
+    title: &str,
+    data_sections: &[(&str, &DataSection)],
+    string_tables: &[(&str, &StringTable)],
+    encoding: StringEncoding,
+    pretty: bool,
+) -> DumpResult {
+    let data_sections = data_sections.iter().map(|(name, data_section)| {
+        let mut offset = 0;
+
+        let entries = data_section
+            .data()
+            .enumerate()
+            .map(|(index, value)| {
+                let entry = DataEntry {
+                    index,
+                    offset,
+                    size: value.size_bytes(),
+                    value_type: super::kosvalue_type_str(value),
+                    value: model::value_json(value, encoding),
+                };
+
+                offset += entry.size;
+
+                entry
+            })
+            .collect();
+
+        SectionEntry {
+            name,
+            size: offset,
+            entries,
+        }
+    });
+
+    let string_tables = string_tables.iter().map(|(name, strtab)| {
+        let mut offset = 0;
+
+        let entries = strtab
+            .strings()
+            .map(|s| {
+                let entry = StringEntry {
+                    offset,
+                    size: s.len() + 1,
+                    value: s,
+                };
+
+                offset += entry.size;
+
+                entry
+            })
+            .collect();
+
+        SectionEntry {
+            name,
+            size: offset,
+            entries,
+        }
+    });
+
+    if pretty {
+        serde_json::to_writer_pretty(
+            &mut *out,
+            &DataMap {
+                format_version: model::FORMAT_VERSION,
+                file: title,
+                data_sections: data_sections.collect(),
+                string_tables: string_tables.collect(),
+            },
+        )?;
+        writeln!(out)?;
+
+        return Ok(());
+    }
+
+    super::start_json_stream(out, title)?;
+    super::write_json_stream_list(out, "data_sections", data_sections)?;
+    super::write_json_stream_list(out, "string_tables", string_tables)?;
+    writeln!(out, "}}")?;
+
+    Ok(())
+}
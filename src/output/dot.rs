@@ -0,0 +1,77 @@
+use std::io::Write;
+
+use crate::analysis::{BasicBlock, CallGraph};
+
+use super::DumpResult;
+
+/// Escapes a string so that it can be put inside of a quoted DOT identifier
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Writes the control flow graph of one code section as a DOT digraph, with one node per basic
+/// block that lists the instructions in it
+///
+/// The lines are the already formatted instructions of the section, indexed the same way as the blocks.
+pub fn write_cfg(
+    out: &mut dyn Write,
+    name: &str,
+    blocks: &[BasicBlock],
+    lines: &[String],
+) -> DumpResult {
+    writeln!(out, "digraph \"{}\" {{", escape(name))?;
+    writeln!(out, "  node [shape=box, fontname=\"monospace\"];")?;
+
+    for (block_index, block) in blocks.iter().enumerate() {
+        let mut label = String::new();
+
+        for line in &lines[block.start..block.end] {
+            label.push_str(&escape(line));
+            label.push_str("\\l");
+        }
+
+        writeln!(out, "  b{} [label=\"{}\"];", block_index, label)?;
+    }
+
+    for (block_index, block) in blocks.iter().enumerate() {
+        for successor in &block.successors {
+            writeln!(out, "  b{} -> b{};", block_index, successor)?;
+        }
+    }
+
+    writeln!(out, "}}")?;
+
+    Ok(())
+}
+
+/// Writes a call graph as a DOT digraph, with the functions outside of the file drawn dashed,
+/// and each edge labelled with the number of calls when there is more than one
+pub fn write_callgraph(out: &mut dyn Write, name: &str, graph: &CallGraph) -> DumpResult {
+    writeln!(out, "digraph \"{}\" {{", escape(name))?;
+    writeln!(out, "  node [shape=box, fontname=\"monospace\"];")?;
+
+    for (node_index, node) in graph.nodes.iter().enumerate() {
+        if node.external {
+            writeln!(
+                out,
+                "  n{} [label=\"{}\", style=dashed];",
+                node_index,
+                escape(&node.name)
+            )?;
+        } else {
+            writeln!(out, "  n{} [label=\"{}\"];", node_index, escape(&node.name))?;
+        }
+    }
+
+    for (&(from, to), &count) in &graph.edges {
+        if count > 1 {
+            writeln!(out, "  n{} -> n{} [label=\"{}\"];", from, to, count)?;
+        } else {
+            writeln!(out, "  n{} -> n{};", from, to)?;
+        }
+    }
+
+    writeln!(out, "}}")?;
+
+    Ok(())
+}
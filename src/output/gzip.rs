@@ -0,0 +1,64 @@
+use termcolor::WriteColor;
+
+use crate::fio::GzipScan;
+use crate::SizeFormat;
+
+use super::hexdump::dump_hex;
+use super::{format_size, DumpResult};
+
+/// Writes what was found in a compressed KSM file besides the one gzip member the parser reads,
+/// and optionally the contents of each extra member
+pub fn dump_gzip_anomalies(
+    stream: &mut dyn WriteColor,
+    scan: &GzipScan,
+    dump_members: bool,
+    size_format: Option<SizeFormat>,
+) -> DumpResult {
+    writeln!(stream, "\nGzip stream anomalies:")?;
+
+    if scan.members.len() > 1 {
+        writeln!(
+            stream,
+            "  The file has {} concatenated gzip members, which are each dumped as a KSM file",
+            scan.members.len()
+        )?;
+
+        for (index, member) in scan.members.iter().enumerate() {
+            writeln!(
+                stream,
+                "    Member {} at 0x{:x}: {}, {} decompressed",
+                index,
+                member.offset,
+                format_size(member.compressed_size, size_format),
+                format_size(member.contents.len(), size_format)
+            )?;
+        }
+    }
+
+    if let Some(trailing) = &scan.trailing {
+        writeln!(
+            stream,
+            "  {} of trailing data after the gzip stream, at 0x{:x}",
+            format_size(trailing.len(), size_format),
+            trailing.start
+        )?;
+
+        if let Some(error) = &scan.error {
+            writeln!(
+                stream,
+                "    It starts like a gzip member, but could not be read: {}",
+                error
+            )?;
+        }
+    }
+
+    if dump_members {
+        for (index, member) in scan.members.iter().enumerate().skip(1) {
+            writeln!(stream, "\nContents of gzip member {}:", index)?;
+
+            dump_hex(stream, &member.contents, 0)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use kerbalobjects::ko::KOFile;
+use termcolor::WriteColor;
+
+use crate::fio::layout;
+
+use super::DumpResult;
+
+const BYTES_PER_LINE: usize = 16;
+
+/// Writes bytes in the canonical hex+ASCII layout, 16 bytes to a line, with offsets counted from
+/// the given base
+pub fn dump_hex(stream: &mut dyn WriteColor, bytes: &[u8], base: usize) -> DumpResult {
+    for (line_index, line) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+        write!(stream, "{:0>8x} ", base + line_index * BYTES_PER_LINE)?;
+
+        for i in 0..BYTES_PER_LINE {
+            // An extra space down the middle, to make it easier to count
+            if i % 8 == 0 {
+                write!(stream, " ")?;
+            }
+
+            match line.get(i) {
+                Some(byte) => write!(stream, "{:0>2x} ", byte)?,
+                None => write!(stream, "   ")?,
+            }
+        }
+
+        let ascii: String = line
+            .iter()
+            .map(|byte| {
+                if byte.is_ascii_graphic() || *byte == b' ' {
+                    *byte as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+
+        writeln!(stream, " |{}|", ascii)?;
+    }
+
+    writeln!(stream, "{:0>8x}", base + bytes.len())?;
+
+    Ok(())
+}
+
+/// Writes the decompressed body of a KSM file as a hex dump
+pub fn dump_ksm_hex(stream: &mut dyn WriteColor, payload: &[u8]) -> DumpResult {
+    writeln!(stream, "\nContents of the decompressed KSM body:")?;
+
+    dump_hex(stream, payload, 0)
+}
+
+/// Writes a KO file as a hex dump, either all of it or only the section with the given name
+pub fn dump_ko_hex(
+    stream: &mut dyn WriteColor,
+    kofile: &KOFile,
+    contents: &[u8],
+    section_name: Option<&str>,
+) -> DumpResult {
+    let section_name = match section_name {
+        Some(section_name) => section_name,
+        None => {
+            writeln!(stream, "\nContents of the file:")?;
+
+            return dump_hex(stream, contents, 0);
+        }
+    };
+
+    let range = layout::ko_section_range(kofile, section_name)
+        .ok_or(format!("No section named {} was found", section_name))?;
+
+    let bytes = contents.get(range.clone()).ok_or(format!(
+        "Section {} runs past the end of the file",
+        section_name
+    ))?;
+
+    writeln!(stream, "\nContents of section {}:", section_name)?;
+
+    dump_hex(stream, bytes, range.start)
+}
@@ -0,0 +1,576 @@
+use kerbalobjects::ko::sections::SymbolIdx;
+use kerbalobjects::ko::symbols::SymType;
+use kerbalobjects::ko::{KOFile, SectionIdx};
+use kerbalobjects::ksm::{Instr, KSMFile};
+use kerbalobjects::{KOSValue, Opcode};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{self, Write};
+use termcolor::{Color, ColorSpec, WriteColor};
+
+use crate::index::{instr_opcode, instr_operands, ko_instr_operands, IndexedKsm};
+use crate::StringEncoding;
+use crate::DARK_RED_COLOR;
+use crate::GREEN_COLOR;
+use crate::LIGHT_RED_COLOR;
+use crate::ORANGE_COLOR;
+use crate::PURPLE_COLOR;
+use crate::VERSION;
+
+use super::ko::OperandRelocs;
+use super::DumpResult;
+use super::KOFileDebug;
+
+/// Escapes the characters that have a meaning in HTML text and attributes
+fn escape(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '"']) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    Cow::Owned(escaped)
+}
+
+/// The CSS form of one of the terminal colors, so that the page looks like the terminal output
+fn css_color(color: Color) -> String {
+    match color {
+        Color::Rgb(r, g, b) => format!("#{:0>2x}{:0>2x}{:0>2x}", r, g, b),
+        _ => String::from("inherit"),
+    }
+}
+
+/// The class a mnemonic is colored with, which groups the instructions that change control flow
+fn mnemonic_class(opcode: Opcode) -> &'static str {
+    match opcode {
+        Opcode::Jmp | Opcode::Bfa | Opcode::Btr | Opcode::Call | Opcode::Ret | Opcode::Eop => {
+            "mn flow"
+        }
+        Opcode::Lbrt => "mn label",
+        Opcode::Bogus => "mn bogus",
+        _ => "mn",
+    }
+}
+
+fn write_header(out: &mut impl Write, title: &str) -> DumpResult {
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>{}</title>", escape(title))?;
+    writeln!(out, "<style>")?;
+    writeln!(
+        out,
+        "body {{ background: #1e1e1e; color: #ffffff; font-family: monospace; }}"
+    )?;
+    writeln!(out, "a {{ color: inherit; text-decoration: none; }}")?;
+    writeln!(out, "a:hover {{ text-decoration: underline; }}")?;
+    writeln!(out, ":target {{ background: #3a3a3a; }}")?;
+    writeln!(out, "td, th {{ padding: 0 1em 0 0; text-align: left; }}")?;
+    writeln!(out, ".label {{ color: {}; }}", css_color(PURPLE_COLOR))?;
+    writeln!(out, ".mn {{ color: {}; }}", css_color(DARK_RED_COLOR))?;
+    writeln!(out, ".mn.flow {{ color: {}; }}", css_color(ORANGE_COLOR))?;
+    writeln!(out, ".mn.bogus {{ text-decoration: line-through; }}")?;
+    writeln!(out, ".type {{ color: {}; }}", css_color(GREEN_COLOR))?;
+    writeln!(out, ".var {{ color: {}; }}", css_color(LIGHT_RED_COLOR))?;
+    writeln!(out, ".sym {{ color: {}; }}", css_color(PURPLE_COLOR))?;
+    writeln!(out, "</style>\n</head>\n<body>")?;
+    writeln!(
+        out,
+        "<h1>{}</h1>\n<p>Generated by kDump version {}</p>",
+        escape(title),
+        VERSION
+    )?;
+
+    Ok(())
+}
+
+fn write_footer(out: &mut impl Write) -> DumpResult {
+    writeln!(out, "</body>\n</html>")?;
+
+    Ok(())
+}
+
+/// A stream that the normal terminal output can be written to, which turns it into an HTML
+/// page with each color change becoming a styled span
+pub struct HtmlColorWriter<W: Write> {
+    out: W,
+    in_span: bool,
+}
+
+impl<W: Write> HtmlColorWriter<W> {
+    /// Writes the start of the page, so that everything written after is inside of it
+    pub fn new(mut out: W, title: &str) -> Result<Self, Box<dyn Error>> {
+        write_header(&mut out, title)?;
+        writeln!(out, "<pre>")?;
+
+        Ok(Self {
+            out,
+            in_span: false,
+        })
+    }
+
+    /// Closes any open span and writes the end of the page
+    pub fn finish(mut self) -> DumpResult {
+        self.reset()?;
+
+        writeln!(self.out, "</pre>")?;
+        write_footer(&mut self.out)?;
+
+        self.out.flush()?;
+
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for HtmlColorWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Escaping happens a byte at a time, which can't split a UTF-8 character because they never contain these
+        for &byte in buf {
+            match byte {
+                b'&' => self.out.write_all(b"&amp;")?,
+                b'<' => self.out.write_all(b"&lt;")?,
+                b'>' => self.out.write_all(b"&gt;")?,
+                _ => self.out.write_all(&[byte])?,
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+impl<W: Write> WriteColor for HtmlColorWriter<W> {
+    fn supports_color(&self) -> bool {
+        true
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        self.reset()?;
+
+        if let Some(&color) = spec.fg() {
+            write!(self.out, "<span style=\"color: {}\">", css_color(color))?;
+            self.in_span = true;
+        }
+
+        Ok(())
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        if self.in_span {
+            write!(self.out, "</span>")?;
+            self.in_span = false;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes a value as HTML, with strings quoted and variable names colored
+fn write_value(out: &mut impl Write, value: &KOSValue, encoding: StringEncoding) -> DumpResult {
+    let text = super::kosvalue_str(value, encoding);
+
+    match value {
+        KOSValue::String(s) | KOSValue::StringValue(s) if s.starts_with('$') => {
+            write!(out, "\"<span class=\"var\">{}</span>\"", escape(&text))?
+        }
+        KOSValue::String(_) | KOSValue::StringValue(_) => write!(out, "\"{}\"", escape(&text))?,
+        _ => write!(out, "{}", escape(&text))?,
+    }
+
+    Ok(())
+}
+
+/// Writes one KSM instruction with its label, and every operand linked to its argument
+fn write_ksm_instr(
+    out: &mut impl Write,
+    index: &IndexedKsm,
+    instr: &Instr,
+    label: &str,
+    encoding: StringEncoding,
+) -> DumpResult {
+    let opcode = instr_opcode(instr);
+    let mnemonic: &str = opcode.into();
+
+    write!(
+        out,
+        "<span class=\"label\">{}</span> <span class=\"{}\">{:<5}</span>",
+        escape(label),
+        mnemonic_class(opcode),
+        mnemonic
+    )?;
+
+    let operands = instr_operands(instr);
+
+    for (i, operand) in operands.into_iter().enumerate() {
+        write!(out, "{}", if i == 0 { " " } else { ", " })?;
+
+        let operand_index = usize::from(operand);
+
+        match index.value(operand) {
+            Some(value) => {
+                write!(out, "<a href=\"#arg-{:x}\">", operand_index)?;
+                write_value(out, value, encoding)?;
+                write!(out, "</a>")?;
+            }
+            None => write!(out, "&lt;invalid {:x}&gt;", operand_index)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a KSM file as a standalone HTML page, with every operand linked to the argument
+/// section entry it refers to
+///
+/// The section names are given in the same order as the file's code sections.
+pub fn write_ksm_page(
+    out: &mut impl Write,
+    title: &str,
+    index: &IndexedKsm,
+    section_names: &[&str],
+    encoding: StringEncoding,
+) -> DumpResult {
+    let ksmfile: &KSMFile = index.ksmfile();
+    let width = index.index_width() * 2;
+
+    write_header(out, title)?;
+
+    writeln!(out, "<h2 id=\"arguments\">Argument section</h2>")?;
+    writeln!(
+        out,
+        "<table>\n<tr><th>Index</th><th>Type</th><th>Value</th></tr>"
+    )?;
+
+    for (arg_index, value) in index.arguments() {
+        write!(
+            out,
+            "<tr id=\"arg-{0:x}\"><td>{0:0>width$x}</td><td class=\"type\">{1}</td><td>",
+            arg_index,
+            super::kosvalue_type_str(value),
+            width = width
+        )?;
+        write_value(out, value, encoding)?;
+        writeln!(out, "</td></tr>")?;
+    }
+
+    writeln!(out, "</table>")?;
+
+    writeln!(out, "<h2 id=\"code\">Code sections</h2>")?;
+
+    for (section_index, code_section) in ksmfile.code_sections().enumerate() {
+        let name = section_names.get(section_index).copied().unwrap_or("");
+
+        writeln!(
+            out,
+            "<h3 id=\"section-{}\">{}</h3>\n<pre>",
+            section_index,
+            escape(name)
+        )?;
+
+        let locations = index.locations(section_index);
+
+        for (instr, location) in code_section.instructions().zip(locations) {
+            write!(out, "  ")?;
+            write_ksm_instr(out, index, instr, &location.label, encoding)?;
+            writeln!(out)?;
+        }
+
+        writeln!(out, "</pre>")?;
+    }
+
+    write_footer(out)
+}
+
+/// The styles and script that make the interactive page work, kept inline so that the page is a
+/// single file that can be shared
+const INTERACTIVE_HEAD: &str = r#"<style>
+.app { display: flex; height: 85vh; border-top: 1px solid #3a3a3a; }
+nav { width: 16em; overflow-y: auto; padding: 0.5em; border-right: 1px solid #3a3a3a; flex-shrink: 0; }
+nav a { display: block; white-space: nowrap; overflow: hidden; text-overflow: ellipsis; }
+nav input { width: 100%; box-sizing: border-box; margin-bottom: 0.5em; background: #2a2a2a; color: inherit; border: 1px solid #3a3a3a; font-family: monospace; }
+main { flex-grow: 1; overflow-y: auto; padding: 0 1em; }
+summary { cursor: pointer; font-weight: bold; margin-top: 0.5em; }
+.code { white-space: pre; }
+.ln.hidden, nav a.hidden, details.hidden { display: none; }
+</style>
+<script>
+function search(query) {
+  query = query.toLowerCase();
+  document.querySelectorAll("details.fn").forEach(function (fn) {
+    var any = false;
+    fn.querySelectorAll(".ln").forEach(function (ln) {
+      var hit = query === "" || ln.textContent.toLowerCase().indexOf(query) !== -1;
+      ln.classList.toggle("hidden", !hit);
+      any = any || hit;
+    });
+    var name = fn.dataset.name.toLowerCase();
+    var shown = any || name.indexOf(query) !== -1;
+    fn.classList.toggle("hidden", !shown);
+    if (query !== "" && shown) { fn.open = true; }
+    var link = document.querySelector("nav a[href='#" + fn.id + "']");
+    if (link) { link.classList.toggle("hidden", !shown); }
+  });
+}
+function setAll(open) {
+  document.querySelectorAll("details.fn").forEach(function (fn) { fn.open = open; });
+}
+</script>
+"#;
+
+/// Writes a KSM file as a single page app, with a sidebar listing every code section, a search
+/// box that filters the instructions, and each code section in a block that can be collapsed
+///
+/// The section names are given in the same order as the file's code sections.
+pub fn write_ksm_interactive_page(
+    out: &mut impl Write,
+    title: &str,
+    index: &IndexedKsm,
+    section_names: &[&str],
+    encoding: StringEncoding,
+) -> DumpResult {
+    let ksmfile: &KSMFile = index.ksmfile();
+    let width = index.index_width() * 2;
+
+    write_header(out, title)?;
+    writeln!(out, "{}", INTERACTIVE_HEAD)?;
+
+    writeln!(out, "<div class=\"app\">\n<nav>")?;
+    writeln!(
+        out,
+        "<input type=\"search\" placeholder=\"Search\" oninput=\"search(this.value)\">"
+    )?;
+    writeln!(
+        out,
+        "<a href=\"javascript:setAll(true)\">Expand all</a><a href=\"javascript:setAll(false)\">Collapse all</a><hr>"
+    )?;
+    writeln!(out, "<a href=\"#arguments\">Argument section</a>")?;
+
+    for (section_index, _) in ksmfile.code_sections().enumerate() {
+        let name = section_names.get(section_index).copied().unwrap_or("");
+
+        writeln!(
+            out,
+            "<a href=\"#section-{}\" class=\"label\">{}</a>",
+            section_index,
+            escape(name)
+        )?;
+    }
+
+    writeln!(out, "</nav>\n<main>")?;
+
+    writeln!(
+        out,
+        "<details id=\"arguments\" class=\"fn\" data-name=\"arguments\"><summary>Argument section</summary><div class=\"code\">"
+    )?;
+
+    for (arg_index, value) in index.arguments() {
+        write!(
+            out,
+            "<div class=\"ln\" id=\"arg-{0:x}\">  {0:0>width$x}  <span class=\"type\">{1:<12}</span>",
+            arg_index,
+            super::kosvalue_type_str(value),
+            width = width
+        )?;
+        write_value(out, value, encoding)?;
+        writeln!(out, "</div>")?;
+    }
+
+    writeln!(out, "</div></details>")?;
+
+    for (section_index, code_section) in ksmfile.code_sections().enumerate() {
+        let name = section_names.get(section_index).copied().unwrap_or("");
+
+        writeln!(
+            out,
+            "<details id=\"section-{}\" class=\"fn\" data-name=\"{}\" open><summary class=\"label\">{}</summary><div class=\"code\">",
+            section_index,
+            escape(name),
+            escape(name)
+        )?;
+
+        let locations = index.locations(section_index);
+
+        for (instr, location) in code_section.instructions().zip(locations) {
+            write!(out, "<div class=\"ln\">  ")?;
+            write_ksm_instr(out, index, instr, &location.label, encoding)?;
+            writeln!(out, "</div>")?;
+        }
+
+        writeln!(out, "</div></details>")?;
+    }
+
+    writeln!(out, "</main>\n</div>")?;
+
+    write_footer(out)
+}
+
+/// Writes a KO file as a standalone HTML page, with relocated operands linked to the symbols
+/// they refer to, and all other operands linked to the .data entry they refer to
+pub fn write_ko_page(
+    out: &mut impl Write,
+    title: &str,
+    kofile: &KOFile,
+    relocations: &HashMap<(u16, u32), OperandRelocs>,
+    encoding: StringEncoding,
+) -> DumpResult {
+    let section_name = |sh_index: SectionIdx| {
+        kofile
+            .get_section_header(sh_index)
+            .and_then(|header| kofile.get_header_name(header))
+            .map(|s| s.as_str())
+            .unwrap_or("")
+    };
+
+    let symtab_opt = kofile.sym_tab_by_name(".symtab");
+    let symstrtab_opt = kofile.str_tab_by_name(".symstrtab");
+    let data_section_opt = kofile.data_section_by_name(".data");
+
+    let symbol_name = |sym_index: SymbolIdx| {
+        let symbol = symtab_opt?.get(sym_index)?;
+
+        Some((symstrtab_opt?.get(symbol.name_idx)?.as_str(), symbol))
+    };
+
+    write_header(out, title)?;
+
+    writeln!(out, "<h2 id=\"symbols\">Symbol table</h2>")?;
+    writeln!(
+        out,
+        "<table>\n<tr><th>Index</th><th>Name</th><th>Value</th><th>Size</th><th>Binding</th><th>Type</th><th>Section</th></tr>"
+    )?;
+
+    if let Some(symtab) = symtab_opt {
+        for (i, symbol) in symtab.symbols().enumerate() {
+            let name = symstrtab_opt
+                .and_then(|symstrtab| symstrtab.get(symbol.name_idx))
+                .map(|s| s.as_str())
+                .unwrap_or("");
+
+            writeln!(
+                out,
+                "<tr id=\"sym-{0}\"><td>{0}</td><td class=\"sym\">{1}</td><td>{2:0>8x}</td><td>{3}</td><td class=\"type\">{4}</td><td class=\"type\">{5}</td><td>{6}</td></tr>",
+                i,
+                escape(name),
+                u32::from(symbol.value_idx),
+                symbol.size,
+                KOFileDebug::bind_as_str(symbol.sym_bind),
+                KOFileDebug::sym_type_as_str(symbol.sym_type),
+                escape(section_name(symbol.sh_idx))
+            )?;
+        }
+    }
+
+    writeln!(out, "</table>")?;
+
+    writeln!(out, "<h2 id=\"data\">Data section</h2>")?;
+    writeln!(
+        out,
+        "<table>\n<tr><th>Index</th><th>Type</th><th>Value</th></tr>"
+    )?;
+
+    if let Some(data_section) = data_section_opt {
+        for (i, value) in data_section.data().enumerate() {
+            write!(
+                out,
+                "<tr id=\"data-{0}\"><td>{0:0>8x}</td><td class=\"type\">{1}</td><td>",
+                i,
+                super::kosvalue_type_str(value)
+            )?;
+            write_value(out, value, encoding)?;
+            writeln!(out, "</td></tr>")?;
+        }
+    }
+
+    writeln!(out, "</table>")?;
+
+    writeln!(out, "<h2 id=\"code\">Function sections</h2>")?;
+
+    for func_section in kofile.func_sections() {
+        let sh_index = func_section.section_index();
+
+        writeln!(
+            out,
+            "<h3 id=\"section-{}\">{}</h3>\n<pre>",
+            u16::from(sh_index),
+            escape(section_name(sh_index))
+        )?;
+
+        for (i, instr) in func_section.instructions().enumerate() {
+            let opcode = instr.opcode();
+            let mnemonic: &str = opcode.into();
+
+            write!(
+                out,
+                "  <span class=\"label\">{:0>8x}</span> <span class=\"{}\">{:<5}</span>",
+                i + 1,
+                mnemonic_class(opcode),
+                mnemonic
+            )?;
+
+            let ((op1_relocated, op1_symbol), (op2_relocated, op2_symbol)) = relocations
+                .get(&(u16::from(sh_index), i as u32))
+                .copied()
+                .unwrap_or((
+                    (false, SymbolIdx::from(0u32)),
+                    (false, SymbolIdx::from(0u32)),
+                ));
+
+            let operands = ko_instr_operands(instr)
+                .into_iter()
+                .zip([(op1_relocated, op1_symbol), (op2_relocated, op2_symbol)]);
+
+            for (n, (operand, (relocated, symbol_index))) in operands.enumerate() {
+                write!(out, "{}", if n == 0 { " " } else { ", " })?;
+
+                if relocated {
+                    match symbol_name(symbol_index) {
+                        Some((name, symbol)) => {
+                            let class = if symbol.sym_type == SymType::NoType {
+                                "var"
+                            } else {
+                                "sym"
+                            };
+
+                            write!(
+                                out,
+                                "<a class=\"{}\" href=\"#sym-{}\">&lt;{}&gt;</a>",
+                                class,
+                                u32::from(symbol_index),
+                                escape(name)
+                            )?;
+                        }
+                        None => write!(out, "&lt;invalid symbol {}&gt;", u32::from(symbol_index))?,
+                    }
+                } else {
+                    match data_section_opt.and_then(|data_section| data_section.get(operand)) {
+                        Some(value) => {
+                            write!(out, "<a href=\"#data-{}\">", u32::from(operand))?;
+                            write_value(out, value, encoding)?;
+                            write!(out, "</a>")?;
+                        }
+                        None => write!(out, "&lt;invalid {:x}&gt;", u32::from(operand))?,
+                    }
+                }
+            }
+
+            writeln!(out)?;
+        }
+
+        writeln!(out, "</pre>")?;
+    }
+
+    write_footer(out)
+}
@@ -0,0 +1,249 @@
+use kerbalobjects::{KOSValue, Opcode};
+use std::collections::{BTreeSet, HashMap};
+use std::io::Write;
+
+use crate::StringEncoding;
+
+use super::DumpResult;
+
+/// A value from a KSM file's argument section or a KO file's data section, which is declared
+/// once and referred to by name, so that its exact type survives being reassembled
+#[derive(Debug, Clone)]
+pub struct KasmValue {
+    pub name: String,
+    pub value: KOSValue,
+}
+
+/// What an operand of an instruction is written as
+#[derive(Debug, Clone)]
+pub enum KasmOperand {
+    /// A declared value, by its name
+    Value(String),
+    /// A function or other symbol, which the linker fills in
+    Symbol(String),
+    /// A branch destination, by the position of the instruction it goes to in its function
+    Target(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct KasmInstr {
+    pub opcode: Opcode,
+    pub operands: Vec<KasmOperand>,
+}
+
+#[derive(Debug, Clone)]
+pub struct KasmFunction {
+    pub name: String,
+    pub global: bool,
+    pub instrs: Vec<KasmInstr>,
+}
+
+/// Everything that goes into a KASM listing
+#[derive(Debug, Clone, Default)]
+pub struct KasmProgram {
+    /// Symbols that are used, but defined in some other file
+    pub externs: Vec<String>,
+    pub values: Vec<KasmValue>,
+    pub functions: Vec<KasmFunction>,
+}
+
+/// Turns a name from a file, like mylib`do_burn or trigger@line_42, into one that KASM accepts as
+/// a label
+pub fn kasm_identifier(name: &str) -> String {
+    let mut identifier: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    if !identifier.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        identifier.insert(0, '_');
+    }
+
+    identifier
+}
+
+/// Gives each name a suffix if it is already taken, so that every label is unique
+pub fn unique_name(name: String, taken: &mut BTreeSet<String>) -> String {
+    let mut unique = name.clone();
+    let mut n = 2;
+
+    while taken.contains(&unique) {
+        unique = format!("{}_{}", name, n);
+        n += 1;
+    }
+
+    taken.insert(unique.clone());
+
+    unique
+}
+
+fn escape_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+
+    escaped.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped.push('"');
+
+    escaped
+}
+
+/// The directive that declares a value with its exact type, and the value as it is written
+///
+/// Floats are written with Rust's shortest representation that reads back as the same number.
+fn value_directive(value: &KOSValue) -> (&'static str, String) {
+    match value {
+        KOSValue::Null => (".null", String::new()),
+        KOSValue::ArgMarker => (".argmarker", String::new()),
+        KOSValue::Bool(b) => (".b", b.to_string()),
+        KOSValue::BoolValue(b) => (".bv", b.to_string()),
+        KOSValue::Byte(b) => (".i8", b.to_string()),
+        KOSValue::Int16(i) => (".i16", i.to_string()),
+        KOSValue::Int32(i) => (".i32", i.to_string()),
+        KOSValue::ScalarInt(i) => (".i32v", i.to_string()),
+        KOSValue::Float(f) => (".f32", format!("{:?}", f)),
+        KOSValue::Double(d) => (".f64", format!("{:?}", d)),
+        KOSValue::ScalarDouble(d) => (".f64v", format!("{:?}", d)),
+        KOSValue::String(s) => (".s", escape_string(s)),
+        KOSValue::StringValue(s) => (".sv", escape_string(s)),
+    }
+}
+
+/// Writes a program as KASM source that the KASM assembler can turn back into an equivalent
+/// object file, which KLinker can then link into an equivalent KSM file
+///
+/// Every value is declared in the data section with its exact type and given a name, and
+/// instructions refer to values by those names. Branches get local labels instead of the
+/// distances they were stored as, so that the listing can be edited and still assemble.
+pub fn write_kasm(
+    out: &mut dyn Write,
+    title: &str,
+    program: &KasmProgram,
+    encoding: StringEncoding,
+) -> DumpResult {
+    writeln!(out, "; KASM source for {}, written by kDump", title)?;
+    writeln!(
+        out,
+        "; Label resets are left out, since the linker writes them again"
+    )?;
+
+    let values: HashMap<&str, &KOSValue> = program
+        .values
+        .iter()
+        .map(|value| (value.name.as_str(), &value.value))
+        .collect();
+
+    if !program.externs.is_empty() {
+        writeln!(out)?;
+
+        for name in &program.externs {
+            writeln!(out, ".extern {}", name)?;
+        }
+    }
+
+    writeln!(out, "\n.section .data")?;
+
+    let name_width = program
+        .values
+        .iter()
+        .map(|value| value.name.len())
+        .max()
+        .unwrap_or(0);
+
+    for KasmValue { name, value } in &program.values {
+        let (directive, literal) = value_directive(value);
+
+        writeln!(
+            out,
+            "    {:<width$} {:<10} {}",
+            name,
+            directive,
+            literal,
+            width = name_width
+        )?;
+    }
+
+    writeln!(out, "\n.section .text")?;
+
+    for function in &program.functions {
+        write_function(out, function, &values, encoding)?;
+    }
+
+    Ok(())
+}
+
+fn write_function(
+    out: &mut dyn Write,
+    function: &KasmFunction,
+    values: &HashMap<&str, &KOSValue>,
+    encoding: StringEncoding,
+) -> DumpResult {
+    writeln!(out)?;
+
+    if function.global {
+        writeln!(out, ".global {}", function.name)?;
+    }
+
+    writeln!(out, ".func")?;
+    writeln!(out, "{}:", function.name)?;
+
+    // Only the instructions that something branches to get a label
+    let targets: BTreeSet<usize> = function
+        .instrs
+        .iter()
+        .flat_map(|instr| &instr.operands)
+        .filter_map(|operand| match operand {
+            KasmOperand::Target(target) => Some(*target),
+            _ => None,
+        })
+        .collect();
+
+    for (i, instr) in function.instrs.iter().enumerate() {
+        if targets.contains(&i) {
+            writeln!(out, ".L{}:", i)?;
+        }
+
+        if instr.opcode == Opcode::Bogus {
+            writeln!(out, "    ; unknown opcode, which can't be reassembled")?;
+            continue;
+        }
+
+        let mnemonic: &str = instr.opcode.into();
+        let mut comments = Vec::new();
+
+        let operands: Vec<String> = instr
+            .operands
+            .iter()
+            .map(|operand| match operand {
+                KasmOperand::Value(name) => {
+                    if let Some(value) = values.get(name.as_str()) {
+                        comments.push(super::kosvalue_quoted(value, encoding));
+                    }
+
+                    name.clone()
+                }
+                KasmOperand::Symbol(name) => name.clone(),
+                KasmOperand::Target(target) => format!(".L{}", target),
+            })
+            .collect();
+
+        let line = format!("    {:<6} {}", mnemonic, operands.join(", "));
+
+        if comments.is_empty() {
+            writeln!(out, "{}", line.trim_end())?;
+        } else {
+            writeln!(out, "{:<40} ; {}", line, comments.join(", "))?;
+        }
+    }
+
+    Ok(())
+}
@@ -2,86 +2,302 @@ use kerbalobjects::ko::sections::{
     DataIdx, DataSection, FuncSection, InstrIdx, SectionKind, StringIdx, StringTable, SymbolIdx,
     SymbolTable,
 };
-use kerbalobjects::ko::symbols::OperandIndex;
+use kerbalobjects::ko::symbols::{KOSymbol, OperandIndex, SymBind, SymType};
 use kerbalobjects::ko::{KOFile, SectionIdx};
 use kerbalobjects::KOSValue;
+use kerbalobjects::Opcode;
+use std::collections::{BTreeSet, HashMap};
 use std::error::Error;
-use std::io::Write;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
 use termcolor::ColorSpec;
-use termcolor::StandardStream;
 use termcolor::WriteColor;
 
+use crate::analysis::{self, CallGraph, FlowInstr};
+use crate::cancel::Deadline;
+use crate::fio::layout;
+use crate::index::ko_instr_operands;
+use crate::model;
 use crate::output::DynResult;
 use crate::CLIConfig;
+use crate::ColorRole;
+use crate::StringEncoding;
 use crate::DARK_RED_COLOR;
 use crate::GREEN_COLOR;
 use crate::LIGHT_RED_COLOR;
 use crate::PURPLE_COLOR;
 
+use super::kasm::{self, KasmFunction, KasmInstr, KasmOperand, KasmProgram, KasmValue};
+use super::style::{
+    self, DisasmLine, DisasmSection, DisasmStyle, KoHeaderInfo, SectionRow, SymbolRow,
+};
 use super::DumpResult;
 
-pub struct KOFileDebug {
-    kofile: KOFile,
+/// The size of each entry in a symbol table: name index, value index, size, binding, type, and section index
+const KO_SYMBOL_SIZE: usize = 14;
+/// The size of each relocation entry: section index, instruction index, operand index, and symbol index
+const KO_RELD_ENTRY_SIZE: usize = 11;
+/// The size of each instruction operand, which is an index into the data section
+const KO_OPERAND_SIZE: usize = 4;
+
+/// The relocation state of both operands of an instruction
+pub(super) type OperandRelocs = ((bool, SymbolIdx), (bool, SymbolIdx));
+
+pub struct KOFileDebug<'a> {
+    kofile: &'a KOFile,
+    /// Every relocation entry, keyed by section index and instruction index
+    relocations: HashMap<(u16, u32), OperandRelocs>,
+    /// The bytes the file was parsed from, which is where the bytes of unknown opcodes come from
+    raw_contents: Option<&'a [u8]>,
+    /// How strings are decoded when they are shown
+    encoding: StringEncoding,
+    /// When the analyses that go section by section have to stop by
+    deadline: Deadline,
 }
 
-impl KOFileDebug {
-    pub fn new(kofile: KOFile) -> Self {
-        KOFileDebug { kofile }
+impl<'a> KOFileDebug<'a> {
+    pub fn new(kofile: &'a KOFile) -> Self {
+        let relocations = KOFileDebug::index_relocations(kofile);
+
+        KOFileDebug {
+            kofile,
+            relocations,
+            raw_contents: None,
+            encoding: StringEncoding::default(),
+            deadline: Deadline::default(),
+        }
+    }
+
+    /// Gives the bytes the file was parsed from, so that unknown opcodes can be shown as the byte they were
+    pub fn with_raw_contents(mut self, raw_contents: &'a [u8]) -> Self {
+        self.raw_contents = Some(raw_contents);
+        self
+    }
+
+    /// Sets how strings are decoded when they are shown
+    pub fn with_encoding(mut self, encoding: StringEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Sets when disassembly, decompiling, and control flow graphs stop and keep what they have
+    /// written, for --timeout and Ctrl-C
+    pub fn with_deadline(mut self, deadline: Deadline) -> Self {
+        self.deadline = deadline;
+        self
     }
 
-    pub fn dump(&self, stream: &mut StandardStream, config: &CLIConfig) -> DumpResult {
+    /// Builds a lookup table of every relocated operand, so that disassembly doesn't have to
+    /// search every relocation entry for every instruction
+    fn index_relocations(kofile: &KOFile) -> HashMap<(u16, u32), OperandRelocs> {
+        let mut relocations = HashMap::new();
+
+        // Any section of the relocation data kind counts, no matter what it is named
+        for reld_section in kofile.reld_sections() {
+            for reld_entry in reld_section.entries() {
+                let key = (
+                    u16::from(reld_entry.section_index),
+                    u32::from(reld_entry.instr_index),
+                );
+
+                let relocs: &mut OperandRelocs = relocations.entry(key).or_insert((
+                    (false, SymbolIdx::from(0u32)),
+                    (false, SymbolIdx::from(0u32)),
+                ));
+
+                match reld_entry.operand_index {
+                    OperandIndex::One => {
+                        relocs.0 = (true, reld_entry.symbol_index);
+                    }
+                    OperandIndex::Two => {
+                        relocs.1 = (true, reld_entry.symbol_index);
+                    }
+                }
+            }
+        }
+
+        relocations
+    }
+
+    pub fn dump(&self, stream: &mut dyn WriteColor, config: &CLIConfig) -> DumpResult {
         let no_color = ColorSpec::new();
-        let mut purple = ColorSpec::new();
-        purple.set_fg(Some(PURPLE_COLOR));
-        let mut light_red = ColorSpec::new();
-        light_red.set_fg(Some(LIGHT_RED_COLOR));
-        let mut green = ColorSpec::new();
-        green.set_fg(Some(GREEN_COLOR));
-        let mut dark_red = ColorSpec::new();
-        dark_red.set_fg(Some(DARK_RED_COLOR));
+        let label_color = super::role_color(config, ColorRole::Labels, GREEN_COLOR);
+        let section_label_color = super::role_color(config, ColorRole::Labels, PURPLE_COLOR);
+        let mnemonic_color = super::role_color(config, ColorRole::Mnemonics, DARK_RED_COLOR);
+        let variable_color = super::role_color(config, ColorRole::Variables, LIGHT_RED_COLOR);
+        let name_color = super::role_color(config, ColorRole::Names, LIGHT_RED_COLOR);
+        let type_color = super::role_color(config, ColorRole::Types, GREEN_COLOR);
+        let number_color = super::role_color(config, ColorRole::Numbers, PURPLE_COLOR);
+
+        if let Some(html_path) = &config.html {
+            self.write_html(config, html_path)?;
+        }
+
+        if let Some(parquet_path) = &config.parquet {
+            super::write_parquet(parquet_path, config, |stream, style| {
+                self.dump_styled(stream, config, style)
+            })?;
+        }
+
+        if let Some(kasm_path) = &config.emit_kasm {
+            self.write_kasm(config, kasm_path)?;
+        }
+
+        if let Some(binary_path) = &config.binary_dump {
+            let dump = model::BinaryDump::collect(|emit| {
+                model::visit_ko(self.kofile, self.encoding, emit)
+            })?;
+
+            super::write_binary_dump(binary_path, &dump)?;
+        }
+
+        // Problems found in the file go to stderr when the output is meant for another program, so
+        // that they can never be mistaken for part of it
+        if config.machine_readable() {
+            super::write_diagnostics(&model::diagnose_ko(self.kofile))?;
+        }
+
+        if config.csv {
+            return self.dump_csv(stream);
+        }
+
+        if config.ndjson {
+            return model::visit_ko(self.kofile, self.encoding, &mut |record| {
+                super::write_ndjson_line(stream, &record)
+            });
+        }
+
+        if let Some(template_path) = &config.template {
+            let mut context =
+                model::collect_records(|emit| model::visit_ko(self.kofile, self.encoding, emit))?;
+            context["diagnostics"] = serde_json::to_value(model::diagnose_ko(self.kofile))?;
+
+            return super::template::dump_template(stream, template_path, &context);
+        }
+
+        if config.cfg_dot {
+            return self.dump_cfg_dot(stream);
+        }
+
+        if config.callgraph_dot {
+            return self.dump_callgraph_dot(stream, config);
+        }
+
+        if config.json {
+            return self.dump_data_json(stream, config);
+        }
 
         if config.info {
             self.dump_info(stream)?;
         }
 
+        let table_style = style::table_style(config.style);
+
         if config.file_headers || config.all_headers {
-            self.dump_ko_header(stream)?;
+            match &table_style {
+                Some(table_style) => table_style.write_ko_header(stream, &self.header_info())?,
+                None => self.dump_ko_header(stream)?,
+            }
         }
 
         if config.section_headers || config.all_headers {
-            self.dump_section_headers(stream, &no_color, &light_red, &green, &purple)?;
+            match &table_style {
+                Some(table_style) => {
+                    table_style.write_section_headers(stream, &self.section_rows()?)?
+                }
+                None => self.dump_section_headers(
+                    stream,
+                    config,
+                    &no_color,
+                    &name_color,
+                    &type_color,
+                    &number_color,
+                )?,
+            }
         }
 
         if config.stabs || config.full_contents {
-            self.dump_strtabs(stream, &no_color, &purple, &light_red)?;
+            self.dump_strtabs(stream, &no_color, &number_color, &name_color)?;
         }
 
         if config.data || config.full_contents {
-            self.dump_data(stream, &no_color, &green, &light_red)?;
+            self.dump_data(stream, &no_color, &type_color, &variable_color)?;
+        } else if config.argument_section {
+            // KO files keep their operands in data sections instead, so -a works on both kinds of file
+            let data_names = self
+                .kofile
+                .data_sections()
+                .map(|data_section| self.get_section_name(data_section.section_index()))
+                .collect::<DynResult<Vec<&str>>>()?;
+
+            writeln!(
+                stream,
+                "\nNote: KO files have no argument section, showing {} instead",
+                if data_names.is_empty() {
+                    String::from("their data sections")
+                } else {
+                    data_names.join(", ")
+                }
+            )?;
+
+            self.dump_data(stream, &no_color, &type_color, &variable_color)?;
         }
 
         if config.syms || config.full_contents {
-            self.dump_symbols(
-                stream, &no_color, &light_red, &purple, &purple, &green, &green, &no_color,
-            )?;
+            match &table_style {
+                Some(table_style) => {
+                    for symbol_table in self.kofile.sym_tabs() {
+                        let name = self.get_section_name(symbol_table.section_index())?;
+
+                        table_style.write_symbols(stream, name, &self.symbol_rows(symbol_table))?;
+                    }
+                }
+                None => self.dump_symbols(
+                    stream,
+                    &no_color,
+                    &name_color,
+                    &number_color,
+                    &number_color,
+                    &type_color,
+                    &type_color,
+                    &no_color,
+                )?,
+            }
+        }
+
+        if config.provenance_symbols {
+            self.dump_provenance(stream, &no_color, &name_color, &type_color)?;
         }
 
         if config.reloc || config.full_contents {
-            self.dump_relocs(stream, &no_color, &purple)?;
+            self.dump_relocs(stream, &no_color, &number_color)?;
+        }
+
+        if config.verify {
+            self.dump_verification(stream)?;
+        }
+
+        if config.spec_report {
+            self.dump_spec_report(stream)?;
         }
 
         if config.disassemble || config.full_contents {
-            self.dump_func_sections(
-                stream,
-                &no_color,
-                &purple,
-                &dark_red,
-                &light_red,
-                &green,
-                &purple,
-                !config.show_no_labels,
-                !config.show_no_raw_instr,
-            )?;
+            match style::disasm_style(config.style) {
+                Some(style) => self.dump_styled(stream, config, style.as_ref())?,
+                None => self.dump_func_sections(
+                    stream,
+                    &no_color,
+                    &number_color,
+                    &mnemonic_color,
+                    &variable_color,
+                    &label_color,
+                    &section_label_color,
+                    !config.show_no_labels,
+                    !config.show_no_raw_instr,
+                    config.annotate_data_symbols,
+                )?,
+            }
         }
 
         if let Some(disassemble_symbol) = &config.disassemble_symbol {
@@ -89,19 +305,474 @@ impl KOFileDebug {
                 stream,
                 disassemble_symbol,
                 &no_color,
-                &purple,
-                &dark_red,
-                &light_red,
-                &green,
-                &purple,
+                &number_color,
+                &mnemonic_color,
+                &variable_color,
+                &label_color,
+                &section_label_color,
                 !config.show_no_labels,
                 !config.show_no_raw_instr,
+                config.annotate_data_symbols,
+            )?;
+        }
+
+        if config.symbol_index {
+            self.dump_symbol_index(stream, &no_color, &section_label_color)?;
+        }
+
+        if config.opcode_costs {
+            let instrs = self
+                .kofile
+                .func_sections()
+                .flat_map(|func_section| func_section.instructions())
+                .map(|instr| match instr {
+                    kerbalobjects::ko::Instr::ZeroOp(opcode) => (*opcode, 0),
+                    kerbalobjects::ko::Instr::OneOp(opcode, _) => (*opcode, 1),
+                    kerbalobjects::ko::Instr::TwoOp(opcode, _, _) => (*opcode, 2),
+                });
+            let costs = super::costs::opcode_costs(instrs, KO_OPERAND_SIZE);
+
+            super::costs::dump_opcode_costs(stream, &costs, config.human_readable)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes every function section using one of the --style formatters
+    ///
+    /// Addresses are byte offsets from the start of each section, since KO files are not yet laid out.
+    fn dump_styled(
+        &self,
+        stream: &mut dyn WriteColor,
+        config: &CLIConfig,
+        style: &dyn DisasmStyle,
+    ) -> DumpResult {
+        style.write_file_header(stream, &super::report_title(config), "ko")?;
+
+        let data_section = self.kofile.data_section_by_name(".data");
+        let symtab_opt = self.kofile.sym_tab_by_name(".symtab");
+        let symstrtab_opt = self.kofile.str_tab_by_name(".symstrtab");
+
+        let symbol_name = |symbol_index: SymbolIdx| {
+            let symbol = symtab_opt?.get(symbol_index)?;
+
+            symstrtab_opt?.get(symbol.name_idx)
+        };
+
+        for func_section in self.kofile.func_sections() {
+            let sh_index = func_section.section_index();
+            let name = self.get_section_name(sh_index)?;
+
+            let mut lines = Vec::new();
+            let mut addr = 0;
+
+            for (i, instr) in func_section.instructions().enumerate() {
+                let opcode = instr.opcode();
+                let relocs = self.get_relocated(sh_index, InstrIdx::from(i));
+
+                let operands: Vec<_> = ko_instr_operands(instr)
+                    .into_iter()
+                    .zip([relocs.0, relocs.1])
+                    .collect();
+
+                let mut bytes = vec![u8::from(opcode)];
+                let mut operand_strs = Vec::new();
+                let mut first_value = None;
+
+                for (n, (op, (relocated, symbol_index))) in operands.iter().enumerate() {
+                    bytes.extend(u32::from(*op).to_le_bytes());
+
+                    if *relocated {
+                        operand_strs.push(match symbol_name(*symbol_index) {
+                            Some(name) => format!("<{}>", name),
+                            None => format!("<{}>", u32::from(*symbol_index)),
+                        });
+                    } else {
+                        let value = data_section.and_then(|data_section| data_section.get(*op));
+
+                        if n == 0 {
+                            first_value = value;
+                        }
+
+                        operand_strs.push(match value {
+                            Some(value) => super::kosvalue_quoted(value, self.encoding),
+                            None => format!("<invalid {:x}>", u32::from(*op)),
+                        });
+                    }
+                }
+
+                let distance = FlowInstr::new(opcode, first_value).distance;
+                let size = bytes.len();
+
+                lines.push((
+                    DisasmLine {
+                        addr,
+                        bytes,
+                        mnemonic: opcode.into(),
+                        operand_indices: operands
+                            .iter()
+                            .map(|(op, _)| u32::from(*op) as usize)
+                            .collect(),
+                        operands: operand_strs,
+                        target: None,
+                    },
+                    distance,
+                ));
+
+                addr += size;
+            }
+
+            // Branch destinations can only be named once every instruction's address is known
+            let addrs: Vec<usize> = lines.iter().map(|(line, _)| line.addr).collect();
+
+            let lines = lines
+                .into_iter()
+                .enumerate()
+                .map(|(i, (mut line, distance))| {
+                    line.target = distance
+                        .and_then(|distance| i.checked_add_signed(distance))
+                        .and_then(|target| addrs.get(target))
+                        .map(|target_addr| format!("{}+0x{:x}", name, target_addr));
+
+                    line
+                })
+                .collect();
+
+            style.write_section(
+                stream,
+                &DisasmSection {
+                    name: name.to_string(),
+                    lines,
+                },
             )?;
         }
 
         Ok(())
     }
 
+    /// Writes which function sections call, or take delegates of, which others as a DOT digraph
+    ///
+    /// Each section is named by the FUNC symbol that marks its entry, if it has one. Targets are
+    /// found through relocated symbols, or by the name in a string operand. Symbols that aren't
+    /// defined in the file, and names that don't match a function, become nodes of their own.
+    fn dump_callgraph_dot(&self, stream: &mut dyn WriteColor, config: &CLIConfig) -> DumpResult {
+        let data_section = self.kofile.data_section_by_name(".data");
+        let symtab_opt = self.kofile.sym_tab_by_name(".symtab");
+        let symstrtab_opt = self.kofile.str_tab_by_name(".symstrtab");
+
+        let symbol_name = |symbol: &KOSymbol| {
+            symstrtab_opt
+                .and_then(|symstrtab| symstrtab.get(symbol.name_idx))
+                .map(|name| name.as_str())
+        };
+
+        let func_symbols: Vec<&KOSymbol> = symtab_opt
+            .map(|symtab| {
+                symtab
+                    .symbols()
+                    .filter(|symbol| {
+                        symbol.sym_type == SymType::Func && symbol.sym_bind != SymBind::Extern
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut graph = CallGraph::default();
+
+        for func_section in self.kofile.func_sections() {
+            let sh_index = func_section.section_index();
+
+            let caller = match func_symbols
+                .iter()
+                .find(|symbol| symbol.sh_idx == sh_index)
+                .and_then(|symbol| symbol_name(symbol))
+            {
+                Some(name) => name,
+                None => self.get_section_name(sh_index)?,
+            };
+
+            graph.node(caller, false);
+
+            for (i, instr) in func_section.instructions().enumerate() {
+                let op = match instr {
+                    kerbalobjects::ko::Instr::OneOp(opcode, op)
+                    | kerbalobjects::ko::Instr::TwoOp(opcode, op, _)
+                        if analysis::is_call_like(*opcode) =>
+                    {
+                        *op
+                    }
+                    _ => continue,
+                };
+
+                let (relocated, symbol_index) = self.get_relocated(sh_index, InstrIdx::from(i)).0;
+
+                if relocated {
+                    let symbol = match symtab_opt.and_then(|symtab| symtab.get(symbol_index)) {
+                        Some(symbol) => symbol,
+                        None => continue,
+                    };
+
+                    if let Some(name) = symbol_name(symbol) {
+                        graph.add_call(caller, name, symbol.sym_bind == SymBind::Extern);
+                    }
+                } else if let Some(KOSValue::String(s) | KOSValue::StringValue(s)) =
+                    data_section.and_then(|data_section| data_section.get(op))
+                {
+                    if s.is_empty() {
+                        continue;
+                    }
+
+                    let defined = func_symbols
+                        .iter()
+                        .any(|symbol| symbol_name(symbol) == Some(s.as_str()));
+
+                    graph.add_call(caller, s, !defined);
+                }
+            }
+        }
+
+        super::dot::write_callgraph(stream, &super::report_title(config), &graph)
+    }
+
+    /// Writes the control flow graph of every function section as DOT
+    ///
+    /// Operands that are relocated to a symbol are shown by name, and never resolve as a branch destination.
+    fn dump_cfg_dot(&self, stream: &mut dyn WriteColor) -> DumpResult {
+        let data_section = self.kofile.data_section_by_name(".data");
+        let symtab_opt = self.kofile.sym_tab_by_name(".symtab");
+        let symstrtab_opt = self.kofile.str_tab_by_name(".symstrtab");
+
+        let symbol_name = |symbol_index: SymbolIdx| {
+            let symbol = symtab_opt?.get(symbol_index)?;
+
+            symstrtab_opt?.get(symbol.name_idx)
+        };
+
+        let total = self.kofile.func_sections().count();
+
+        for (done, func_section) in self.kofile.func_sections().enumerate() {
+            if let Some(reason) = self.deadline.check() {
+                return super::write_stop_note(stream, "// ", reason, done, total, false);
+            }
+
+            let sh_index = func_section.section_index();
+            let name = self.get_section_name(sh_index)?;
+
+            let mut flow_instrs = Vec::new();
+            let mut lines = Vec::new();
+
+            for (i, instr) in func_section.instructions().enumerate() {
+                if let Some(reason) = self.deadline.check_at(i) {
+                    return super::write_stop_note(stream, "// ", reason, done, total, false);
+                }
+
+                let opcode = instr.opcode();
+                let mnemonic: &str = opcode.into();
+                let relocs = self.get_relocated(sh_index, InstrIdx::from(i));
+
+                let operands: Vec<_> = ko_instr_operands(instr)
+                    .into_iter()
+                    .zip([relocs.0, relocs.1])
+                    .collect();
+
+                let values: Vec<Option<&KOSValue>> = operands
+                    .iter()
+                    .map(|(op, (relocated, _))| {
+                        if *relocated {
+                            None
+                        } else {
+                            data_section.and_then(|data_section| data_section.get(*op))
+                        }
+                    })
+                    .collect();
+
+                let operand_strs: Vec<String> = operands
+                    .iter()
+                    .zip(&values)
+                    .map(|((_, (relocated, symbol_index)), value)| match value {
+                        Some(value) => super::kosvalue_str(value, self.encoding),
+                        None if *relocated => match symbol_name(*symbol_index) {
+                            Some(name) => format!("<{}>", name),
+                            None => format!("<{}>", u32::from(*symbol_index)),
+                        },
+                        None => String::from("?"),
+                    })
+                    .collect();
+
+                flow_instrs.push(FlowInstr::new(opcode, values.first().copied().flatten()));
+                lines.push(format!(
+                    "{:0>8x} {:<5} {}",
+                    i + 1,
+                    mnemonic,
+                    operand_strs.join(", ")
+                ));
+            }
+
+            let blocks = analysis::build_cfg(&flow_instrs);
+
+            super::dot::write_cfg(stream, name, &blocks, &lines)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the whole file as an HTML report
+    fn write_html(&self, config: &CLIConfig, html_path: &Path) -> DumpResult {
+        let mut out = BufWriter::new(File::create(html_path)?);
+
+        super::html::write_ko_page(
+            &mut out,
+            &super::report_title(config),
+            self.kofile,
+            &self.relocations,
+            self.encoding,
+        )?;
+
+        out.flush()?;
+
+        Ok(())
+    }
+
+    /// Writes the file as KASM source that can be assembled back into it
+    fn write_kasm(&self, config: &CLIConfig, kasm_path: &Path) -> DumpResult {
+        let program = self.kasm_program()?;
+
+        let mut out = BufWriter::new(File::create(kasm_path)?);
+
+        super::kasm::write_kasm(
+            &mut out,
+            &super::report_title(config),
+            &program,
+            self.encoding,
+        )?;
+
+        out.flush()?;
+
+        Ok(())
+    }
+
+    /// Gathers what a KASM listing needs, which is every value in .data, named after the data
+    /// symbol that refers to it if there is one, and every function section
+    ///
+    /// Relocated operands are written as the symbol they refer to, and symbols that are only
+    /// declared in this file become .extern directives.
+    fn kasm_program(&self) -> DynResult<KasmProgram> {
+        let symtab_opt = self.kofile.sym_tab_by_name(".symtab");
+        let symstrtab_opt = self.kofile.str_tab_by_name(".symstrtab");
+        let data_section = self.kofile.data_section_by_name(".data");
+
+        let symbol_name = |symbol: &KOSymbol| {
+            symstrtab_opt
+                .and_then(|symstrtab| symstrtab.get(symbol.name_idx))
+                .map(|name| name.as_str())
+        };
+
+        let mut taken = BTreeSet::new();
+        let mut program = KasmProgram::default();
+
+        if let Some(symtab) = symtab_opt {
+            for symbol in symtab
+                .symbols()
+                .filter(|symbol| symbol.sym_bind == SymBind::Extern)
+            {
+                if let Some(name) = symbol_name(symbol) {
+                    program.externs.push(name.to_string());
+                    taken.insert(name.to_string());
+                }
+            }
+        }
+
+        let mut value_names = Vec::new();
+
+        if let Some(data_section) = data_section {
+            for (i, value) in data_section.data().enumerate() {
+                let op = DataIdx::from(i as u32);
+                let name = match self.data_symbol_name(op, data_section, symtab_opt, symstrtab_opt)
+                {
+                    Some(name) => kasm::kasm_identifier(name),
+                    None => format!("d_{}", i),
+                };
+                let name = kasm::unique_name(name, &mut taken);
+
+                value_names.push(name.clone());
+                program.values.push(KasmValue {
+                    name,
+                    value: value.clone(),
+                });
+            }
+        }
+
+        for func_section in self.kofile.func_sections() {
+            let sh_index = func_section.section_index();
+            let section_name = self.get_section_name(sh_index)?;
+
+            let entry_symbol = symtab_opt.and_then(|symtab| {
+                symtab
+                    .symbols()
+                    .find(|symbol| symbol.sym_type == SymType::Func && symbol.sh_idx == sh_index)
+            });
+
+            let name = entry_symbol.and_then(&symbol_name).unwrap_or(section_name);
+
+            let instructions: Vec<&kerbalobjects::ko::Instr> =
+                func_section.instructions().collect();
+            let mut instrs = Vec::new();
+
+            for (i, instr) in instructions.iter().enumerate() {
+                let ops = ko_instr_operands(instr);
+                let relocs = self.get_relocated(sh_index, InstrIdx::from(i));
+                let relocs = [relocs.0, relocs.1];
+
+                let operands = ops
+                    .iter()
+                    .zip(relocs)
+                    .enumerate()
+                    .map(|(n, (op, (relocated, symbol_index)))| {
+                        if relocated {
+                            if let Some(name) = symtab_opt
+                                .and_then(|symtab| symtab.get(symbol_index))
+                                .and_then(&symbol_name)
+                            {
+                                return KasmOperand::Symbol(name.to_string());
+                            }
+                        }
+
+                        let value = data_section.and_then(|data_section| data_section.get(*op));
+
+                        let target = value
+                            .filter(|_| n == 0 && analysis::is_branch(instr.opcode()))
+                            .and_then(analysis::branch_distance)
+                            .and_then(|distance| i.checked_add_signed(distance))
+                            .filter(|target| *target < instructions.len());
+
+                        match target {
+                            Some(target) => KasmOperand::Target(target),
+                            None => KasmOperand::Value(
+                                value_names
+                                    .get(u32::from(*op) as usize)
+                                    .cloned()
+                                    .unwrap_or_else(|| format!("d_{}", u32::from(*op))),
+                            ),
+                        }
+                    })
+                    .collect();
+
+                instrs.push(KasmInstr {
+                    opcode: instr.opcode(),
+                    operands,
+                });
+            }
+
+            program.functions.push(KasmFunction {
+                name: name.to_string(),
+                global: entry_symbol.is_some_and(|symbol| symbol.sym_bind == SymBind::Global),
+                instrs,
+            });
+        }
+
+        Ok(program)
+    }
+
     fn get_section_name(&self, sh_index: SectionIdx) -> Result<&str, Box<dyn Error>> {
         let header = self.kofile.get_section_header(sh_index).ok_or(format!(
             "Failed to find KO file section header for string table with index {}",
@@ -118,7 +789,7 @@ impl KOFileDebug {
 
     fn dump_relocs(
         &self,
-        stream: &mut StandardStream,
+        stream: &mut dyn WriteColor,
         regular_color: &ColorSpec,
         index_color: &ColorSpec,
     ) -> DumpResult {
@@ -161,7 +832,7 @@ impl KOFileDebug {
     #[allow(clippy::too_many_arguments)]
     fn dump_func_by_symbol(
         &self,
-        stream: &mut StandardStream,
+        stream: &mut dyn WriteColor,
         symbol_text: &String,
         regular_color: &ColorSpec,
         index_color: &ColorSpec,
@@ -171,6 +842,7 @@ impl KOFileDebug {
         section_color: &ColorSpec,
         show_labels: bool,
         show_raw_instr: bool,
+        annotate_data_symbols: bool,
     ) -> DumpResult {
         let mut func_section_found = None;
 
@@ -195,46 +867,25 @@ impl KOFileDebug {
             for (i, instr) in func_section.instructions().enumerate() {
                 let relocs = self.get_relocated(sh_index, InstrIdx::from(i));
 
-                match instr {
-                    kerbalobjects::ko::Instr::ZeroOp(_) => {}
-                    kerbalobjects::ko::Instr::OneOp(_, op1) => {
-                        if self.operand_matches_str(
-                            *op1,
-                            relocs.0,
-                            data_section,
-                            symtab_opt,
-                            symstrtab_opt,
-                            symbol_text,
-                        )? {
-                            func_section_found = Some(func_section);
-                            break;
-                        }
+                for (op, reloc) in ko_instr_operands(instr)
+                    .into_iter()
+                    .zip([relocs.0, relocs.1])
+                {
+                    if self.operand_matches_str(
+                        op,
+                        reloc,
+                        data_section,
+                        symtab_opt,
+                        symstrtab_opt,
+                        symbol_text,
+                    )? {
+                        func_section_found = Some(func_section);
+                        break;
                     }
-                    kerbalobjects::ko::Instr::TwoOp(_, op1, op2) => {
-                        func_section_found = if self.operand_matches_str(
-                            *op1,
-                            relocs.0,
-                            data_section,
-                            symtab_opt,
-                            symstrtab_opt,
-                            symbol_text,
-                        )? || self.operand_matches_str(
-                            *op2,
-                            relocs.1,
-                            data_section,
-                            symtab_opt,
-                            symstrtab_opt,
-                            symbol_text,
-                        )? {
-                            Some(func_section)
-                        } else {
-                            None
-                        };
+                }
 
-                        if func_section_found.is_some() {
-                            break;
-                        }
-                    }
+                if func_section_found.is_some() {
+                    break;
                 }
             }
 
@@ -255,6 +906,7 @@ impl KOFileDebug {
                     section_color,
                     show_labels,
                     show_raw_instr,
+                    annotate_data_symbols,
                     section,
                 )?;
             }
@@ -315,7 +967,7 @@ impl KOFileDebug {
     #[allow(clippy::too_many_arguments)]
     fn dump_func_sections(
         &self,
-        stream: &mut StandardStream,
+        stream: &mut dyn WriteColor,
         regular_color: &ColorSpec,
         index_color: &ColorSpec,
         mnemonic_color: &ColorSpec,
@@ -324,12 +976,21 @@ impl KOFileDebug {
         section_color: &ColorSpec,
         show_labels: bool,
         show_raw_instr: bool,
+        annotate_data_symbols: bool,
     ) -> DumpResult {
         stream.set_color(regular_color)?;
 
         writeln!(stream, "\nFunction sections: ")?;
 
-        for func_section in self.kofile.func_sections() {
+        let total = self.kofile.func_sections().count();
+
+        for (done, func_section) in self.kofile.func_sections().enumerate() {
+            if let Some(reason) = self.deadline.check() {
+                stream.set_color(regular_color)?;
+
+                return super::write_stop_note(stream, "", reason, done, total, false);
+            }
+
             self.dump_func_section(
                 stream,
                 regular_color,
@@ -340,6 +1001,7 @@ impl KOFileDebug {
                 section_color,
                 show_labels,
                 show_raw_instr,
+                annotate_data_symbols,
                 func_section,
             )?;
         }
@@ -350,7 +1012,7 @@ impl KOFileDebug {
     #[allow(clippy::too_many_arguments)]
     fn dump_func_section(
         &self,
-        stream: &mut StandardStream,
+        stream: &mut dyn WriteColor,
         regular_color: &ColorSpec,
         index_color: &ColorSpec,
         mnemonic_color: &ColorSpec,
@@ -359,6 +1021,7 @@ impl KOFileDebug {
         section_color: &ColorSpec,
         show_labels: bool,
         show_raw_instr: bool,
+        annotate_data_symbols: bool,
         func_section: &FuncSection,
     ) -> DumpResult {
         stream.set_color(regular_color)?;
@@ -375,9 +1038,47 @@ impl KOFileDebug {
         let symtab_opt = self.kofile.sym_tab_by_name(".symtab");
         let symstrtab_opt = self.kofile.str_tab_by_name(".symstrtab");
 
-        writeln!(stream, "{}:", name)?;
+        write!(stream, "{}", name)?;
+
+        // The FUNC symbol that marks the entry of this section, if there is one
+        let entry_symbol = symtab_opt.and_then(|symtab| {
+            symtab
+                .symbols()
+                .find(|symbol| symbol.sym_type == SymType::Func && symbol.sh_idx == sh_index)
+        });
+
+        if let Some(symbol) = entry_symbol {
+            let symbol_name = symstrtab_opt
+                .and_then(|symstrtab| symstrtab.get(symbol.name_idx))
+                .map(|s| s.as_str())
+                .unwrap_or("");
+
+            if symbol_name != name {
+                write!(stream, " <")?;
+                stream.set_color(func_color)?;
+                write!(stream, "{}", symbol_name)?;
+                stream.set_color(regular_color)?;
+                write!(stream, ">")?;
+            }
+
+            write!(
+                stream,
+                " ({} {}, {} bytes)",
+                KOFileDebug::bind_as_str(symbol.sym_bind),
+                KOFileDebug::sym_type_as_str(symbol.sym_type),
+                symbol.size
+            )?;
+        }
+
+        writeln!(stream, ":")?;
+
+        // Where each instruction is in the file, so that unknown opcodes can be shown as their byte
+        let mut offset = layout::ko_section_range(self.kofile, name).map(|range| range.start);
 
         for (i, instr) in func_section.instructions().enumerate() {
+            let instr_offset = offset;
+            offset = offset.map(|offset| offset + layout::ko_instr_size(instr));
+
             write!(stream, "  ")?;
 
             if show_labels {
@@ -386,6 +1087,28 @@ impl KOFileDebug {
                 stream.set_color(regular_color)?;
             }
 
+            // An unknown opcode is shown as the byte it was, instead of an instruction that doesn't exist
+            if instr.opcode() == Opcode::Bogus {
+                let byte = instr_offset.and_then(|offset| self.raw_contents?.get(offset));
+
+                if show_raw_instr {
+                    match byte {
+                        Some(byte) => write!(stream, "{:0>2x} {:<8} {:<8} ", byte, "", "")?,
+                        None => write!(stream, "?? {:<8} {:<8} ", "", "")?,
+                    }
+                }
+
+                super::write_unknown_byte(
+                    stream,
+                    mnemonic_color,
+                    regular_color,
+                    byte.copied(),
+                    instr_offset,
+                )?;
+
+                continue;
+            }
+
             let instr_opcode = if show_raw_instr {
                 match instr {
                     kerbalobjects::ko::Instr::ZeroOp(opcode) => {
@@ -425,196 +1148,345 @@ impl KOFileDebug {
 
             let relocs = self.get_relocated(sh_index, InstrIdx::from(i));
 
-            match instr {
-                kerbalobjects::ko::Instr::ZeroOp(_) => {}
-                kerbalobjects::ko::Instr::OneOp(_, op1) => {
-                    // If this operand has a relocation entry
-                    if relocs.0 .0 {
-                        let symtab = symtab_opt
-                            .ok_or("Instruction requires symbol, but symbol table not found")?;
-                        let symstrtab = symstrtab_opt.ok_or(
-                            "Instruction requires symbol, but symbol string table not found",
-                        )?;
+            for (n, (op, reloc)) in ko_instr_operands(instr)
+                .into_iter()
+                .zip([relocs.0, relocs.1])
+                .enumerate()
+            {
+                if n > 0 {
+                    write!(stream, ", ")?;
+                }
 
-                        let sym1 = symtab.get(relocs.0 .1).ok_or(format!(
-                            "Reld entry symbol index invalid: {}",
-                            u32::from(relocs.0 .1)
-                        ))?;
-
-                        let sym1_name = symstrtab.get(sym1.name_idx).ok_or(format!(
-                            "Symbol has invalid name index: {}",
-                            u32::from(sym1.name_idx)
-                        ))?;
-
-                        match sym1.sym_type {
-                            kerbalobjects::ko::symbols::SymType::Func => {
-                                stream.set_color(func_color)?;
-                                write!(stream, "<{}>", sym1_name)?;
-                                stream.set_color(regular_color)?;
-                            }
-                            kerbalobjects::ko::symbols::SymType::Section => {
-                                stream.set_color(section_color)?;
-                                write!(stream, "<{}>", sym1_name)?;
-                                stream.set_color(regular_color)?;
-                            }
-                            kerbalobjects::ko::symbols::SymType::NoType => {
-                                stream.set_color(variable_color)?;
-                                write!(stream, "<{}>", sym1_name)?;
-                                stream.set_color(regular_color)?;
-                            }
-                            _ => {}
-                        }
-                    } else {
-                        // This instruction has a regular value
-                        let value = data_section.get(*op1).ok_or(format!(
-                            "Instruction data index invalid: {}",
-                            u32::from(*op1)
-                        ))?;
+                self.dump_operand(
+                    stream,
+                    op,
+                    reloc,
+                    data_section,
+                    symtab_opt,
+                    symstrtab_opt,
+                    regular_color,
+                    variable_color,
+                    func_color,
+                    section_color,
+                    annotate_data_symbols,
+                )?;
+            }
 
-                        super::write_kosvalue(stream, value, regular_color, variable_color)?;
-                    }
+            writeln!(stream)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a single instruction operand, which is either a symbol if it has a relocation entry,
+    /// or a value from the data section
+    #[allow(clippy::too_many_arguments)]
+    fn dump_operand(
+        &self,
+        stream: &mut dyn WriteColor,
+        op: DataIdx,
+        reloc: (bool, SymbolIdx),
+        data_section: &DataSection,
+        symtab_opt: Option<&SymbolTable>,
+        symstrtab_opt: Option<&StringTable>,
+        regular_color: &ColorSpec,
+        variable_color: &ColorSpec,
+        func_color: &ColorSpec,
+        section_color: &ColorSpec,
+        annotate_data_symbols: bool,
+    ) -> DumpResult {
+        // If this operand has a relocation entry
+        if reloc.0 {
+            let symtab =
+                symtab_opt.ok_or("Instruction requires symbol, but symbol table not found")?;
+            let symstrtab = symstrtab_opt
+                .ok_or("Instruction requires symbol, but symbol string table not found")?;
+
+            let sym = symtab.get(reloc.1).ok_or(format!(
+                "Reld entry symbol index invalid: {}",
+                u32::from(reloc.1)
+            ))?;
+
+            let sym_name = symstrtab.get(sym.name_idx).ok_or(format!(
+                "Symbol has invalid name index: {}",
+                u32::from(sym.name_idx)
+            ))?;
+
+            match sym.sym_type {
+                SymType::Func => {
+                    stream.set_color(func_color)?;
+                    write!(stream, "<{}>", sym_name)?;
+                    stream.set_color(regular_color)?;
                 }
-                kerbalobjects::ko::Instr::TwoOp(_, op1, op2) => {
-                    // If this operand has a relocation entry
-                    if relocs.0 .0 {
-                        let symtab = symtab_opt
-                            .ok_or("Instruction requires symbol, but symbol table not found")?;
-                        let symstrtab = symstrtab_opt.ok_or(
-                            "Instruction requires symbol, but symbol string table not found",
-                        )?;
+                SymType::Section => {
+                    stream.set_color(section_color)?;
+                    write!(stream, "<{}>", sym_name)?;
+                    stream.set_color(regular_color)?;
+                }
+                SymType::NoType => {
+                    stream.set_color(variable_color)?;
+                    write!(stream, "<{}>", sym_name)?;
+                    stream.set_color(regular_color)?;
+                }
+                _ => {}
+            }
+        } else {
+            // This instruction has a regular value
+            let value = data_section
+                .get(op)
+                .ok_or(format!("Instruction data index invalid: {}", u32::from(op)))?;
 
-                        let sym1 = symtab.get(relocs.0 .1).ok_or(format!(
-                            "Reld entry symbol index invalid: {}",
-                            u32::from(relocs.0 .1)
-                        ))?;
-
-                        let sym1_name = symstrtab.get(sym1.name_idx).ok_or(format!(
-                            "Symbol has invalid name index: {}",
-                            u32::from(sym1.name_idx)
-                        ))?;
-
-                        match sym1.sym_type {
-                            kerbalobjects::ko::symbols::SymType::Func => {
-                                stream.set_color(func_color)?;
-                                write!(stream, "<{}>", sym1_name)?;
-                                stream.set_color(regular_color)?;
-                            }
-                            kerbalobjects::ko::symbols::SymType::Section => {
-                                stream.set_color(section_color)?;
-                                write!(stream, "<{}>", sym1_name)?;
-                                stream.set_color(regular_color)?;
-                            }
-                            kerbalobjects::ko::symbols::SymType::NoType => {
-                                stream.set_color(variable_color)?;
-                                write!(stream, "<{}>", sym1_name)?;
-                                stream.set_color(regular_color)?;
-                            }
-                            _ => {}
-                        }
-                    } else {
-                        // This instruction has a regular value
-                        let value = data_section.get(*op1).ok_or(format!(
-                            "Instruction data index invalid: {}",
-                            u32::from(*op1)
-                        ))?;
+            super::write_kosvalue(stream, value, self.encoding, regular_color, variable_color)?;
 
-                        super::write_kosvalue(stream, value, regular_color, variable_color)?;
-                    }
+            if annotate_data_symbols {
+                if let Some(sym_name) =
+                    self.data_symbol_name(op, data_section, symtab_opt, symstrtab_opt)
+                {
+                    write!(stream, " ")?;
+                    stream.set_color(variable_color)?;
+                    write!(stream, "<{}>", sym_name)?;
+                    stream.set_color(regular_color)?;
+                }
+            }
+        }
 
-                    write!(stream, ", ")?;
+        Ok(())
+    }
 
-                    // If this operand has a relocation entry
-                    if relocs.1 .0 {
-                        let symtab = symtab_opt
-                            .ok_or("Instruction requires symbol, but symbol table not found")?;
-                        let symstrtab = symstrtab_opt.ok_or(
-                            "Instruction requires symbol, but symbol string table not found",
-                        )?;
+    /// Finds the name of a data symbol that refers to the given index in the data section, if there is one
+    fn data_symbol_name<'b>(
+        &self,
+        op: DataIdx,
+        data_section: &DataSection,
+        symtab_opt: Option<&'b SymbolTable>,
+        symstrtab_opt: Option<&'b StringTable>,
+    ) -> Option<&'b str> {
+        let symtab = symtab_opt?;
+        let symstrtab = symstrtab_opt?;
+
+        let symbol = symtab.symbols().find(|symbol| {
+            matches!(symbol.sym_type, SymType::NoType | SymType::Object)
+                && symbol.sh_idx == data_section.section_index()
+                && symbol.value_idx == op
+        })?;
+
+        symstrtab.get(symbol.name_idx).map(|s| s.as_str())
+    }
 
-                        let sym2 = symtab.get(relocs.1 .1).ok_or(format!(
-                            "Reld entry symbol index invalid: {}",
-                            u32::from(relocs.1 .1)
-                        ))?;
-
-                        let sym2_name = symstrtab.get(sym2.name_idx).ok_or(format!(
-                            "Symbol has invalid name index: {}",
-                            u32::from(sym2.name_idx)
-                        ))?;
-
-                        match sym2.sym_type {
-                            kerbalobjects::ko::symbols::SymType::Func => {
-                                stream.set_color(func_color)?;
-                                write!(stream, "<{}>", sym2_name)?;
-                                stream.set_color(regular_color)?;
-                            }
-                            kerbalobjects::ko::symbols::SymType::Section => {
-                                stream.set_color(section_color)?;
-                                write!(stream, "<{}>", sym2_name)?;
-                                stream.set_color(regular_color)?;
-                            }
-                            kerbalobjects::ko::symbols::SymType::NoType => {
-                                stream.set_color(variable_color)?;
-                                write!(stream, "<{}>", sym2_name)?;
-                                stream.set_color(regular_color)?;
-                            }
-                            _ => {}
-                        }
-                    } else {
-                        // This instruction has a regular value
-                        let value = data_section.get(*op2).ok_or(format!(
-                            "Instruction data index invalid: {}",
-                            u32::from(*op1)
-                        ))?;
+    /// Lists every symbol alphabetically, along with the section it is in and its value, which for
+    /// functions is the label of the instruction it begins at
+    fn dump_symbol_index(
+        &self,
+        stream: &mut dyn WriteColor,
+        regular_color: &ColorSpec,
+        label_color: &ColorSpec,
+    ) -> DumpResult {
+        let mut entries = Vec::new();
 
-                        super::write_kosvalue(stream, value, regular_color, variable_color)?;
+        if let Some(symstrtab) = self.kofile.str_tab_by_name(".symstrtab") {
+            for symbol_table in self.kofile.sym_tabs() {
+                for symbol in symbol_table.symbols() {
+                    if symbol.sym_type == SymType::File {
+                        continue;
                     }
+
+                    let name = match symstrtab.get(symbol.name_idx) {
+                        Some(name) if !name.is_empty() => name.as_str(),
+                        _ => continue,
+                    };
+
+                    let section_name = self.get_section_name(symbol.sh_idx).unwrap_or("");
+
+                    entries.push((name, section_name, symbol));
                 }
             }
+        }
 
-            writeln!(stream)?;
+        entries.sort_by_key(|(name, _, _)| name.to_lowercase());
+
+        stream.set_color(regular_color)?;
+        writeln!(stream, "\nSymbol index:")?;
+
+        for (name, section_name, symbol) in entries {
+            write!(
+                stream,
+                "  {:<32} {:<8} {:<16} ",
+                name,
+                KOFileDebug::sym_type_as_str(symbol.sym_type),
+                section_name
+            )?;
+            stream.set_color(label_color)?;
+            writeln!(stream, "{:0>8x}", u32::from(symbol.value_idx) + 1)?;
+            stream.set_color(regular_color)?;
         }
 
         Ok(())
     }
 
-    fn get_relocated(
-        &self,
-        section_index: SectionIdx,
-        instr_index: InstrIdx,
-    ) -> ((bool, SymbolIdx), (bool, SymbolIdx)) {
-        let mut first_reloc = (false, SymbolIdx::from(0u32));
-        let mut second_reloc = (false, SymbolIdx::from(0u32));
-
-        let reld_section = match self.kofile.reld_section_by_name(".reld") {
-            Some(section) => section,
-            None => {
-                return (
-                    (false, SymbolIdx::from(0u32)),
-                    (false, SymbolIdx::from(0u32)),
-                );
+    /// Cross-checks each function section against the size its section header gives it
+    fn dump_verification(&self, stream: &mut dyn WriteColor) -> DumpResult {
+        let checks = analysis::check_ko_sections(self.kofile);
+        let names = self
+            .kofile
+            .func_sections()
+            .map(|func_section| self.get_section_name(func_section.section_index()))
+            .collect::<DynResult<Vec<_>>>()?;
+
+        super::write_verification(stream, &checks, &names)
+    }
+
+    /// Checks the file against the invariants that the KO format documents, and prints whether
+    /// each one holds along with what broke it
+    fn dump_spec_report(&self, stream: &mut dyn WriteColor) -> DumpResult {
+        const REQUIRED_SECTIONS: [&str; 4] = [".shstrtab", ".symstrtab", ".symtab", ".data"];
+
+        writeln!(stream, "\nKO spec conformance:")?;
+
+        let header = self.kofile.header();
+        let headers: Vec<_> = self.kofile.section_headers().collect();
+        let names: Vec<Option<&str>> = headers
+            .iter()
+            .map(|header| self.kofile.get_header_name(header).map(|s| s.as_str()))
+            .collect();
+
+        let mut checks: Vec<(&str, Vec<String>)> = Vec::new();
+
+        let mut problems = Vec::new();
+        if headers.len() != header.num_headers as usize {
+            problems.push(format!(
+                "the file header says there are {} section headers, but {} were read",
+                header.num_headers,
+                headers.len()
+            ));
+        }
+        checks.push(("Section header count matches the file header", problems));
+
+        let mut problems = Vec::new();
+        match headers.first() {
+            Some(first) if first.section_kind != SectionKind::Null => problems.push(format!(
+                "section 0 is of kind {}",
+                KOFileDebug::kind_as_str(first.section_kind)
+            )),
+            Some(first) if first.size != 0 => {
+                problems.push(format!("section 0 has a size of {}", first.size))
             }
-        };
+            Some(_) => {}
+            None => problems.push(String::from("there are no section headers")),
+        }
+        checks.push(("Section 0 is an empty null section", problems));
+
+        let mut problems = Vec::new();
+        let shstrtab_idx = u16::from(header.shstrtab_idx) as usize;
+        match headers.get(shstrtab_idx) {
+            Some(shstrtab) => {
+                if shstrtab.section_kind != SectionKind::StrTab {
+                    problems.push(format!(
+                        "section {} is of kind {}",
+                        shstrtab_idx,
+                        KOFileDebug::kind_as_str(shstrtab.section_kind)
+                    ));
+                }
 
-        for reld_entry in reld_section.entries() {
-            if reld_entry.section_index == section_index && reld_entry.instr_index == instr_index {
-                match reld_entry.operand_index {
-                    OperandIndex::One => {
-                        first_reloc = (true, reld_entry.symbol_index);
-                    }
-                    OperandIndex::Two => {
-                        second_reloc = (true, reld_entry.symbol_index);
-                    }
+                if names[shstrtab_idx] != Some(".shstrtab") {
+                    problems.push(format!(
+                        "section {} is named {}",
+                        shstrtab_idx,
+                        names[shstrtab_idx].unwrap_or("nothing")
+                    ));
+                }
+            }
+            None => problems.push(format!(
+                "index {} is past the last section header",
+                shstrtab_idx
+            )),
+        }
+        checks.push(("The .shstrtab index points to .shstrtab", problems));
+
+        let problems = names
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, name)| name.is_none())
+            .map(|(i, _)| format!("section {} has an invalid name index", i))
+            .collect();
+        checks.push(("Every section has a name", problems));
+
+        let problems = REQUIRED_SECTIONS
+            .iter()
+            .filter(|required| !names.contains(&Some(**required)))
+            .map(|required| format!("{} is missing", required))
+            .collect();
+        checks.push(("Required sections are present", problems));
+
+        let mut problems = Vec::new();
+        for (i, (header, name)) in headers.iter().zip(&names).enumerate() {
+            let expected = match name {
+                Some(".shstrtab" | ".symstrtab" | ".comment") => SectionKind::StrTab,
+                Some(".symtab") => SectionKind::SymTab,
+                Some(".data") => SectionKind::Data,
+                Some(".reld") => SectionKind::Reld,
+                _ => continue,
+            };
+
+            if header.section_kind != expected {
+                problems.push(format!(
+                    "section {} ({}) is of kind {}, but should be {}",
+                    i,
+                    name.unwrap_or(""),
+                    KOFileDebug::kind_as_str(header.section_kind),
+                    KOFileDebug::kind_as_str(expected)
+                ));
+            }
+        }
+        checks.push(("Section kinds match their names", problems));
+
+        let mut problems = Vec::new();
+        for (i, name) in names.iter().enumerate().skip(1) {
+            if let Some(name) = name {
+                if names[..i].contains(&Some(name)) {
+                    problems.push(format!("{} appears more than once", name));
+                }
+            }
+        }
+        checks.push(("Section names are unique", problems));
+
+        let num_checks = checks.len();
+        let mut num_passed = 0;
+
+        for (description, problems) in checks {
+            if problems.is_empty() {
+                num_passed += 1;
+                writeln!(stream, "  [PASS] {}", description)?;
+            } else {
+                writeln!(stream, "  [FAIL] {}", description)?;
+
+                for problem in problems {
+                    writeln!(stream, "           {}", problem)?;
                 }
             }
         }
 
-        (first_reloc, second_reloc)
+        writeln!(stream, "  {} of {} checks passed.", num_passed, num_checks)?;
+
+        Ok(())
+    }
+
+    fn get_relocated(&self, section_index: SectionIdx, instr_index: InstrIdx) -> OperandRelocs {
+        let key = (u16::from(section_index), u32::from(instr_index));
+
+        match self.relocations.get(&key) {
+            Some(relocs) => *relocs,
+            None => (
+                (false, SymbolIdx::from(0u32)),
+                (false, SymbolIdx::from(0u32)),
+            ),
+        }
     }
 
     #[allow(clippy::too_many_arguments)]
     fn dump_symbols(
         &self,
-        stream: &mut StandardStream,
+        stream: &mut dyn WriteColor,
         regular_color: &ColorSpec,
         name_color: &ColorSpec,
         value_color: &ColorSpec,
@@ -662,22 +1534,12 @@ impl KOFileDebug {
                         stream.set_color(size_color)?;
                         write!(stream, "{:0>4x}    ", symbol.size)?;
 
-                        let bind_str = match symbol.sym_bind {
-                            kerbalobjects::ko::symbols::SymBind::Local => "LOCAL",
-                            kerbalobjects::ko::symbols::SymBind::Global => "GLOBAL",
-                            kerbalobjects::ko::symbols::SymBind::Extern => "EXTERN",
-                        };
+                        let bind_str = KOFileDebug::bind_as_str(symbol.sym_bind);
 
                         stream.set_color(bind_color)?;
                         write!(stream, "{:<10}", bind_str)?;
 
-                        let kind_str = match symbol.sym_type {
-                            kerbalobjects::ko::symbols::SymType::Func => "FUNC",
-                            kerbalobjects::ko::symbols::SymType::File => "FILE",
-                            kerbalobjects::ko::symbols::SymType::NoType => "NOTYPE",
-                            kerbalobjects::ko::symbols::SymType::Object => "OBJECT",
-                            kerbalobjects::ko::symbols::SymType::Section => "SECTION",
-                        };
+                        let kind_str = KOFileDebug::sym_type_as_str(symbol.sym_type);
 
                         stream.set_color(type_color)?;
                         write!(stream, "{:<10}", kind_str)?;
@@ -695,9 +1557,100 @@ impl KOFileDebug {
         Ok(())
     }
 
+    /// Groups symbols under the FILE symbol that precedes them in the symbol table, which is
+    /// how a linker records which source file each section and symbol came from
+    fn dump_provenance(
+        &self,
+        stream: &mut dyn WriteColor,
+        regular_color: &ColorSpec,
+        name_color: &ColorSpec,
+        type_color: &ColorSpec,
+    ) -> DumpResult {
+        stream.set_color(regular_color)?;
+        writeln!(stream, "\nSymbol provenance:")?;
+
+        let symstrtab = match self.kofile.str_tab_by_name(".symstrtab") {
+            Some(symstrtab) => symstrtab,
+            None => {
+                writeln!(stream, "None.")?;
+                return Ok(());
+            }
+        };
+
+        for symbol_table in self.kofile.sym_tabs() {
+            let mut current_file: Option<&str> = None;
+            let mut printed_header = false;
+
+            for symbol in symbol_table.symbols() {
+                let symbol_name = symstrtab
+                    .get(symbol.name_idx)
+                    .map(|s| s.as_str())
+                    .unwrap_or("");
+
+                if symbol.sym_type == SymType::File {
+                    current_file = Some(symbol_name);
+                    printed_header = false;
+                    continue;
+                }
+
+                if !printed_header {
+                    stream.set_color(regular_color)?;
+                    write!(stream, "File ")?;
+                    stream.set_color(name_color)?;
+                    writeln!(stream, "{}", current_file.unwrap_or("<unknown>"))?;
+                    stream.set_color(regular_color)?;
+
+                    printed_header = true;
+                }
+
+                let kind_str = KOFileDebug::sym_type_as_str(symbol.sym_type);
+                let bind_str = KOFileDebug::bind_as_str(symbol.sym_bind);
+
+                let section_name = self.get_section_name(symbol.sh_idx).unwrap_or("");
+
+                stream.set_color(type_color)?;
+                write!(stream, "  {:<9}{:<8}", kind_str, bind_str)?;
+                stream.set_color(name_color)?;
+                write!(stream, "{:<24}", symbol_name)?;
+                stream.set_color(regular_color)?;
+                writeln!(stream, "in {}", section_name)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the data sections and string tables as JSON, with the offset and size of each entry
+    fn dump_data_json(&self, stream: &mut dyn WriteColor, config: &CLIConfig) -> DumpResult {
+        let data_sections = self
+            .kofile
+            .data_sections()
+            .map(|data_section| {
+                Ok((
+                    self.get_section_name(data_section.section_index())?,
+                    data_section,
+                ))
+            })
+            .collect::<DynResult<Vec<_>>>()?;
+        let string_tables = self
+            .kofile
+            .str_tabs()
+            .map(|strtab| Ok((self.get_section_name(strtab.section_index())?, strtab)))
+            .collect::<DynResult<Vec<_>>>()?;
+
+        super::datamap::write_ko_data_json(
+            stream,
+            &super::report_title(config),
+            &data_sections,
+            &string_tables,
+            self.encoding,
+            config.pretty,
+        )
+    }
+
     fn dump_data(
         &self,
-        stream: &mut StandardStream,
+        stream: &mut dyn WriteColor,
         regular_color: &ColorSpec,
         type_color: &ColorSpec,
         variable_color: &ColorSpec,
@@ -711,10 +1664,18 @@ impl KOFileDebug {
             let name = self.get_section_name(sh_index)?;
 
             writeln!(stream, "Section {}", name)?;
-            writeln!(stream, "{:<12}{:<12}Value", "Index", "Type")?;
+            writeln!(
+                stream,
+                "{:<10}{:<10}{:<10}{:<12}Value",
+                "Index", "Hex", "Offset", "Type"
+            )?;
+
+            let mut offset = 0;
 
             for (i, value) in data_section.data().enumerate() {
-                write!(stream, "  {:<10}", i)?;
+                write!(stream, "  {:<8}{:0>8x}  {:0>8x}  ", i, i, offset)?;
+
+                offset += value.size_bytes();
 
                 stream.set_color(type_color)?;
                 match value {
@@ -803,7 +1764,8 @@ impl KOFileDebug {
 
     fn dump_section_headers(
         &self,
-        stream: &mut StandardStream,
+        stream: &mut dyn WriteColor,
+        config: &CLIConfig,
         regular_color: &ColorSpec,
         name_color: &ColorSpec,
         type_color: &ColorSpec,
@@ -830,13 +1792,93 @@ impl KOFileDebug {
                 KOFileDebug::kind_as_str(header.section_kind)
             )?;
             stream.set_color(size_color)?;
-            writeln!(stream, "{:<12}\n", header.size)?;
+            writeln!(
+                stream,
+                "{:<12}\n",
+                super::format_size(header.size as usize, config.human_readable)
+            )?;
             stream.set_color(regular_color)?;
         }
 
         Ok(())
     }
 
+    pub(super) fn bind_as_str(bind: SymBind) -> &'static str {
+        match bind {
+            SymBind::Local => "LOCAL",
+            SymBind::Global => "GLOBAL",
+            SymBind::Extern => "EXTERN",
+        }
+    }
+
+    pub(super) fn sym_type_as_str(sym_type: SymType) -> &'static str {
+        match sym_type {
+            SymType::Func => "FUNC",
+            SymType::File => "FILE",
+            SymType::NoType => "NOTYPE",
+            SymType::Object => "OBJECT",
+            SymType::Section => "SECTION",
+        }
+    }
+
+    /// Writes the section header table and the symbol tables as CSV
+    ///
+    /// Section rows have the columns: table, index, name, kind, size
+    /// Symbol rows have the columns: table, name, value, size, binding, type, section
+    fn dump_csv(&self, stream: &mut dyn WriteColor) -> DumpResult {
+        super::write_csv_row(stream, &["table", "index", "name", "kind", "size"])?;
+
+        for (i, header) in self.kofile.section_headers().enumerate() {
+            let name = self.get_section_name(SectionIdx::from(i as u16))?;
+
+            super::write_csv_row(
+                stream,
+                &[
+                    "section",
+                    &i.to_string(),
+                    name,
+                    KOFileDebug::kind_as_str(header.section_kind),
+                    &header.size.to_string(),
+                ],
+            )?;
+        }
+
+        writeln!(stream)?;
+
+        super::write_csv_row(
+            stream,
+            &[
+                "table", "name", "value", "size", "binding", "type", "section",
+            ],
+        )?;
+
+        let symstrtab_opt = self.kofile.str_tab_by_name(".symstrtab");
+
+        for symbol_table in self.kofile.sym_tabs() {
+            for symbol in symbol_table.symbols() {
+                let name = symstrtab_opt
+                    .and_then(|symstrtab| symstrtab.get(symbol.name_idx))
+                    .map(|s| s.as_str())
+                    .unwrap_or("");
+
+                super::write_csv_row(
+                    stream,
+                    &[
+                        "symbol",
+                        name,
+                        &u32::from(symbol.value_idx).to_string(),
+                        &symbol.size.to_string(),
+                        KOFileDebug::bind_as_str(symbol.sym_bind),
+                        KOFileDebug::sym_type_as_str(symbol.sym_type),
+                        &u16::from(symbol.sh_idx).to_string(),
+                    ],
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
     fn kind_as_str(kind: SectionKind) -> &'static str {
         match kind {
             SectionKind::Null => "NULL",
@@ -849,7 +1891,7 @@ impl KOFileDebug {
         }
     }
 
-    fn dump_info(&self, stream: &mut StandardStream) -> DumpResult {
+    fn dump_info(&self, stream: &mut dyn WriteColor) -> DumpResult {
         writeln!(stream, "\nKO File Info:")?;
 
         if let Some(comment_section) =
@@ -877,7 +1919,7 @@ impl KOFileDebug {
 
     fn dump_strtabs(
         &self,
-        stream: &mut StandardStream,
+        stream: &mut dyn WriteColor,
         regular_color: &ColorSpec,
         index_color: &ColorSpec,
         str_color: &ColorSpec,
@@ -918,7 +1960,75 @@ impl KOFileDebug {
         Ok(())
     }
 
-    fn dump_ko_header(&self, stream: &mut StandardStream) -> DumpResult {
+    fn header_info(&self) -> KoHeaderInfo {
+        let header = self.kofile.header();
+
+        KoHeaderInfo {
+            version: header.version,
+            num_headers: header.num_headers,
+            shstrtab_idx: u16::from(header.shstrtab_idx),
+        }
+    }
+
+    /// Describes each section header, along with where its section is in the file
+    fn section_rows(&self) -> DynResult<Vec<SectionRow>> {
+        let section_index_of = |name: &str| {
+            self.kofile
+                .section_headers()
+                .position(|header| {
+                    self.kofile.get_header_name(header).map(|s| s.as_str()) == Some(name)
+                })
+                .unwrap_or(0)
+        };
+
+        let ranges = layout::ko_section_ranges(self.kofile);
+        let mut rows = Vec::new();
+
+        for (i, (header, range)) in self.kofile.section_headers().zip(ranges).enumerate() {
+            let name = self.get_section_name(SectionIdx::from(i as u16))?;
+
+            let (entry_size, flags, link) = match header.section_kind {
+                SectionKind::SymTab => (KO_SYMBOL_SIZE, "", section_index_of(".symstrtab")),
+                SectionKind::Reld => (KO_RELD_ENTRY_SIZE, "", section_index_of(".symtab")),
+                SectionKind::Func => (0, "AX", 0),
+                SectionKind::Data => (0, "A", 0),
+                _ => (0, "", 0),
+            };
+
+            rows.push(SectionRow {
+                name: name.to_string(),
+                kind: KOFileDebug::kind_as_str(header.section_kind),
+                offset: range.start,
+                size: range.len(),
+                entry_size,
+                flags,
+                link,
+            });
+        }
+
+        Ok(rows)
+    }
+
+    fn symbol_rows(&self, symbol_table: &SymbolTable) -> Vec<SymbolRow> {
+        let symstrtab_opt = self.kofile.str_tab_by_name(".symstrtab");
+
+        symbol_table
+            .symbols()
+            .map(|symbol| SymbolRow {
+                value: u32::from(symbol.value_idx),
+                size: symbol.size,
+                sym_type: KOFileDebug::sym_type_as_str(symbol.sym_type),
+                bind: KOFileDebug::bind_as_str(symbol.sym_bind),
+                section: u16::from(symbol.sh_idx),
+                name: symstrtab_opt
+                    .and_then(|symstrtab| symstrtab.get(symbol.name_idx))
+                    .cloned()
+                    .unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    fn dump_ko_header(&self, stream: &mut dyn WriteColor) -> DumpResult {
         writeln!(stream, "\nFile header:")?;
 
         writeln!(stream, "\tVersion: {}", self.kofile.header().version)?;
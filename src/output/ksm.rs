@@ -1,4 +1,17 @@
+use crate::analysis::{self, CallGraph, FlowInstr};
+use crate::asm;
+use crate::cancel::{Deadline, StopReason};
+use crate::fio::{self, layout};
+use crate::index::{instr_opcode, instr_operand_count, instr_operands, IndexedKsm, InstrLocation};
+use crate::model;
+use crate::AddressBound;
+use crate::ArgIndexPlacement;
 use crate::CLIConfig;
+use crate::ColorRole;
+use crate::GrepPattern;
+use crate::SectionOrder;
+use crate::SectionSelector;
+use crate::StringEncoding;
 use crate::DARK_RED_COLOR;
 use crate::GREEN_COLOR;
 use crate::LIGHT_RED_COLOR;
@@ -7,54 +20,392 @@ use crate::ORANGE_COLOR;
 use crate::PURPLE_COLOR;
 use kerbalobjects::ksm::sections::DebugEntry;
 use kerbalobjects::ksm::sections::DebugRange;
-use kerbalobjects::ksm::sections::{ArgIndex, CodeSection};
+use kerbalobjects::ksm::sections::{ArgIndex, CodeSection, CodeType};
 use kerbalobjects::ksm::Instr;
 use kerbalobjects::ksm::KSMFile;
 use kerbalobjects::KOSValue;
 use kerbalobjects::Opcode;
-use std::io::Write;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::ops::Range;
+use std::path::Path;
 use termcolor::ColorSpec;
-use termcolor::StandardStream;
 use termcolor::WriteColor;
 
+use super::kasm::{self, KasmFunction, KasmInstr, KasmOperand, KasmProgram, KasmValue};
+use super::style::{self, DisasmLine, DisasmSection, DisasmStyle};
 use super::{DumpResult, DynResult};
 
-pub struct KSMFileDebug {
-    ksmfile: KSMFile,
+/// The number of unknown opcodes in a row that it takes to decide that a code section can't be
+/// decoded any further
+const BOGUS_RUN_LIMIT: usize = 8;
+
+pub struct KSMFileDebug<'a> {
+    ksmfile: &'a KSMFile,
+    index: IndexedKsm<'a>,
+    /// The position of the code section that each label, and each function's name, is in
+    call_targets: HashMap<String, usize>,
+    /// The names given to trigger bodies and anonymous functions, by the position of their section
+    anonymous_names: HashMap<usize, String>,
+    /// The file as it was read, which is used to try decoding it in other ways when it doesn't decode
+    raw_contents: Option<&'a [u8]>,
+    /// How strings are decoded when they are shown
+    encoding: StringEncoding,
+    /// When the analyses that go section by section have to stop by
+    deadline: Deadline,
 }
 
-impl KSMFileDebug {
-    pub fn new(ksmfile: KSMFile) -> Self {
-        KSMFileDebug { ksmfile }
+impl<'a> KSMFileDebug<'a> {
+    pub fn new(ksmfile: &'a KSMFile) -> Self {
+        let index = IndexedKsm::new(ksmfile);
+        let call_targets = Self::index_call_targets(ksmfile, &index);
+
+        let mut debug = KSMFileDebug {
+            ksmfile,
+            index,
+            call_targets,
+            anonymous_names: HashMap::new(),
+            raw_contents: None,
+            encoding: StringEncoding::default(),
+            deadline: Deadline::default(),
+        };
+
+        debug.anonymous_names = debug.name_anonymous_functions();
+
+        debug
+    }
+
+    /// Finds the function sections that kOS made for trigger bodies and anonymous functions,
+    /// which only have labels for names, and names each one after the source line that makes it
+    ///
+    /// A trigger's body is pushed with prl and then installed by the next addt, and an anonymous
+    /// function is pushed as a delegate with pdrl, or phdl once it has been relocated.
+    fn name_anonymous_functions(&self) -> HashMap<usize, String> {
+        let mut names: HashMap<usize, String> = HashMap::new();
+
+        for (section_index, code_section) in self.ksmfile.code_sections().enumerate() {
+            let instructions: Vec<&Instr> = code_section.instructions().collect();
+            let locations = self.index.locations(section_index);
+
+            for (i, (instr, location)) in instructions.iter().zip(locations).enumerate() {
+                let (kind, op) = match instr {
+                    Instr::OneOp(Opcode::Prl, op) => {
+                        let installs = instructions[i + 1..]
+                            .iter()
+                            .map(|instr| instr_opcode(instr))
+                            .take_while(|opcode| !analysis::is_call_like(*opcode))
+                            .any(|opcode| opcode == Opcode::Addt);
+
+                        if !installs {
+                            continue;
+                        }
+
+                        (analysis::AnonymousKind::Trigger, op)
+                    }
+                    Instr::TwoOp(Opcode::Pdrl | Opcode::Phdl, op, _) => {
+                        (analysis::AnonymousKind::Function, op)
+                    }
+                    _ => continue,
+                };
+
+                let Some(KOSValue::String(target) | KOSValue::StringValue(target)) =
+                    self.index.value(*op)
+                else {
+                    continue;
+                };
+
+                let Some(callee) = self.resolve_call(target) else {
+                    continue;
+                };
+
+                if names.contains_key(&callee) {
+                    continue;
+                }
+
+                let is_opaque = self
+                    .ksmfile
+                    .code_sections()
+                    .nth(callee)
+                    .filter(|callee| callee.section_type == CodeType::Function)
+                    .and_then(|callee| self.section_name(callee).ok())
+                    .is_some_and(analysis::is_opaque_name);
+
+                if !is_opaque {
+                    continue;
+                }
+
+                let line = self
+                    .find_entry_with_addr(location.addr)
+                    .map(|(entry, _)| entry.line_number);
+                let base = analysis::anonymous_name(kind, line, &Self::long_label(target));
+
+                // A line can make more than one, so the later ones are numbered
+                let mut name = base.clone();
+                let mut n = 2;
+
+                while names.values().any(|taken| *taken == name) {
+                    name = format!("{}_{}", base, n);
+                    n += 1;
+                }
+
+                names.insert(callee, name);
+            }
+        }
+
+        names
     }
 
-    pub fn dump(&self, stream: &mut StandardStream, config: &CLIConfig) -> DumpResult {
+    /// Finds which code section every label is in, so that calls can be followed to the
+    /// function they go to
+    fn index_call_targets(ksmfile: &KSMFile, index: &IndexedKsm) -> HashMap<String, usize> {
+        let mut targets = HashMap::new();
+
+        for (section_index, code_section) in ksmfile.code_sections().enumerate() {
+            for location in index.locations(section_index) {
+                targets
+                    .entry(location.label.clone())
+                    .or_insert(section_index);
+            }
+
+            if let Some(&Instr::OneOp(Opcode::Lbrt, op)) = code_section.instructions().next() {
+                if let Some(KOSValue::String(s) | KOSValue::StringValue(s)) = index.value(op) {
+                    targets.entry(s.clone()).or_insert(section_index);
+                }
+            }
+
+            if let Ok(name) = index.section_name(code_section) {
+                targets.entry(name.to_string()).or_insert(section_index);
+            }
+        }
+
+        targets
+    }
+
+    /// Operands name labels the short way, like @0013, so they are lengthened to match the index
+    fn long_label(target: &str) -> String {
+        let mut label = target.to_string();
+
+        if label.starts_with('@') {
+            label.insert_str(1, "00");
+            label.truncate(7);
+        }
+
+        label
+    }
+
+    /// The position of the code section that a call, or a delegate push, goes to
+    fn resolve_call(&self, target: &str) -> Option<usize> {
+        let label = Self::long_label(target);
+
+        self.call_targets
+            .get(&label)
+            .or_else(|| self.call_targets.get(target))
+            .copied()
+    }
+
+    /// The destination of a call-like instruction, if it names one
+    ///
+    /// Calls through a delegate have an empty destination, since they take it from the stack.
+    fn call_destination(&self, instr: &Instr) -> Option<&'a str> {
+        match instr {
+            Instr::OneOp(opcode, op) | Instr::TwoOp(opcode, op, _)
+                if analysis::is_call_like(*opcode) =>
+            {
+                match self.index.value(*op) {
+                    Some(KOSValue::String(s) | KOSValue::StringValue(s)) if !s.is_empty() => {
+                        Some(s.as_str())
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// The comment written after a call-like instruction
+    ///
+    /// A call just gets the function it goes to. prl and pdrl push a label that the kOS loader
+    /// replaces with an address when the program is loaded, so they also get the label, and pdrl
+    /// gets what its second operand means, which is whether the delegate captures the variables
+    /// in scope where it was made.
+    fn call_note(&self, instr: &Instr) -> Option<String> {
+        let target = self.call_destination(instr)?;
+        let callee = self
+            .resolve_call(target)
+            .and_then(|callee| self.function_name(callee));
+
+        let label = Self::long_label(target);
+        let place = match &callee {
+            Some(callee) if *callee == label || *callee == target => callee.clone(),
+            Some(callee) => format!("{} in {}", label, callee),
+            None => format!("{} (not in this file)", label),
+        };
+
+        match instr {
+            Instr::OneOp(Opcode::Prl, _) => {
+                Some(format!("; -> {}, relocated by the loader", place))
+            }
+            Instr::TwoOp(Opcode::Pdrl, _, op2) => match self.index.value(*op2) {
+                Some(KOSValue::Bool(true) | KOSValue::BoolValue(true)) => Some(format!(
+                    "; -> delegate to {}, with closure (captures variables in scope)",
+                    place
+                )),
+                Some(KOSValue::Bool(false) | KOSValue::BoolValue(false)) => {
+                    Some(format!("; -> delegate to {}, without closure", place))
+                }
+                _ => Some(format!("; -> delegate to {}", place)),
+            },
+            _ => callee.map(|callee| format!("; -> {}", callee)),
+        }
+    }
+
+    /// The full name of the function in a code section, as given by its first label reset, like
+    /// mylib`do_burn, which unlike the section's name keeps what comes after the backtick
+    ///
+    /// Trigger bodies and anonymous functions get the names they were given instead.
+    fn function_name(&self, section_index: usize) -> Option<String> {
+        if let Some(name) = self.anonymous_names.get(&section_index) {
+            return Some(name.clone());
+        }
+
+        let code_section = self.ksmfile.code_sections().nth(section_index)?;
+
+        if let Some(&Instr::OneOp(Opcode::Lbrt, op)) = code_section.instructions().next() {
+            if let Some(KOSValue::String(s) | KOSValue::StringValue(s)) = self.index.value(op) {
+                return Some(s.clone());
+            }
+        }
+
+        self.section_name(code_section).ok().map(String::from)
+    }
+
+    /// Gives the bytes the file was parsed from, so that problems decoding it can be diagnosed
+    pub fn with_raw_contents(mut self, raw_contents: &'a [u8]) -> Self {
+        self.raw_contents = Some(raw_contents);
+        self
+    }
+
+    /// Sets how strings are decoded when they are shown
+    pub fn with_encoding(mut self, encoding: StringEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Sets when disassembly, decompiling, and control flow graphs stop and keep what they have
+    /// written, for --timeout and Ctrl-C
+    pub fn with_deadline(mut self, deadline: Deadline) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    pub fn dump(&self, stream: &mut dyn WriteColor, config: &CLIConfig) -> DumpResult {
         let no_color = ColorSpec::new();
         // no_color.set_fg(Some(NO_COLOR));
-        let mut purple = ColorSpec::new();
-        purple.set_fg(Some(PURPLE_COLOR));
-        let mut light_red = ColorSpec::new();
-        light_red.set_fg(Some(LIGHT_RED_COLOR));
-        let mut green = ColorSpec::new();
-        green.set_fg(Some(GREEN_COLOR));
-        let mut dark_red = ColorSpec::new();
-        dark_red.set_fg(Some(DARK_RED_COLOR));
-        let mut orange = ColorSpec::new();
-        orange.set_fg(Some(ORANGE_COLOR));
+        let label_color = super::role_color(config, ColorRole::Labels, PURPLE_COLOR);
+        let line_color = super::role_color(config, ColorRole::Lines, ORANGE_COLOR);
+        let mnemonic_color = super::role_color(config, ColorRole::Mnemonics, DARK_RED_COLOR);
+        let variable_color = super::role_color(config, ColorRole::Variables, LIGHT_RED_COLOR);
+        let type_color = super::role_color(config, ColorRole::Types, GREEN_COLOR);
+
+        if let Some(html_path) = &config.html {
+            self.write_html(config, html_path)?;
+        }
+
+        if let Some(html_path) = &config.html_interactive {
+            self.write_html_interactive(config, html_path)?;
+        }
+
+        if let Some(kasm_path) = &config.emit_kasm {
+            self.write_kasm(config, kasm_path)?;
+        }
+
+        if let Some(linemap_path) = &config.linemap {
+            self.write_linemap(config, linemap_path)?;
+        }
+
+        if let Some(parquet_path) = &config.parquet {
+            super::write_parquet(parquet_path, config, |stream, style| {
+                self.dump_styled(stream, config, style)
+            })?;
+        }
+
+        if let Some(binary_path) = &config.binary_dump {
+            let section_names = self.section_names()?;
+            let dump = model::BinaryDump::collect(|emit| {
+                model::visit_ksm(&self.index, &section_names, self.encoding, emit)
+            })?;
+
+            super::write_binary_dump(binary_path, &dump)?;
+        }
+
+        // Problems found in the file go to stderr when the output is meant for another program, so
+        // that they can never be mistaken for part of it
+        if config.machine_readable() {
+            let section_names = self.section_names()?;
+
+            super::write_diagnostics(&model::diagnose_ksm(&self.index, &section_names))?;
+        }
+
+        if config.csv {
+            return self.dump_csv(stream);
+        }
+
+        if config.ndjson {
+            return self.dump_ndjson(stream);
+        }
+
+        if let Some(template_path) = &config.template {
+            let section_names = self.section_names()?;
+            let mut context = model::collect_records(|emit| {
+                model::visit_ksm(&self.index, &section_names, self.encoding, emit)
+            })?;
+            context["diagnostics"] =
+                serde_json::to_value(model::diagnose_ksm(&self.index, &section_names))?;
+
+            return super::template::dump_template(stream, template_path, &context);
+        }
+
+        if config.cfg_dot {
+            return self.dump_cfg_dot(stream);
+        }
+
+        if config.callgraph_dot {
+            return self.dump_callgraph_dot(stream, config);
+        }
 
         if config.info {
             writeln!(stream, "\nKSM File Info:")?;
             writeln!(stream, "\t{}", self.get_info())?;
+
+            self.dump_attribution(stream)?;
+        }
+
+        if config.file_headers || config.all_headers {
+            self.dump_ksm_header(stream, config)?;
         }
 
         if config.argument_section || config.full_contents {
-            self.dump_argument_section(stream, &no_color, &green, &light_red)?;
+            self.dump_argument_section(stream, &no_color, &type_color, &variable_color)?;
         }
 
         if config.disassemble || config.full_contents {
-            self.dump_code_sections(
-                stream, config, &no_color, &orange, &purple, &dark_red, &light_red,
-            )?;
+            match style::disasm_style(config.style) {
+                Some(style) => self.dump_styled(stream, config, style.as_ref())?,
+                None => self.dump_code_sections(
+                    stream,
+                    config,
+                    &no_color,
+                    &line_color,
+                    &label_color,
+                    &mnemonic_color,
+                    &variable_color,
+                )?,
+            }
+        }
+
+        if config.decompile {
+            self.dump_decompiled(stream, config)?;
         }
 
         if let Some(disassemble_symbol) = &config.disassemble_symbol {
@@ -63,10 +414,10 @@ impl KSMFileDebug {
                 config,
                 disassemble_symbol,
                 &no_color,
-                &orange,
-                &purple,
-                &dark_red,
-                &light_red,
+                &line_color,
+                &label_color,
+                &mnemonic_color,
+                &variable_color,
             )?;
         }
 
@@ -74,6 +425,526 @@ impl KSMFileDebug {
             self.dump_debug(stream, &no_color)?;
         }
 
+        if config.verify {
+            self.dump_verification(stream)?;
+        }
+
+        if config.symbol_index {
+            self.dump_symbol_index(stream, config, &no_color, &label_color)?;
+        }
+
+        if let Some(additional) = config.simulate_growth {
+            self.dump_growth_simulation(stream, config, additional)?;
+        }
+
+        if config.opcode_costs {
+            let instrs = self
+                .ksmfile
+                .code_sections()
+                .flat_map(|code_section| code_section.instructions())
+                .map(|instr| (instr_opcode(instr), instr_operand_count(instr)));
+            let costs = super::costs::opcode_costs(instrs, self.index.index_width());
+
+            super::costs::dump_opcode_costs(stream, &costs, config.human_readable)?;
+        }
+
+        if config.line_sizes || config.line_costs {
+            let source = Self::read_source(config)?;
+
+            if config.line_sizes {
+                let sizes = super::costs::line_sizes(self.instrs_by_line(true));
+
+                super::costs::dump_line_sizes(
+                    stream,
+                    &sizes,
+                    source.as_deref(),
+                    config.human_readable,
+                )?;
+            }
+
+            if config.line_costs {
+                // Label resets are taken out when the program is loaded, so they never run
+                let costs = super::costs::line_costs(self.instrs_by_line(false));
+
+                super::costs::dump_line_costs(stream, &costs, source.as_deref())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the whole file as an HTML report
+    fn write_html(&self, config: &CLIConfig, html_path: &Path) -> DumpResult {
+        let section_names = self.section_names()?;
+
+        let mut out = BufWriter::new(File::create(html_path)?);
+
+        super::html::write_ksm_page(
+            &mut out,
+            &super::report_title(config),
+            &self.index,
+            &section_names,
+            self.encoding,
+        )?;
+
+        out.flush()?;
+
+        Ok(())
+    }
+
+    fn write_html_interactive(&self, config: &CLIConfig, html_path: &Path) -> DumpResult {
+        let section_names = self.section_names()?;
+
+        let mut out = BufWriter::new(File::create(html_path)?);
+
+        super::html::write_ksm_interactive_page(
+            &mut out,
+            &super::report_title(config),
+            &self.index,
+            &section_names,
+            self.encoding,
+        )?;
+
+        out.flush()?;
+
+        Ok(())
+    }
+
+    /// Writes every code section as pseudo-kerboscript, lifted from the instructions
+    fn dump_decompiled(&self, stream: &mut dyn WriteColor, config: &CLIConfig) -> DumpResult {
+        writeln!(
+            stream,
+            "\n// Decompiled by kDump. This is pseudo-kerboscript, which may not compile as it is."
+        )?;
+
+        let sections = self.ordered_sections(config.order);
+        let total = sections.len();
+
+        for (done, (section_index, code_section)) in sections.into_iter().enumerate() {
+            if let Some(reason) = self.deadline.check() {
+                return super::write_stop_note(stream, "// ", reason, done, total, false);
+            }
+
+            let instructions: Vec<&Instr> = code_section.instructions().collect();
+            let locations = self.index.locations(section_index);
+
+            let lift_instrs: Vec<analysis::LiftInstr> = instructions
+                .iter()
+                .zip(locations)
+                .enumerate()
+                .map(|(i, (instr, location))| {
+                    let opcode = instr_opcode(instr);
+                    let operands: Vec<Option<&KOSValue>> = instr_operands(instr)
+                        .into_iter()
+                        .map(|op| self.value_from_operand(op))
+                        .collect();
+
+                    let target = operands
+                        .first()
+                        .copied()
+                        .flatten()
+                        .filter(|_| analysis::is_branch(opcode))
+                        .and_then(|value| Self::branch_target_index(locations, i, value));
+
+                    analysis::LiftInstr {
+                        opcode,
+                        operands,
+                        label: Self::display_label(config, location),
+                        target,
+                        callee: self
+                            .called_function(instr)
+                            .and_then(|callee| self.function_name(callee)),
+                    }
+                })
+                .collect();
+
+            let depth = match code_section.section_type {
+                CodeType::Function => 1,
+                _ => 0,
+            };
+
+            let lines = match analysis::decompile(&lift_instrs, depth, &self.deadline) {
+                Ok(lines) => lines,
+                Err(reason) => {
+                    return super::write_stop_note(stream, "// ", reason, done, total, false);
+                }
+            };
+
+            writeln!(stream)?;
+
+            match code_section.section_type {
+                CodeType::Function => {
+                    let name = self.function_name(section_index).unwrap_or_default();
+
+                    writeln!(stream, "function {} {{", name)?;
+
+                    for line in lines {
+                        writeln!(stream, "{}", line)?;
+                    }
+
+                    writeln!(stream, "}}")?;
+                }
+                _ => {
+                    writeln!(stream, "// {}", self.section_name(code_section)?)?;
+
+                    for line in lines {
+                        writeln!(stream, "{}", line)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the file as KASM source that can be assembled and linked back into it
+    fn write_kasm(&self, config: &CLIConfig, kasm_path: &Path) -> DumpResult {
+        let program = self.kasm_program()?;
+
+        let mut out = BufWriter::new(File::create(kasm_path)?);
+
+        super::kasm::write_kasm(
+            &mut out,
+            &super::report_title(config),
+            &program,
+            self.encoding,
+        )?;
+
+        out.flush()?;
+
+        Ok(())
+    }
+
+    /// Gathers what a KASM listing needs, which is every argument as a named value and every code
+    /// section as a function
+    ///
+    /// MAIN becomes _start and INIT becomes _init, since those are the functions KLinker puts in
+    /// those sections. Calls to the start of a function in the file name it, so that the linker
+    /// can place it anywhere.
+    fn kasm_program(&self) -> DynResult<KasmProgram> {
+        let mut values = Vec::new();
+        let mut value_names = HashMap::new();
+        let mut taken = BTreeSet::new();
+
+        for (arg_index, value) in self.index.arguments() {
+            let name = kasm::unique_name(format!("arg_{:x}", arg_index), &mut taken);
+
+            value_names.insert(arg_index, name.clone());
+            values.push(KasmValue {
+                name,
+                value: value.clone(),
+            });
+        }
+
+        let function_names: Vec<String> = self
+            .ksmfile
+            .code_sections()
+            .enumerate()
+            .map(|(section_index, code_section)| {
+                let name = match code_section.section_type {
+                    CodeType::Main => String::from("_start"),
+                    CodeType::Initialization => String::from("_init"),
+                    CodeType::Function => kasm::kasm_identifier(
+                        &self.function_name(section_index).unwrap_or_default(),
+                    ),
+                };
+
+                kasm::unique_name(name, &mut taken)
+            })
+            .collect();
+
+        let mut functions = Vec::new();
+
+        for (section_index, code_section) in self.ksmfile.code_sections().enumerate() {
+            let instructions: Vec<&Instr> = code_section.instructions().collect();
+            let locations = self.index.locations(section_index);
+
+            // Label resets are left out, so branch destinations are counted without them
+            let kept: Vec<usize> = (0..instructions.len())
+                .filter(|i| instr_opcode(instructions[*i]) != Opcode::Lbrt)
+                .collect();
+
+            let mut instrs = Vec::new();
+
+            for &i in &kept {
+                let instr = instructions[i];
+                let opcode = instr_opcode(instr);
+
+                let ops = instr_operands(instr);
+
+                let operands = ops
+                    .iter()
+                    .enumerate()
+                    .map(|(n, op)| {
+                        let value = self.index.value(*op);
+
+                        let target = value
+                            .filter(|_| n == 0 && analysis::is_branch(opcode))
+                            .and_then(|value| Self::branch_target_index(locations, i, value))
+                            .and_then(|target| kept.binary_search(&target).ok());
+
+                        if let Some(target) = target {
+                            return KasmOperand::Target(target);
+                        }
+
+                        if n == 0 {
+                            if let Some(callee) = self.called_function(instr) {
+                                return KasmOperand::Symbol(function_names[callee].clone());
+                            }
+                        }
+
+                        KasmOperand::Value(
+                            value_names
+                                .get(&usize::from(*op))
+                                .cloned()
+                                .unwrap_or_else(|| format!("arg_{:x}", usize::from(*op))),
+                        )
+                    })
+                    .collect();
+
+                instrs.push(KasmInstr { opcode, operands });
+            }
+
+            functions.push(KasmFunction {
+                name: function_names[section_index].clone(),
+                global: code_section.section_type != CodeType::Function,
+                instrs,
+            });
+        }
+
+        Ok(KasmProgram {
+            externs: Vec::new(),
+            values,
+            functions,
+        })
+    }
+
+    /// The position of the code section a call-like instruction goes to, but only when it goes
+    /// to the start of it, since a label in the middle of a function can't be named by a symbol
+    fn called_function(&self, instr: &Instr) -> Option<usize> {
+        let target = self.call_destination(instr)?;
+        let callee = self.resolve_call(target)?;
+
+        let entry = self
+            .index
+            .locations(callee)
+            .iter()
+            .find(|location| !location.is_label_reset)?;
+
+        let is_entry = entry.label == Self::long_label(target)
+            || self.function_name(callee).as_deref() == Some(target)
+            || self
+                .ksmfile
+                .code_sections()
+                .nth(callee)
+                .and_then(|code_section| self.section_name(code_section).ok())
+                == Some(target);
+
+        is_entry.then_some(callee)
+    }
+
+    fn write_linemap(&self, config: &CLIConfig, linemap_path: &Path) -> DumpResult {
+        let section_names = self.section_names()?;
+
+        let mut out = BufWriter::new(File::create(linemap_path)?);
+
+        super::linemap::write_ksm_linemap(
+            &mut out,
+            &super::report_title(config),
+            &self.index,
+            &section_names,
+            config.pretty,
+        )?;
+
+        out.flush()?;
+
+        Ok(())
+    }
+
+    /// Writes every code section using one of the --style formatters
+    fn dump_styled(
+        &self,
+        stream: &mut dyn WriteColor,
+        config: &CLIConfig,
+        style: &dyn DisasmStyle,
+    ) -> DumpResult {
+        style.write_file_header(stream, &super::report_title(config), "ksm")?;
+
+        for (section_index, code_section) in self.ordered_sections(config.order) {
+            let name = self.section_name(code_section)?;
+            let locations = self.index.locations(section_index);
+            let section_start = locations.first().map(|location| location.addr).unwrap_or(0);
+            let width = self.index.index_width();
+
+            let mut lines = Vec::new();
+
+            for (i, (instr, location)) in code_section.instructions().zip(locations).enumerate() {
+                let opcode = instr_opcode(instr);
+
+                let operand_indices = instr_operands(instr);
+
+                let arg_indices: Vec<usize> =
+                    operand_indices.iter().map(|op| usize::from(*op)).collect();
+                let bytes = asm::encode(opcode, &arg_indices, width);
+
+                let operands = operand_indices
+                    .iter()
+                    .map(|op| match self.value_from_operand(*op) {
+                        Some(value) => super::kosvalue_quoted(value, self.encoding),
+                        None => format!("<invalid {:x}>", usize::from(*op)),
+                    })
+                    .collect();
+
+                let first_value = operand_indices
+                    .first()
+                    .and_then(|op| self.value_from_operand(*op));
+
+                let target = FlowInstr::new(opcode, first_value)
+                    .distance
+                    .and_then(|distance| i.checked_add_signed(distance))
+                    .and_then(|target| locations.get(target))
+                    .map(|target| format!("{}+0x{:x}", name, target.addr - section_start));
+
+                lines.push(DisasmLine {
+                    addr: location.addr,
+                    bytes,
+                    mnemonic: opcode.into(),
+                    operand_indices: arg_indices,
+                    operands,
+                    target,
+                });
+            }
+
+            style.write_section(
+                stream,
+                &DisasmSection {
+                    name: name.to_string(),
+                    lines,
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes which code sections call, or take delegates of, which others as a DOT digraph
+    ///
+    /// Targets are matched by the labels of each section's instructions, the label its first
+    /// label reset gives it, and its name. Anything else, like a built in function, becomes a
+    /// node of its own.
+    ///
+    /// Trigger bodies and anonymous functions are shown with the names they were given.
+    fn dump_callgraph_dot(&self, stream: &mut dyn WriteColor, config: &CLIConfig) -> DumpResult {
+        let section_names = self.section_names()?;
+        let section_names: Vec<&str> = section_names
+            .iter()
+            .enumerate()
+            .map(
+                |(section_index, name)| match self.anonymous_names.get(&section_index) {
+                    Some(anonymous_name) => anonymous_name.as_str(),
+                    None => name,
+                },
+            )
+            .collect();
+        let mut graph = CallGraph::default();
+
+        for (section_index, code_section) in self.ksmfile.code_sections().enumerate() {
+            let caller = section_names[section_index];
+            graph.node(caller, false);
+
+            for instr in code_section.instructions() {
+                let Some(target) = self.call_destination(instr) else {
+                    continue;
+                };
+
+                match self.resolve_call(target) {
+                    Some(callee) => graph.add_call(caller, section_names[callee], false),
+                    None => graph.add_call(caller, target, true),
+                }
+            }
+        }
+
+        super::dot::write_callgraph(stream, &super::report_title(config), &graph)
+    }
+
+    /// Writes the control flow graph of every code section as DOT
+    fn dump_cfg_dot(&self, stream: &mut dyn WriteColor) -> DumpResult {
+        let total = self.ksmfile.code_sections().count();
+
+        for (section_index, code_section) in self.ksmfile.code_sections().enumerate() {
+            if let Some(reason) = self.deadline.check() {
+                return super::write_stop_note(stream, "// ", reason, section_index, total, false);
+            }
+
+            let name = self.section_name(code_section)?;
+            let locations = self.index.locations(section_index);
+
+            let mut flow_instrs = Vec::new();
+            let mut lines = Vec::new();
+
+            for (i, (instr, location)) in code_section.instructions().zip(locations).enumerate() {
+                if let Some(reason) = self.deadline.check_at(i) {
+                    return super::write_stop_note(
+                        stream,
+                        "// ",
+                        reason,
+                        section_index,
+                        total,
+                        false,
+                    );
+                }
+
+                let opcode = instr_opcode(instr);
+                let mnemonic: &str = opcode.into();
+
+                let operands: Vec<&KOSValue> = instr_operands(instr)
+                    .into_iter()
+                    .filter_map(|op| self.value_from_operand(op))
+                    .collect();
+
+                let operand_strs: Vec<String> = operands
+                    .iter()
+                    .map(|value| super::kosvalue_str(value, self.encoding))
+                    .collect();
+
+                flow_instrs.push(FlowInstr::new(opcode, operands.first().copied()));
+                lines.push(format!(
+                    "{} {:<5} {}",
+                    location.label,
+                    mnemonic,
+                    operand_strs.join(", ")
+                ));
+            }
+
+            let blocks = analysis::build_cfg(&flow_instrs);
+
+            super::dot::write_cfg(stream, name, &blocks, &lines)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes which toolchain most likely produced this file, and the patterns that were found
+    fn dump_attribution(&self, stream: &mut dyn WriteColor) -> DumpResult {
+        let attribution = analysis::attribute_ksm(&self.index);
+
+        match &attribution.toolchain {
+            Some(toolchain) => writeln!(
+                stream,
+                "\tAttributed to {} (confidence {:.2})",
+                toolchain, attribution.confidence
+            )?,
+            None => writeln!(stream, "\tNo toolchain patterns found")?,
+        }
+
+        for evidence in &attribution.evidence {
+            writeln!(
+                stream,
+                "\t  {:.2} {}: {}",
+                evidence.weight, evidence.toolchain, evidence.pattern
+            )?;
+        }
+
         Ok(())
     }
 
@@ -96,7 +967,350 @@ impl KSMFileDebug {
         }
     }
 
-    fn dump_debug(&self, stream: &mut StandardStream, regular_color: &ColorSpec) -> DumpResult {
+    /// Lists every code section alphabetically, along with the label it begins at
+    fn dump_symbol_index(
+        &self,
+        stream: &mut dyn WriteColor,
+        config: &CLIConfig,
+        regular_color: &ColorSpec,
+        label_color: &ColorSpec,
+    ) -> DumpResult {
+        let mut entries = Vec::new();
+
+        for (section_index, code_section) in self.ksmfile.code_sections().enumerate() {
+            let name = self.section_name(code_section)?;
+
+            let first_label = self
+                .index
+                .locations(section_index)
+                .iter()
+                .find(|location| !location.is_label_reset)
+                .map(|location| Self::display_label(config, location));
+
+            if let Some(first_label) = first_label {
+                entries.push((name, first_label));
+            }
+        }
+
+        entries.sort_by_key(|(name, _)| name.to_lowercase());
+
+        stream.set_color(regular_color)?;
+        writeln!(stream, "\nSymbol index:")?;
+
+        for (name, label) in entries {
+            write!(stream, "  {:<32} ", name)?;
+            stream.set_color(label_color)?;
+            writeln!(stream, "{}", label)?;
+            stream.set_color(regular_color)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether the instructions begin with enough unknown opcodes in a row that the instruction
+    /// stream must have lost its place
+    fn starts_bogus_run(instructions: &[&Instr]) -> bool {
+        instructions.len() >= BOGUS_RUN_LIMIT
+            && instructions[..BOGUS_RUN_LIMIT]
+                .iter()
+                .all(|instr| instr_opcode(instr) == Opcode::Bogus)
+    }
+
+    /// Explains why a code section stopped being disassembled, and what probably caused it
+    fn dump_desync_note(&self, stream: &mut dyn WriteColor, label: &str) -> DumpResult {
+        let width = self.index.index_width();
+
+        writeln!(
+            stream,
+            "  ; stopped at {} after {} unknown opcodes in a row, the instruction stream is out of sync, resuming at the next section marker",
+            label, BOGUS_RUN_LIMIT
+        )?;
+        writeln!(
+            stream,
+            "  ; likely causes: the index width of {} byte(s) in the argument section header is wrong, or the section's offset is corrupted",
+            width
+        )?;
+
+        let payload = match self.raw_contents.map(fio::ksm_payload) {
+            Some(Ok(payload)) => payload,
+            _ => return Ok(()),
+        };
+
+        let widths = layout::clean_alternate_widths(&payload, width);
+
+        if widths.is_empty() {
+            writeln!(
+                stream,
+                "  ; no other index width decodes the file cleanly, so a corrupted offset is more likely"
+            )?;
+        }
+
+        for width in widths {
+            writeln!(
+                stream,
+                "  ; decoding with an index width of {} byte(s) finds no unknown opcodes",
+                width
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Shows the bytes at the start of the decompressed file, which are the magic and the header of
+    /// the argument section, so that differences between kOS releases can be seen without a hex editor
+    ///
+    /// After them come the sizes and widths that the rest of the file is laid out with.
+    fn dump_ksm_header(&self, stream: &mut dyn WriteColor, config: &CLIConfig) -> DumpResult {
+        writeln!(stream, "\nFile header:")?;
+
+        match self.raw_contents {
+            Some(raw_contents) => self.dump_ksm_header_bytes(stream, config, raw_contents)?,
+            None => writeln!(stream, "\tThe raw file is not available")?,
+        }
+
+        writeln!(
+            stream,
+            "\tArgument index width: {} byte(s)",
+            self.index.index_width()
+        )?;
+
+        let count_of = |section_type: CodeType| {
+            self.ksmfile
+                .code_sections()
+                .filter(|code_section| code_section.section_type == section_type)
+                .count()
+        };
+
+        writeln!(
+            stream,
+            "\tCode sections: {} ({} main, {} init, {} function)",
+            self.ksmfile.code_sections().len(),
+            count_of(CodeType::Main),
+            count_of(CodeType::Initialization),
+            count_of(CodeType::Function)
+        )?;
+
+        writeln!(
+            stream,
+            "\tDebug range width: {} byte(s)",
+            self.ksmfile.debug_section.range_size() as usize
+        )?;
+
+        Ok(())
+    }
+
+    fn dump_ksm_header_bytes(
+        &self,
+        stream: &mut dyn WriteColor,
+        config: &CLIConfig,
+        raw_contents: &[u8],
+    ) -> DumpResult {
+        let payload = fio::ksm_payload(raw_contents)?;
+
+        let text = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .map(|&byte| {
+                    if byte.is_ascii_graphic() {
+                        (byte as char).to_string()
+                    } else {
+                        format!("\\x{:02x}", byte)
+                    }
+                })
+                .collect::<String>()
+        };
+
+        let magic = &payload[..payload.len().min(fio::KSM_MAGIC.len())];
+
+        write!(
+            stream,
+            "\tMagic: {} (\"{}\")",
+            fio::hex_bytes(magic),
+            text(magic)
+        )?;
+
+        if magic == fio::KSM_MAGIC {
+            writeln!(stream)?;
+        } else {
+            writeln!(stream, ", expected {}", fio::hex_bytes(&fio::KSM_MAGIC))?;
+        }
+
+        if let Some(&version) = magic.get(1) {
+            writeln!(
+                stream,
+                "\tFormat version: {} (this version of kDump reads version {})",
+                version,
+                fio::KSM_MAGIC[1]
+            )?;
+        }
+
+        let marker_start = fio::KSM_MAGIC.len();
+        let marker = payload
+            .get(marker_start..marker_start + 2)
+            .unwrap_or_default();
+
+        writeln!(
+            stream,
+            "\tArgument section marker: {} (\"{}\")",
+            fio::hex_bytes(marker),
+            text(marker)
+        )?;
+
+        if let Some(width) = payload.get(marker_start + 2) {
+            writeln!(stream, "\tArgument index width byte: {:02x}", width)?;
+        }
+
+        if fio::is_gzip(raw_contents) {
+            writeln!(
+                stream,
+                "\tCompressed size: {}",
+                super::format_size(raw_contents.len(), config.human_readable)
+            )?;
+        } else {
+            writeln!(stream, "\tCompressed size: not compressed")?;
+        }
+
+        writeln!(
+            stream,
+            "\tDecompressed size: {}",
+            super::format_size(payload.len(), config.human_readable)
+        )?;
+
+        Ok(())
+    }
+
+    /// Cross-checks each code section against its bytes in the decompressed file, which are
+    /// stepped over with the opcode table the way a reader would
+    fn dump_verification(&self, stream: &mut dyn WriteColor) -> DumpResult {
+        let payload = self.raw_contents.map(fio::ksm_payload).transpose()?;
+        let checks = analysis::check_ksm_sections(&self.index, payload.as_deref());
+
+        super::write_verification(stream, &checks, &self.section_names()?)
+    }
+
+    /// Estimates what adding a number of average-sized arguments would do to the
+    /// argument index width, and how much the code sections would grow because of it
+    fn dump_growth_simulation(
+        &self,
+        stream: &mut dyn WriteColor,
+        config: &CLIConfig,
+        additional: usize,
+    ) -> DumpResult {
+        let size = |bytes| super::format_size(bytes, config.human_readable);
+        let arg_section = &self.ksmfile.arg_section;
+        let current_width = arg_section.num_index_bytes() as usize;
+
+        let end = self.index.arguments_end();
+        let last_index = self
+            .index
+            .arguments()
+            .last()
+            .map_or(end, |(index, _)| index);
+        let num_args = self.index.arguments().count();
+        let args_size: usize = self
+            .index
+            .arguments()
+            .map(|(_, value)| value.size_bytes())
+            .sum();
+
+        let average_size = args_size.checked_div(num_args).unwrap_or(1).max(1);
+
+        let projected_last_index = if additional == 0 {
+            last_index
+        } else {
+            end + (additional - 1) * average_size
+        };
+        let projected_end = end + additional * average_size;
+
+        let projected_width = Self::index_width_for(projected_last_index);
+
+        let mut num_operands = 0;
+
+        for code_section in self.ksmfile.code_sections() {
+            for instr in code_section.instructions() {
+                num_operands += instr_operand_count(instr);
+            }
+        }
+
+        let growth = num_operands * projected_width.saturating_sub(current_width);
+
+        // How many more average arguments fit before the current width overflows
+        let max_index = 1usize
+            .checked_shl(8 * current_width as u32)
+            .map(|limit| limit - 1)
+            .unwrap_or(usize::MAX);
+        let headroom = if end > max_index {
+            0
+        } else {
+            (max_index - end) / average_size + 1
+        };
+
+        writeln!(
+            stream,
+            "\nArgument growth simulation (+{} arguments):",
+            additional
+        )?;
+        writeln!(stream, "  {:<32}{}", "Current arguments", num_args)?;
+        writeln!(stream, "  {:<32}{}", "Current argument section", size(end))?;
+        writeln!(
+            stream,
+            "  {:<32}{}",
+            "Average argument size",
+            size(average_size)
+        )?;
+        writeln!(stream, "  {:<32}{}", "Current index width", current_width)?;
+        writeln!(
+            stream,
+            "  {:<32}{}",
+            "Arguments until width increase", headroom
+        )?;
+        writeln!(
+            stream,
+            "  {:<32}{}",
+            "Projected argument section",
+            size(projected_end)
+        )?;
+        writeln!(
+            stream,
+            "  {:<32}{}",
+            "Projected index width", projected_width
+        )?;
+        writeln!(
+            stream,
+            "  {:<32}{}",
+            "Operands in code sections", num_operands
+        )?;
+        writeln!(stream, "  {:<32}{}", "Code section growth", size(growth))?;
+
+        if projected_width > current_width {
+            writeln!(
+                stream,
+                "\n  Adding {} arguments would force {}-byte argument indices.",
+                additional, projected_width
+            )?;
+        } else {
+            writeln!(
+                stream,
+                "\n  Adding {} arguments would not change the argument index width.",
+                additional
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// The number of bytes needed to encode an argument index
+    fn index_width_for(index: usize) -> usize {
+        let mut width = 1;
+
+        while width < 4 && index >= 1usize << (8 * width) {
+            width += 1;
+        }
+
+        width
+    }
+
+    fn dump_debug(&self, stream: &mut dyn WriteColor, regular_color: &ColorSpec) -> DumpResult {
         stream.set_color(regular_color)?;
 
         writeln!(stream, "\nDebug section:")?;
@@ -140,7 +1354,7 @@ impl KSMFileDebug {
     #[allow(clippy::too_many_arguments)]
     fn dump_code_by_symbol(
         &self,
-        stream: &mut StandardStream,
+        stream: &mut dyn WriteColor,
         config: &CLIConfig,
         symbol: &String,
         regular_color: &ColorSpec,
@@ -149,11 +1363,37 @@ impl KSMFileDebug {
         mnemonic_color: &ColorSpec,
         variable_color: &ColorSpec,
     ) -> DumpResult {
-        let mut index = 1;
-        let mut addr = 0;
         let mut found_section = None;
+        let mut window = self.address_window(config)?;
+
+        // A function that begins partway through a section is shown on its own, up to wherever
+        // the next function begins
+        for (section_index, code_section) in self.ksmfile.code_sections().enumerate() {
+            let starts = analysis::find_function_starts(&self.first_operands(code_section));
+
+            if let Some(position) = starts.iter().position(|start| start.name == *symbol) {
+                let locations = self.index.locations(section_index);
+                let offset_of = |start: &analysis::FunctionStart| {
+                    self.index.file_offset(locations[start.index].addr)
+                };
+
+                let start = offset_of(&starts[position]);
+                let end = starts
+                    .get(position + 1)
+                    .map(offset_of)
+                    .unwrap_or(usize::MAX);
+
+                window = window.start.max(start)..window.end.min(end);
+                found_section = Some((section_index, code_section));
+                break;
+            }
+        }
+
+        for (section_index, code_section) in self.ksmfile.code_sections().enumerate() {
+            if found_section.is_some() {
+                break;
+            }
 
-        for code_section in self.ksmfile.code_sections() {
             let matches = match code_section.section_type {
                 kerbalobjects::ksm::sections::CodeType::Main => symbol.eq_ignore_ascii_case("main"),
                 kerbalobjects::ksm::sections::CodeType::Initialization => {
@@ -163,80 +1403,62 @@ impl KSMFileDebug {
             };
 
             if matches {
-                found_section = Some(code_section);
+                found_section = Some((section_index, code_section));
                 break;
             } else {
                 for (in_func_index, instr) in code_section.instructions().enumerate() {
-                    let matches = match instr {
-                        Instr::ZeroOp(_) => false,
-                        Instr::OneOp(_, op1) => {
-                            let val1 = self.value_from_operand(*op1).ok_or(format!(
-                                "Instruction number {} references invalid argument index: {:x}",
-                                in_func_index,
-                                usize::from(*op1)
-                            ))?;
-
-                            match val1 {
-                                KOSValue::String(s) | KOSValue::StringValue(s) => s == symbol,
-                                _ => false,
-                            }
-                        }
-                        Instr::TwoOp(_, op1, op2) => {
-                            let val1 = self.value_from_operand(*op1).ok_or(format!(
-                                "Instruction number {} references invalid argument index: {:x}",
-                                in_func_index,
-                                usize::from(*op1)
-                            ))?;
-                            let val2 = self.value_from_operand(*op2).ok_or(format!(
-                                "Instruction number {} references invalid argument index: {:x}",
-                                in_func_index,
-                                usize::from(*op2)
-                            ))?;
-
-                            let match1 = match val1 {
-                                KOSValue::String(s) | KOSValue::StringValue(s) => s == symbol,
-                                _ => false,
-                            };
-                            let match2 = match val2 {
-                                KOSValue::String(s) | KOSValue::StringValue(s) => s == symbol,
-                                _ => false,
-                            };
-
-                            match1 || match2
-                        }
-                    };
+                    let mut matches = false;
+
+                    for op in instr_operands(instr) {
+                        let value = self.value_from_operand(op).ok_or(format!(
+                            "Instruction number {} references invalid argument index: {:x}",
+                            in_func_index,
+                            usize::from(op)
+                        ))?;
+
+                        matches |= matches!(
+                            value,
+                            KOSValue::String(s) | KOSValue::StringValue(s) if s == symbol
+                        );
+                    }
 
                     if matches {
-                        found_section = Some(code_section);
+                        found_section = Some((section_index, code_section));
                         break;
                     }
                 }
             }
 
-            index += code_section.instructions().len() as i32;
-
-            addr += 2; // Offsets for the header bytes
-            for instr in code_section.instructions() {
-                addr += self.instr_size(instr);
+            if found_section.is_some() {
+                break;
             }
         }
 
+        let first_operand_width = self.first_operand_width(config);
+        let source = Self::read_source(config)?;
+
         match found_section {
-            Some(code_section) => {
-                self.dump_code_section(
+            Some((section_index, code_section)) => {
+                let stopped = self.dump_code_section(
                     stream,
+                    config,
+                    section_index,
                     code_section,
-                    index,
-                    addr,
                     regular_color,
                     line_color,
                     label_color,
                     mnemonic_color,
                     variable_color,
-                    config.line_numbers,
-                    !config.show_no_labels,
-                    !config.show_no_raw_instr,
+                    first_operand_width,
+                    &window,
+                    source.as_deref(),
                 )?;
+
+                if let Some(reason) = stopped {
+                    stream.set_color(regular_color)?;
+
+                    return super::write_stop_note(stream, "", reason, 0, 1, true);
+                }
             }
             None => {
                 writeln!(stream, "\nNo section found with that symbol.")?;
@@ -249,7 +1471,7 @@ impl KSMFileDebug {
     #[allow(clippy::too_many_arguments)]
     fn dump_code_sections(
         &self,
-        stream: &mut StandardStream,
+        stream: &mut dyn WriteColor,
         config: &CLIConfig,
         regular_color: &ColorSpec,
         line_color: &ColorSpec,
@@ -257,34 +1479,75 @@ impl KSMFileDebug {
         mnemonic_color: &ColorSpec,
         variable_color: &ColorSpec,
     ) -> DumpResult {
-        let mut index = 1;
-        let mut addr = 0;
+        let first_operand_width = self.first_operand_width(config);
+        let window = self.address_window(config)?;
+        let source = Self::read_source(config)?;
+        let mut sections = self.ordered_sections(config.order);
+
+        if let Some(selector) = config.section {
+            // Functions are numbered in file order, whatever order they are shown in
+            let function_sections: Vec<usize> = self
+                .ksmfile
+                .code_sections()
+                .enumerate()
+                .filter(|(_, code_section)| code_section.section_type == CodeType::Function)
+                .map(|(section_index, _)| section_index)
+                .collect();
+
+            sections.retain(|(section_index, code_section)| match selector {
+                SectionSelector::Main => code_section.section_type == CodeType::Main,
+                SectionSelector::Init => code_section.section_type == CodeType::Initialization,
+                SectionSelector::Function(n) => function_sections.get(n) == Some(section_index),
+            });
+
+            if sections.is_empty() {
+                return Err(format!(
+                    "The file has no {} code section",
+                    match selector {
+                        SectionSelector::Main => String::from("main"),
+                        SectionSelector::Init => String::from("init"),
+                        SectionSelector::Function(n) => format!("function number {}", n),
+                    }
+                )
+                .into());
+            }
+        }
 
-        for code_section in self.ksmfile.code_sections() {
-            if code_section.instructions().len() != 0 {
-                let (new_index, new_addr) = self.dump_code_section(
+        let total = sections.len();
+
+        for (done, (section_index, code_section)) in sections.into_iter().enumerate() {
+            if let Some(reason) = self.deadline.check() {
+                stream.set_color(regular_color)?;
+
+                return super::write_stop_note(stream, "", reason, done, total, false);
+            }
+
+            let in_window = self
+                .index
+                .locations(section_index)
+                .iter()
+                .any(|location| window.contains(&self.index.file_offset(location.addr)));
+
+            if in_window {
+                let stopped = self.dump_code_section(
                     stream,
+                    config,
+                    section_index,
                     code_section,
-                    index,
-                    addr,
                     regular_color,
                     line_color,
                     label_color,
                     mnemonic_color,
                     variable_color,
-                    config.line_numbers,
-                    !config.show_no_labels,
-                    !config.show_no_raw_instr,
+                    first_operand_width,
+                    &window,
+                    source.as_deref(),
                 )?;
 
-                index = new_index;
-                addr = new_addr;
-            } else {
-                index += code_section.instructions().len() as i32;
+                if let Some(reason) = stopped {
+                    stream.set_color(regular_color)?;
 
-                addr += 2; // Offsets for the header bytes
-                for instr in code_section.instructions() {
-                    addr += self.instr_size(instr);
+                    return super::write_stop_note(stream, "", reason, done, total, true);
                 }
             }
         }
@@ -292,72 +1555,344 @@ impl KSMFileDebug {
         Ok(())
     }
 
+    /// Writes a line under a section's name with its type, size, instruction count, range of
+    /// labels, and how many source lines it was compiled from, so that large dumps are easier to
+    /// find your way around in
+    fn dump_section_summary(
+        &self,
+        stream: &mut dyn WriteColor,
+        config: &CLIConfig,
+        code_section: &CodeSection,
+        locations: &[InstrLocation],
+    ) -> DumpResult {
+        let section_type = match code_section.section_type {
+            CodeType::Main => "main",
+            CodeType::Initialization => "init",
+            CodeType::Function => "function",
+        };
+
+        // Every code section begins with a two byte marker
+        let size = 2 + locations
+            .iter()
+            .map(|location| location.size)
+            .sum::<usize>();
+
+        write!(
+            stream,
+            "  ; {}, {}, {} instr",
+            section_type,
+            super::format_size(size, config.human_readable),
+            locations.len()
+        )?;
+
+        let mut labelled = locations.iter().filter(|location| !location.is_label_reset);
+
+        if let Some(first) = labelled.next() {
+            let last = labelled.next_back().unwrap_or(first);
+
+            write!(
+                stream,
+                ", {} - {}",
+                Self::display_label(config, first),
+                Self::display_label(config, last)
+            )?;
+        }
+
+        let source_lines: HashSet<isize> = locations
+            .iter()
+            .filter_map(|location| self.find_entry_with_addr(location.addr))
+            .map(|(entry, _)| entry.line_number)
+            .collect();
+
+        writeln!(stream, ", {} source line(s)", source_lines.len())?;
+
+        Ok(())
+    }
+
+    /// How --show-arg-indices shows the argument index of an operand, like [idx 0x0cc5]
+    fn arg_index_tag(&self, op: ArgIndex) -> String {
+        format!(
+            "[idx 0x{:0>width$x}]",
+            usize::from(op),
+            width = self.index.index_width() * 2
+        )
+    }
+
+    /// Writes the argument index of an operand before its value with --show-arg-indices=each
+    fn write_arg_index(
+        &self,
+        stream: &mut dyn WriteColor,
+        config: &CLIConfig,
+        op: ArgIndex,
+    ) -> DumpResult {
+        if config.show_arg_indices == Some(ArgIndexPlacement::Each) {
+            write!(stream, "{} ", self.arg_index_tag(op))?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads the lines of the script given with --source
+    fn read_source(config: &CLIConfig) -> DynResult<Option<Vec<String>>> {
+        match &config.source {
+            Some(path) => {
+                let source = std::fs::read_to_string(path)
+                    .map_err(|e| format!("Failed to read source file {}: {}", path.display(), e))?;
+
+                Ok(Some(source.lines().map(String::from).collect()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// The byte offsets in the decompressed file between --start-address and --stop-address
+    fn address_window(&self, config: &CLIConfig) -> DynResult<Range<usize>> {
+        let start = match &config.start_address {
+            Some(bound) => self.bound_offset(bound)?,
+            None => 0,
+        };
+        let stop = match &config.stop_address {
+            Some(bound) => self.bound_offset(bound)?,
+            None => usize::MAX,
+        };
+
+        Ok(start..stop)
+    }
+
+    fn bound_offset(&self, bound: &AddressBound) -> DynResult<usize> {
+        match bound {
+            AddressBound::Offset(offset) => Ok(*offset),
+            AddressBound::Label(label) => (0..self.ksmfile.code_sections().len())
+                .flat_map(|section_index| self.index.locations(section_index))
+                .find(|location| !location.is_label_reset && location.label == *label)
+                .map(|location| self.index.file_offset(location.addr))
+                .ok_or_else(|| format!("No instruction has the label {}", label).into()),
+        }
+    }
+
+    /// Disassembles one code section, returning why it stopped partway through if it did
     #[allow(clippy::too_many_arguments)]
     fn dump_code_section(
         &self,
-        stream: &mut StandardStream,
+        stream: &mut dyn WriteColor,
+        config: &CLIConfig,
+        section_index: usize,
         code_section: &CodeSection,
-        start_index: i32,
-        start_addr: usize,
         regular_color: &ColorSpec,
         line_color: &ColorSpec,
         label_color: &ColorSpec,
         mnemonic_color: &ColorSpec,
         variable_color: &ColorSpec,
-        show_line_numbers: bool,
-        show_labels: bool,
-        show_raw_instr: bool,
-    ) -> DynResult<(i32, usize)> {
-        let section_type = code_section.section_type;
-        let addr_width = self.ksmfile.arg_section.num_index_bytes() as u8 as usize;
-
-        let name = match section_type {
-            kerbalobjects::ksm::sections::CodeType::Main => "MAIN",
-            kerbalobjects::ksm::sections::CodeType::Initialization => "INIT",
-            kerbalobjects::ksm::sections::CodeType::Function => {
-                match code_section.instructions().next() {
-                    Some(&Instr::OneOp(opcode, op1)) => {
-                        if opcode == Opcode::Lbrt {
-                            let operand = self.value_from_operand(op1).ok_or(format!(
-                                "Instruction number {} references invalid argument index: {:x}",
-                                0,
-                                usize::from(op1)
-                            ))?;
-
-                            match operand {
-                                KOSValue::String(s) | KOSValue::StringValue(s) => {
-                                    // If this is a kOS-compiled function
-                                    if s.contains('`') {
-                                        s.split('`').next().unwrap()
-                                    } else {
-                                        s
-                                    }
-                                }
-                                _ => "FUNC",
-                            }
-                        } else {
-                            "FUNC"
-                        }
-                    }
-                    _ => "FUNC",
+        first_operand_width: usize,
+        window: &Range<usize>,
+        source: Option<&[String]>,
+    ) -> DynResult<Option<StopReason>> {
+        let show_line_numbers = config.line_numbers;
+        let show_labels = !config.show_no_labels;
+        let show_raw_instr = !config.show_no_raw_instr;
+
+        let locations = self.index.locations(section_index);
+        let instructions: Vec<&Instr> = code_section.instructions().collect();
+        let mut skip = 0;
+
+        if config.hide_boilerplate && code_section.section_type == CodeType::Main {
+            skip = analysis::detect_preamble(&instructions);
+        }
+
+        // Sections with nothing that matches --grep are left out entirely
+        let shown = match &config.grep {
+            Some(pattern) => {
+                let matches: Vec<bool> = instructions
+                    .iter()
+                    .map(|instr| self.grep_matches(pattern, instr))
+                    .collect();
+
+                if !matches.contains(&true) {
+                    return Ok(None);
                 }
+
+                Some(super::grep_context(
+                    &matches,
+                    config.before_context,
+                    config.after_context,
+                ))
+            }
+            None => None,
+        };
+        let first_shown = shown
+            .as_ref()
+            .and_then(|shown| shown.iter().position(|shown| *shown));
+
+        let name = match self.anonymous_names.get(&section_index) {
+            Some(anonymous_name) => {
+                format!("{} ({})", anonymous_name, self.section_name(code_section)?)
             }
+            None => self.section_name(code_section)?.to_string(),
         };
 
         stream.set_color(regular_color)?;
-        writeln!(stream, "\n{}:", name)?;
 
-        let mut label = String::from("@000001");
-        let mut index = start_index;
-        let mut addr = start_addr + 2;
+        let mut labelled = locations.iter().filter(|location| !location.is_label_reset);
+
+        match (config.renumber_from, labelled.next(), labelled.next_back()) {
+            // The real labels aren't shown anymore, so the header shows where the section really is
+            (Some(_), Some(first), last) => writeln!(
+                stream,
+                "\n{} (global {} - {}):",
+                name,
+                first.label,
+                last.unwrap_or(first).label
+            )?,
+            _ => writeln!(stream, "\n{}:", name)?,
+        }
+
+        self.dump_section_summary(stream, config, code_section, locations)?;
+
+        if skip > 0 {
+            writeln!(stream, "  ; standard kOS preamble ({} instr)", skip)?;
+        }
 
         let max_line_number = self.max_debug_line_number();
         let max_width = max_line_number.to_string().len();
 
-        for (in_func_index, instr) in code_section.instructions().enumerate() {
-            let instr_size = self.instr_size(instr);
+        // Branches can point forwards, so every label is worked out before any are printed
+        let labels: Vec<String> = locations
+            .iter()
+            .map(|location| Self::display_label(config, location))
+            .collect();
+
+        let ascii_art = config.ascii_art || !super::locale_is_utf8();
+
+        // Unknown opcodes are shown as the byte they were, which only the raw file has
+        let payload = if instructions
+            .iter()
+            .any(|instr| instr_opcode(instr) == Opcode::Bogus)
+        {
+            self.raw_contents
+                .and_then(|raw_contents| fio::ksm_payload(raw_contents).ok())
+        } else {
+            None
+        };
+
+        let first_operands = self.first_operands(code_section);
+
+        let stack_steps = if config.stack_effect {
+            Some(analysis::stack_depths(&first_operands))
+        } else {
+            None
+        };
+
+        // The function that begins the section is already named by its header
+        let function_starts: HashMap<usize, String> =
+            analysis::find_function_starts(&first_operands)
+                .into_iter()
+                .filter(|start| start.index > 0)
+                .map(|start| (start.index, start.name))
+                .collect();
+
+        // The source line that was last printed, so each one is only printed above the first
+        // instruction of the group that it compiled to
+        let mut last_source_line = None;
+
+        for (in_func_index, instr) in instructions.iter().copied().enumerate() {
+            if let Some(reason) = self.deadline.check_at(in_func_index) {
+                return Ok(Some(reason));
+            }
+
+            if !window.contains(&self.index.file_offset(locations[in_func_index].addr)) {
+                continue;
+            }
+
+            // Skip over instructions that were collapsed into a previous one
+            if skip > 0 {
+                skip -= 1;
+                continue;
+            }
+
+            // Past this point the rest of the section would only be pages of bogus
+            if Self::starts_bogus_run(&instructions[in_func_index..]) {
+                let label = Self::display_label(config, &locations[in_func_index]);
+
+                self.dump_desync_note(stream, &label)?;
+
+                return Ok(None);
+            }
+
+            if let Some(shown) = &shown {
+                if !shown[in_func_index] {
+                    continue;
+                }
+
+                // Like grep, groups of instructions that aren't next to each other are split up
+                if Some(in_func_index) != first_shown && !shown[in_func_index - 1] {
+                    writeln!(stream, "  --")?;
+                }
+            }
+
+            if let Some(name) = function_starts.get(&in_func_index) {
+                stream.set_color(regular_color)?;
+                writeln!(stream, "\n<{}>:", name)?;
+            }
+
+            // The label resets were already used to work out the labels, so nothing is lost
+            if config.hide_lbrt && instr_opcode(instr) == Opcode::Lbrt {
+                continue;
+            }
+
+            let repeats = if config.collapse_repeats {
+                // Repeats past the end of the address window aren't shown, so they can't be counted
+                let in_window = locations[in_func_index..]
+                    .iter()
+                    .take_while(|location| window.contains(&self.index.file_offset(location.addr)))
+                    .count();
+
+                Self::count_repeats(&instructions[in_func_index..in_func_index + in_window])
+            } else {
+                1
+            };
+
+            let location = &locations[in_func_index];
+            let addr = location.addr;
+            let instr_size = location.size;
+
+            if let Some(source) = source {
+                let line_number = self
+                    .find_entry_with_addr(addr)
+                    .map(|(entry, _)| entry.line_number);
+
+                if line_number.is_some() && line_number != last_source_line {
+                    last_source_line = line_number;
+
+                    let line_number = line_number.unwrap_or_default();
+                    let text = usize::try_from(line_number - 1)
+                        .ok()
+                        .and_then(|i| source.get(i));
+
+                    if let Some(text) = text {
+                        stream.set_color(line_color)?;
+                        writeln!(
+                            stream,
+                            "  ; {:>width$}: {}",
+                            line_number,
+                            text.trim_end(),
+                            width = max_width
+                        )?;
+                        stream.set_color(regular_color)?;
+                    }
+                }
+            }
 
-            if show_line_numbers {
+            if config.line_numbers_plain {
+                // Every instruction gets its own line number, so that grep and diff see it too
+                let line_number = self
+                    .find_entry_with_addr(addr)
+                    .map(|(entry, _)| entry.line_number.to_string())
+                    .unwrap_or_default();
+
+                stream.set_color(line_color)?;
+                write!(stream, "   {:>width$}  ", line_number, width = max_width)?;
+                stream.set_color(regular_color)?;
+            } else if show_line_numbers {
                 let debug_entry = self.find_entry_with_addr(addr);
 
                 match debug_entry {
@@ -373,14 +1908,9 @@ impl KSMFileDebug {
                         {
                             3
                         } else if addr == range_start {
-                            let next_instr_option =
-                                code_section.instructions().nth(index as usize + 1);
-
-                            match next_instr_option {
-                                Some(next_instr) => {
-                                    if addr + operand_length + self.instr_size(next_instr)
-                                        == range_end
-                                    {
+                            match locations.get(in_func_index + 1) {
+                                Some(next_location) => {
+                                    if addr + operand_length + next_location.size == range_end {
                                         5
                                     } else {
                                         0
@@ -404,13 +1934,19 @@ impl KSMFileDebug {
                             _ => String::new(),
                         };
 
-                        let art = match state {
-                            0 => " ╔═",
-                            1 => " ║ ",
-                            2 => "═╣ ",
-                            3 => "═══",
-                            4 => " ╚═",
-                            5 => "═╦═",
+                        let art = match (ascii_art, state) {
+                            (false, 0) => " ╔═",
+                            (false, 1) => " ║ ",
+                            (false, 2) => "═╣ ",
+                            (false, 3) => "═══",
+                            (false, 4) => " ╚═",
+                            (false, 5) => "═╦═",
+                            (true, 0) => " +-",
+                            (true, 1) => " | ",
+                            (true, 2) => "-+ ",
+                            (true, 3) => "---",
+                            (true, 4) => " +-",
+                            (true, 5) => "-+-",
                             _ => "   ",
                         };
 
@@ -426,87 +1962,44 @@ impl KSMFileDebug {
                 write!(stream, "  ")?;
             }
 
-            let instr_opcode = match instr {
-                Instr::ZeroOp(opcode) => *opcode,
-                Instr::OneOp(opcode, _) => *opcode,
-                Instr::TwoOp(opcode, _, _) => *opcode,
-            };
+            let instr_opcode = instr_opcode(instr);
 
-            let is_lbrt = instr_opcode == Opcode::Lbrt;
+            if config.show_addr {
+                write!(stream, "{:0>8x}  ", self.index.file_offset(addr))?;
+            }
 
             if show_labels {
                 stream.set_color(label_color)?;
 
-                if is_lbrt {
+                if location.is_label_reset {
                     write!(stream, "{:7} ", "")?;
                 } else {
-                    write!(stream, "{:<7} ", label)?;
+                    write!(stream, "{:<7} ", Self::display_label(config, location))?;
                 }
             }
 
             stream.set_color(regular_color)?;
 
-            if is_lbrt {
-                if let &Instr::OneOp(_, op) = instr {
-                    let arg = self.value_from_operand(op).ok_or(format!(
-                        "Instruction number {} references invalid argument index: {:x}",
-                        in_func_index,
-                        usize::from(op)
-                    ))?;
-
-                    if let KOSValue::String(s) = arg {
-                        label = s.clone();
-
-                        if label.starts_with('@') {
-                            // Makes @0013 @000013
-                            label.insert_str(1, "00");
-                        }
-                    }
-
-                    label.truncate(7);
-                }
-            }
-            // If it isn't a label reset
-            else {
-                index += 1;
-                label = format!("@{:>06}", index);
+            if show_raw_instr {
+                self.write_raw_instr(stream, instr)?;
             }
 
-            addr += instr_size;
+            if instr_opcode == Opcode::Bogus {
+                let offset = self.index.file_offset(addr);
+                let byte = payload
+                    .as_ref()
+                    .and_then(|payload| payload.get(offset))
+                    .copied();
 
-            if show_raw_instr {
-                match instr {
-                    Instr::ZeroOp(opcode) => {
-                        write!(
-                            stream,
-                            "{:x} {:<width$} {:<width$}",
-                            u8::from(*opcode),
-                            "",
-                            "",
-                            width = addr_width * 2
-                        )?;
-                    }
-                    Instr::OneOp(opcode, op1) => {
-                        write!(
-                            stream,
-                            "{:x} {:0>width$x} {:<width$}",
-                            u8::from(*opcode),
-                            usize::from(*op1),
-                            "",
-                            width = addr_width * 2
-                        )?;
-                    }
-                    Instr::TwoOp(opcode, op1, op2) => {
-                        write!(
-                            stream,
-                            "{:x} {:0>width$x} {:0>width$x}",
-                            u8::from(*opcode),
-                            usize::from(*op1),
-                            usize::from(*op2),
-                            width = addr_width * 2
-                        )?;
-                    }
-                }
+                super::write_unknown_byte(
+                    stream,
+                    mnemonic_color,
+                    regular_color,
+                    byte,
+                    Some(offset),
+                )?;
+
+                continue;
             }
 
             stream.set_color(mnemonic_color)?;
@@ -526,7 +2019,31 @@ impl KSMFileDebug {
                         usize::from(*op1)
                     ))?;
 
-                    super::write_kosvalue(stream, val1, regular_color, variable_color)?;
+                    let target = if analysis::is_branch(instr_opcode) {
+                        Self::branch_target(&labels, locations, in_func_index, val1)
+                    } else {
+                        None
+                    };
+
+                    self.write_arg_index(stream, config, *op1)?;
+
+                    match target {
+                        Some(target) => {
+                            write!(stream, "-> ")?;
+                            stream.set_color(label_color)?;
+                            write!(stream, "{}", target)?;
+                            stream.set_color(regular_color)?;
+                        }
+                        None => {
+                            super::write_kosvalue(
+                                stream,
+                                val1,
+                                self.encoding,
+                                regular_color,
+                                variable_color,
+                            )?;
+                        }
+                    }
                 }
                 Instr::TwoOp(_, op1, op2) => {
                     let val1 = self.value_from_operand(*op1).ok_or(format!(
@@ -540,28 +2057,235 @@ impl KSMFileDebug {
                         usize::from(*op2)
                     ))?;
 
-                    super::write_kosvalue(stream, val1, regular_color, variable_color)?;
+                    self.write_arg_index(stream, config, *op1)?;
+                    super::write_kosvalue(
+                        stream,
+                        val1,
+                        self.encoding,
+                        regular_color,
+                        variable_color,
+                    )?;
 
                     write!(stream, ",")?;
 
-                    super::write_kosvalue(stream, val2, regular_color, variable_color)?;
+                    // Line up the second operands of every instruction in the file
+                    let padding = first_operand_width
+                        .saturating_sub(super::kosvalue_width(val1, self.encoding));
+                    write!(stream, "{:padding$}", "", padding = padding)?;
+
+                    self.write_arg_index(stream, config, *op2)?;
+                    super::write_kosvalue(
+                        stream,
+                        val2,
+                        self.encoding,
+                        regular_color,
+                        variable_color,
+                    )?;
+                }
+            }
+
+            if config.show_arg_indices == Some(ArgIndexPlacement::First) {
+                if let Instr::OneOp(_, op1) | Instr::TwoOp(_, op1, _) = instr {
+                    write!(stream, " {}", self.arg_index_tag(*op1))?;
+                }
+            }
+
+            if let Some(note) = self.call_note(instr) {
+                write!(stream, "  {}", note)?;
+            }
+
+            if let Some(step) = stack_steps.as_ref().map(|steps| steps[in_func_index]) {
+                stream.set_color(line_color)?;
+                write!(stream, "  ; stack {:+}, depth {}", step.net, step.depth)?;
+
+                if step.underflow {
+                    write!(stream, " (underflow)")?;
                 }
+
+                stream.set_color(regular_color)?;
+            }
+
+            if repeats > 1 {
+                let last_label =
+                    Self::display_label(config, &locations[in_func_index + repeats - 1]);
+
+                let times = if ascii_art { "x" } else { "×" };
+
+                write!(stream, "  ; {}{} (through {})", times, repeats, last_label)?;
+
+                skip = repeats - 1;
             }
 
             writeln!(stream)?;
         }
 
-        Ok((index, addr))
+        Ok(None)
     }
 
-    fn instr_size(&self, instr: &Instr) -> usize {
-        let addr_width = self.ksmfile.arg_section.num_index_bytes() as usize;
+    /// Every code section along with its index in the file, in the order they should be disassembled in
+    ///
+    /// The logical order puts MAIN first and INIT second, since that is the order they run in, and
+    /// leaves the functions in file order after them.
+    fn ordered_sections(&self, order: SectionOrder) -> Vec<(usize, &'a CodeSection)> {
+        let mut sections: Vec<(usize, &'a CodeSection)> =
+            self.ksmfile.code_sections().enumerate().collect();
+
+        if order == SectionOrder::Logical {
+            sections.sort_by_key(|(_, code_section)| match code_section.section_type {
+                CodeType::Main => 0,
+                CodeType::Initialization => 1,
+                CodeType::Function => 2,
+            });
+        }
 
-        match instr {
-            Instr::ZeroOp(_) => 1,
-            Instr::OneOp(_, _) => 1 + addr_width,
-            Instr::TwoOp(_, _, _) => 1 + addr_width * 2,
+        sections
+    }
+
+    /// The display names of every code section, in file order
+    fn section_names(&self) -> DynResult<Vec<&'a str>> {
+        self.ksmfile
+            .code_sections()
+            .map(|code_section| self.section_name(code_section))
+            .collect()
+    }
+
+    /// Writes one JSON object per line for every argument, instruction, and debug entry
+    fn dump_ndjson(&self, stream: &mut dyn WriteColor) -> DumpResult {
+        let section_names = self.section_names()?;
+
+        model::visit_ksm(&self.index, &section_names, self.encoding, &mut |record| {
+            super::write_ndjson_line(stream, &record)
+        })
+    }
+
+    /// Gets the display name of a code section, which for functions is the name given by its first label reset
+    fn section_name(&self, code_section: &CodeSection) -> DynResult<&'a str> {
+        self.index.section_name(code_section)
+    }
+
+    /// The label an instruction should be shown with, which is either its real label, its
+    /// position in its function with --local-labels, or its position counted from --renumber-from
+    fn display_label(config: &CLIConfig, location: &InstrLocation) -> String {
+        if config.local_labels {
+            format!("fn+{:0>4}", location.local_index)
+        } else if let Some(origin) = config.renumber_from {
+            format!("@{:0>6}", origin + location.local_index)
+        } else {
+            location.label.clone()
+        }
+    }
+
+    /// The label of the instruction a branch lands on, given the labels of every instruction in
+    /// its section
+    fn branch_target(
+        labels: &[String],
+        locations: &[InstrLocation],
+        in_func_index: usize,
+        value: &KOSValue,
+    ) -> Option<String> {
+        let target = Self::branch_target_index(locations, in_func_index, value)?;
+
+        labels.get(target).cloned()
+    }
+
+    /// The position in its section of the instruction a branch lands on
+    ///
+    /// Branches usually store how many instructions away their destination is, but they can also
+    /// store the destination's label, which may be one set by a label reset. A distance that goes
+    /// past the end of the section, other than to just after its last instruction, has no
+    /// destination.
+    fn branch_target_index(
+        locations: &[InstrLocation],
+        in_func_index: usize,
+        value: &KOSValue,
+    ) -> Option<usize> {
+        match value {
+            KOSValue::String(label) | KOSValue::StringValue(label) => {
+                let mut label = label.clone();
+
+                if label.starts_with('@') {
+                    // Makes @0013 @000013, like the labels in the index
+                    label.insert_str(1, "00");
+                }
+
+                label.truncate(7);
+
+                locations
+                    .iter()
+                    .position(|location| !location.is_label_reset && location.label == label)
+            }
+            _ => in_func_index
+                .checked_add_signed(analysis::branch_distance(value)?)
+                .filter(|target| *target <= locations.len()),
+        }
+    }
+
+    /// Counts how many times the first instruction is repeated back to back, including itself
+    ///
+    /// Label resets are never collapsed, because they change the labels of what follows them.
+    fn count_repeats(instructions: &[&Instr]) -> usize {
+        let first = match instructions.first() {
+            Some(first) => Self::instr_key(first),
+            None => return 0,
+        };
+
+        if first.0 == Opcode::Lbrt {
+            return 1;
+        }
+
+        instructions
+            .iter()
+            .take_while(|instr| Self::instr_key(instr) == first)
+            .count()
+    }
+
+    /// Everything that makes up an instruction, in a form that can be compared
+    fn instr_key(instr: &Instr) -> (Opcode, Option<usize>, Option<usize>) {
+        let operands = instr_operands(instr);
+
+        (
+            instr_opcode(instr),
+            operands.first().copied().map(usize::from),
+            operands.get(1).copied().map(usize::from),
+        )
+    }
+
+    /// Writes the raw bytes of an instruction, with enough room for the most operands any
+    /// instruction in the file has, so that the mnemonics line up in every section
+    fn write_raw_instr(&self, stream: &mut dyn WriteColor, instr: &Instr) -> DumpResult {
+        let width = self.index.index_width() * 2;
+
+        let operands = instr_operands(instr);
+
+        write!(stream, "{:0>2x}", u8::from(instr_opcode(instr)))?;
+
+        for i in 0..self.index.max_operands() {
+            match operands.get(i) {
+                Some(op) => write!(stream, " {:0>width$x}", usize::from(*op), width = width)?,
+                None => write!(stream, " {:<width$}", "", width = width)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// With --align-columns, the width of the widest first operand of any two operand instruction in
+    /// the file. Otherwise 0, which leaves operands unaligned.
+    fn first_operand_width(&self, config: &CLIConfig) -> usize {
+        if !config.align_columns {
+            return 0;
         }
+
+        self.ksmfile
+            .code_sections()
+            .flat_map(|code_section| code_section.instructions())
+            .filter_map(|instr| match instr {
+                Instr::TwoOp(_, op1, _) => self.value_from_operand(*op1),
+                _ => None,
+            })
+            .map(|value| super::kosvalue_width(value, self.encoding))
+            .max()
+            .unwrap_or(0)
     }
 
     fn max_debug_line_number(&self) -> isize {
@@ -574,27 +2298,89 @@ impl KSMFileDebug {
         max
     }
 
-    fn find_entry_with_addr(&self, addr: usize) -> Option<(&DebugEntry, &DebugRange)> {
-        let debug_section = &self.ksmfile.debug_section;
+    /// Whether an instruction matches --grep, with its operands written as the disassembly shows them
+    fn grep_matches(&self, pattern: &GrepPattern, instr: &Instr) -> bool {
+        let mnemonic: &str = instr_opcode(instr).into();
+        let operands: Vec<String> = instr_operands(instr)
+            .into_iter()
+            .map(|op| {
+                self.value_from_operand(op)
+                    .map(|value| super::kosvalue_quoted(value, self.encoding))
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        super::grep_matches(pattern, mnemonic, &operands)
+    }
 
-        for debug_entry in debug_section.debug_entries() {
-            for debug_range in debug_entry.ranges() {
-                if addr >= debug_range.start && addr <= debug_range.end {
-                    return Some((debug_entry, debug_range));
-                }
-            }
-        }
+    /// Every instruction in the file as the source line it was compiled from and its size in bytes
+    fn instrs_by_line(
+        &self,
+        include_lbrt: bool,
+    ) -> impl Iterator<Item = (Option<isize>, usize)> + '_ {
+        self.ksmfile
+            .code_sections()
+            .enumerate()
+            .flat_map(|(section_index, code_section)| {
+                code_section
+                    .instructions()
+                    .zip(self.index.locations(section_index))
+            })
+            .filter(move |(instr, _)| include_lbrt || instr_opcode(instr) != Opcode::Lbrt)
+            .map(|(_, location)| {
+                let line = self
+                    .find_entry_with_addr(location.addr)
+                    .map(|(entry, _)| entry.line_number);
+
+                (line, location.size)
+            })
+    }
+
+    fn find_entry_with_addr(&self, addr: usize) -> Option<(&'a DebugEntry, &'a DebugRange)> {
+        self.index.debug_ranges().find(addr)
+    }
 
-        None
+    fn value_from_operand(&self, op: ArgIndex) -> Option<&'a KOSValue> {
+        self.index.value(op)
     }
 
-    fn value_from_operand(&self, op: ArgIndex) -> Option<&KOSValue> {
-        self.ksmfile.arg_section.get(op)
+    /// Every instruction in a code section as its opcode and the value of its first operand,
+    /// which is all that the analysis passes over a whole section need
+    fn first_operands(&self, code_section: &CodeSection) -> Vec<(Opcode, Option<&'a KOSValue>)> {
+        code_section
+            .instructions()
+            .map(|instr| {
+                let first_operand = instr_operands(instr)
+                    .first()
+                    .and_then(|op| self.value_from_operand(*op));
+
+                (instr_opcode(instr), first_operand)
+            })
+            .collect()
+    }
+
+    /// Writes the argument section as CSV, with the columns: table, index, type, value
+    fn dump_csv(&self, stream: &mut dyn WriteColor) -> DumpResult {
+        super::write_csv_row(stream, &["table", "index", "type", "value"])?;
+
+        for (index, value) in self.index.arguments() {
+            super::write_csv_row(
+                stream,
+                &[
+                    "argument",
+                    &index.to_string(),
+                    super::kosvalue_type_str(value),
+                    &super::kosvalue_str(value, self.encoding),
+                ],
+            )?;
+        }
+
+        Ok(())
     }
 
     fn dump_argument_section(
         &self,
-        stream: &mut StandardStream,
+        stream: &mut dyn WriteColor,
         regular_color: &ColorSpec,
         type_color: &ColorSpec,
         variable_color: &ColorSpec,
@@ -618,17 +2404,13 @@ impl KSMFileDebug {
             "Value",
         )?;
 
-        let mut index = 3;
-
-        for value in arg_section.arguments() {
+        for (index, value) in self.index.arguments() {
             stream.set_color(regular_color)?;
 
             let index_str = format!("  {:0>width$x}", index, width = addr_width * 2);
 
             write!(stream, "{:<20}", index_str)?;
 
-            index += value.size_bytes();
-
             stream.set_color(type_color)?;
             match value {
                 KOSValue::Null => {
@@ -674,7 +2456,7 @@ impl KSMFileDebug {
                     } else {
                         stream.set_color(regular_color)?;
                     }
-                    write!(stream, "{}", s)?;
+                    write!(stream, "{}", super::decode_string(s, self.encoding))?;
                     stream.set_color(regular_color)?;
                     write!(stream, "\"")?;
                 }
@@ -704,12 +2486,55 @@ impl KSMFileDebug {
                     } else {
                         stream.set_color(regular_color)?;
                     }
-                    write!(stream, "\"{}\"", s)?;
+                    write!(stream, "\"{}\"", super::decode_string(s, self.encoding))?;
+                }
+            }
+
+            if let KOSValue::String(s) | KOSValue::StringValue(s) = value {
+                if super::non_ascii_bytes(s).is_some() {
+                    stream.set_color(regular_color)?;
+                    write!(stream, "  ; has non-ASCII bytes, see --encoding")?;
                 }
             }
+
             writeln!(stream)?;
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locations(n: usize) -> Vec<InstrLocation> {
+        (0..n)
+            .map(|i| InstrLocation {
+                label: format!("@{:>06}", i + 1),
+                local_index: i,
+                addr: 2 + i * 2,
+                size: 2,
+                is_label_reset: false,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn branch_past_end_of_section_has_no_target() {
+        let locations = locations(4);
+
+        assert_eq!(
+            KSMFileDebug::branch_target_index(&locations, 3, &KOSValue::Int32(1)),
+            Some(4)
+        );
+        assert_eq!(
+            KSMFileDebug::branch_target_index(&locations, 3, &KOSValue::Int32(2)),
+            None
+        );
+        assert_eq!(
+            KSMFileDebug::branch_target_index(&locations, 1, &KOSValue::Int32(-2)),
+            None
+        );
+    }
+}
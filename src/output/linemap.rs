@@ -0,0 +1,149 @@
+use serde::Serialize;
+use std::io::Write;
+
+use crate::index::IndexedKsm;
+use crate::model;
+
+use super::DumpResult;
+
+/// Maps the source lines of a KSM file to the instructions that were compiled from them
+#[derive(Debug, Serialize)]
+struct LineMap<'a> {
+    format_version: u32,
+    file: &'a str,
+    lines: Vec<LineEntry<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct LineEntry<'a> {
+    line: isize,
+    ranges: Vec<RangeEntry<'a>>,
+}
+
+/// One debug range, with its addresses counted the same way as instruction addresses
+#[derive(Debug, Serialize)]
+struct RangeEntry<'a> {
+    start: usize,
+    end: usize,
+    instructions: Vec<InstrEntry<'a>>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+struct InstrEntry<'a> {
+    addr: usize,
+    label: &'a str,
+    section: &'a str,
+}
+
+/// Writes a JSON map from each line in a KSM file's debug section to the addresses and labels of
+/// the instructions in each of its ranges, for tools that need to symbolize kOS runtime addresses
+///
+/// The section names are given in the same order as the file's code sections. Unless the JSON is
+/// pretty-printed, each line is written as soon as it is worked out, and the JSON is compact.
+pub fn write_ksm_linemap(
+    out: &mut impl Write,
+    title: &str,
+    index: &IndexedKsm,
+    section_names: &[&str],
+    pretty: bool,
+) -> DumpResult {
+    // Every instruction in address order, so that the ones in a range can be found by binary search
+    let mut instructions = Vec::new();
+
+    for (section_index, section) in section_names.iter().enumerate() {
+        for location in index.locations(section_index) {
+            instructions.push(InstrEntry {
+                addr: location.addr,
+                label: &location.label,
+                section,
+            });
+        }
+    }
+
+    instructions.sort_by_key(|instr| instr.addr);
+
+    let lines = index
+        .ksmfile()
+        .debug_section
+        .debug_entries()
+        .map(|entry| LineEntry {
+            line: entry.line_number,
+            ranges: entry
+                .ranges()
+                .map(|range| {
+                    let first = instructions.partition_point(|instr| instr.addr < range.start);
+                    let last = instructions.partition_point(|instr| instr.addr <= range.end);
+
+                    RangeEntry {
+                        start: range.start,
+                        end: range.end,
+                        instructions: instructions[first..last.max(first)].to_vec(),
+                    }
+                })
+                .collect(),
+        });
+
+    if pretty {
+        serde_json::to_writer_pretty(
+            &mut *out,
+            &LineMap {
+                format_version: model::FORMAT_VERSION,
+                file: title,
+                lines: lines.collect(),
+            },
+        )?;
+        writeln!(out)?;
+
+        return Ok(());
+    }
+
+    super::start_json_stream(out, title)?;
+    super::write_json_stream_list(out, "lines", lines)?;
+    writeln!(out, "}}")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kerbalobjects::ksm::KSMFile;
+    use kerbalobjects::{KOSValue, Opcode};
+    use serde_json::Value;
+
+    use crate::testing::ksm_with;
+
+    fn linemap(ksm: &KSMFile, pretty: bool) -> Value {
+        let index = IndexedKsm::new(ksm);
+        let mut out = Vec::new();
+
+        write_ksm_linemap(
+            &mut out,
+            "test.ksm",
+            &index,
+            &["FUNC", "INIT", "MAIN"],
+            pretty,
+        )
+        .unwrap();
+
+        serde_json::from_slice(&out).unwrap()
+    }
+
+    #[test]
+    fn streaming_writes_the_same_document() {
+        let x = [KOSValue::String(String::from("$x"))];
+        let ksm = ksm_with(
+            &[],
+            &[],
+            &[
+                &[(Opcode::Push, &[KOSValue::Int16(1)]), (Opcode::Sto, &x)],
+                &[(Opcode::Push, &x), (Opcode::Pop, &[])],
+            ],
+        );
+
+        let streamed = linemap(&ksm, false);
+
+        assert_eq!(streamed, linemap(&ksm, true));
+        assert_eq!(streamed["lines"].as_array().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,212 @@
+use std::error::Error;
+use std::io::{self, Write};
+use termcolor::{Color, ColorSpec, WriteColor};
+
+use crate::{DARK_RED_COLOR, GREEN_COLOR, LIGHT_RED_COLOR, ORANGE_COLOR, PURPLE_COLOR};
+
+use super::DumpResult;
+
+/// The typesetting languages a listing can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListingFormat {
+    /// A LaTeX document using fancyvrb's Verbatim environment
+    Latex,
+    /// A typst document where every run of text is a raw block
+    Typst,
+}
+
+/// The macro each color of the normal terminal output is written with, named after what that
+/// color is mostly used for, so that a document can restyle each kind of text
+fn color_macros() -> [(Color, &'static str); 5] {
+    [
+        (PURPLE_COLOR, "kdlabel"),
+        (ORANGE_COLOR, "kdline"),
+        (DARK_RED_COLOR, "kdmnemonic"),
+        (LIGHT_RED_COLOR, "kdvariable"),
+        (GREEN_COLOR, "kdtype"),
+    ]
+}
+
+fn color_macro(color: Color) -> Option<&'static str> {
+    color_macros()
+        .into_iter()
+        .find(|(macro_color, _)| *macro_color == color)
+        .map(|(_, name)| name)
+}
+
+fn rgb(color: Color) -> (u8, u8, u8) {
+    match color {
+        Color::Rgb(r, g, b) => (r, g, b),
+        _ => (0, 0, 0),
+    }
+}
+
+fn escape_latex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '{' => escaped.push_str("\\{"),
+            '}' => escaped.push_str("\\}"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn escape_typst(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// A stream that the normal terminal output can be written to, which turns it into a LaTeX or
+/// typst listing with each color change becoming one of the kd macros
+///
+/// Text is held until the color changes or the line ends, since a macro can't span lines.
+pub struct ListingColorWriter<W: Write> {
+    out: W,
+    format: ListingFormat,
+    color_macro: Option<&'static str>,
+    pending: Vec<u8>,
+    /// Whether anything has been written since the last line ended
+    mid_line: bool,
+}
+
+impl<W: Write> ListingColorWriter<W> {
+    /// Writes the start of the document, including a definition of each macro
+    pub fn new(mut out: W, format: ListingFormat, title: &str) -> Result<Self, Box<dyn Error>> {
+        match format {
+            ListingFormat::Latex => {
+                writeln!(out, "% Disassembly of {}, written by kDump", title)?;
+                writeln!(out, "\\documentclass{{article}}")?;
+                writeln!(out, "\\usepackage{{xcolor}}")?;
+                writeln!(out, "\\usepackage{{fancyvrb}}")?;
+
+                for (color, name) in color_macros() {
+                    let (r, g, b) = rgb(color);
+                    writeln!(
+                        out,
+                        "\\newcommand{{\\{}}}[1]{{\\textcolor[RGB]{{{},{},{}}}{{#1}}}}",
+                        name, r, g, b
+                    )?;
+                }
+
+                writeln!(out, "\\begin{{document}}")?;
+                writeln!(out, "\\begin{{Verbatim}}[commandchars=\\\\\\{{\\}}]")?;
+            }
+            ListingFormat::Typst => {
+                writeln!(out, "// Disassembly of {}, written by kDump", title)?;
+
+                for (color, name) in color_macros() {
+                    let (r, g, b) = rgb(color);
+                    writeln!(
+                        out,
+                        "#let {}(s) = text(fill: rgb({}, {}, {}), raw(s))",
+                        name, r, g, b
+                    )?;
+                }
+
+                writeln!(out)?;
+            }
+        }
+
+        Ok(Self {
+            out,
+            format,
+            color_macro: None,
+            pending: Vec::new(),
+            mid_line: false,
+        })
+    }
+
+    /// Writes out the text held so far, wrapped in the macro for its color
+    fn flush_pending(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let text = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        self.mid_line = true;
+
+        match (self.format, self.color_macro) {
+            (ListingFormat::Latex, Some(name)) => {
+                write!(self.out, "\\{}{{{}}}", name, escape_latex(&text))
+            }
+            (ListingFormat::Latex, None) => write!(self.out, "{}", escape_latex(&text)),
+            (ListingFormat::Typst, Some(name)) => {
+                write!(self.out, "#{}(\"{}\")", name, escape_typst(&text))
+            }
+            (ListingFormat::Typst, None) => write!(self.out, "#raw(\"{}\")", escape_typst(&text)),
+        }
+    }
+
+    fn end_line(&mut self) -> io::Result<()> {
+        self.flush_pending()?;
+        self.mid_line = false;
+
+        match self.format {
+            ListingFormat::Latex => writeln!(self.out),
+            // A backslash at the end of a line is a line break, which keeps empty lines too
+            ListingFormat::Typst => writeln!(self.out, " \\"),
+        }
+    }
+
+    /// Writes whatever text is left and the end of the document
+    pub fn finish(mut self) -> DumpResult {
+        self.flush_pending()?;
+
+        if self.mid_line {
+            self.end_line()?;
+        }
+
+        if self.format == ListingFormat::Latex {
+            writeln!(self.out, "\\end{{Verbatim}}")?;
+            writeln!(self.out, "\\end{{document}}")?;
+        }
+
+        self.out.flush()?;
+
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for ListingColorWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            if byte == b'\n' {
+                self.end_line()?;
+            } else {
+                self.pending.push(byte);
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+impl<W: Write> WriteColor for ListingColorWriter<W> {
+    fn supports_color(&self) -> bool {
+        true
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        let color_macro = spec.fg().and_then(|&color| color_macro(color));
+
+        if color_macro != self.color_macro {
+            self.flush_pending()?;
+            self.color_macro = color_macro;
+        }
+
+        Ok(())
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        self.set_color(&ColorSpec::new())
+    }
+}
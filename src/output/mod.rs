@@ -1,20 +1,383 @@
 use kerbalobjects::KOSValue;
+use serde::Serialize;
+use std::borrow::Cow;
 use std::error::Error;
-use std::io::Write;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use termcolor::Color;
 use termcolor::ColorSpec;
-use termcolor::StandardStream;
 use termcolor::WriteColor;
 
+use crate::analysis;
+use crate::cancel::StopReason;
+use crate::model;
+use crate::CLIConfig;
+use crate::ColorRole;
+use crate::GrepPattern;
+use crate::SizeFormat;
+use crate::StringEncoding;
+
 type DynResult<T> = Result<T, Box<dyn Error>>;
 type DumpResult = DynResult<()>;
 
+mod costs;
+
+mod datamap;
+
+mod dot;
+
+mod gzip;
+pub use gzip::dump_gzip_anomalies;
+
+mod hexdump;
+pub use hexdump::{dump_ko_hex, dump_ksm_hex};
+
+mod html;
+pub use html::HtmlColorWriter;
+
+mod kasm;
+
 mod ko;
 pub use ko::KOFileDebug;
 
 mod ksm;
 pub use ksm::KSMFileDebug;
 
-pub fn kosvalue_str(value: &KOSValue) -> String {
+mod linemap;
+
+mod listing;
+pub use listing::{ListingColorWriter, ListingFormat};
+
+mod outline;
+pub use outline::{dump_ksm_outline, dump_width_trials};
+
+mod parquet;
+
+mod style;
+
+mod template;
+
+/// Whether the terminal's locale can show characters outside of ASCII, like box drawing characters
+///
+/// The first of LC_ALL, LC_CTYPE, and LANG that is set decides. The C and POSIX locales are
+/// ASCII, but any other locale that doesn't name an encoding is taken to be UTF-8, since that's
+/// what nearly every terminal uses now.
+pub fn locale_is_utf8() -> bool {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty());
+
+    match locale {
+        Some(locale) => {
+            let locale = locale.to_ascii_lowercase();
+
+            if locale == "c" || locale == "posix" {
+                false
+            } else {
+                !locale.contains('.') || locale.contains("utf-8") || locale.contains("utf8")
+            }
+        }
+        None => true,
+    }
+}
+
+/// The bytes of a string as the file stores them, if it has any that aren't ASCII
+///
+/// The KSM reader turns every byte into one character, so this only fails for strings that
+/// didn't come from one, which are already decoded.
+pub fn non_ascii_bytes(s: &str) -> Option<Vec<u8>> {
+    if s.is_ascii() {
+        return None;
+    }
+
+    s.chars().map(|c| u8::try_from(c).ok()).collect()
+}
+
+/// Decodes a string read from a file according to --encoding
+pub fn decode_string(s: &str, encoding: StringEncoding) -> Cow<'_, str> {
+    if encoding == StringEncoding::Latin1 {
+        return Cow::Borrowed(s);
+    }
+
+    match non_ascii_bytes(s).map(String::from_utf8) {
+        Some(Ok(decoded)) => Cow::Owned(decoded),
+        _ => Cow::Borrowed(s),
+    }
+}
+
+/// Formats a number of bytes according to the --human-readable setting
+pub fn format_size(bytes: usize, format: Option<SizeFormat>) -> String {
+    match format {
+        None => format!("{} bytes", bytes),
+        Some(SizeFormat::Separators) => {
+            let digits = bytes.to_string();
+            let mut s = String::new();
+
+            for (i, c) in digits.chars().enumerate() {
+                if i != 0 && (digits.len() - i).is_multiple_of(3) {
+                    s.push(',');
+                }
+
+                s.push(c);
+            }
+
+            format!("{} bytes", s)
+        }
+        Some(SizeFormat::Units) => {
+            const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+            if bytes < 1024 {
+                return format!("{} B", bytes);
+            }
+
+            let mut value = bytes as f64 / 1024.0;
+            let mut unit = 0;
+
+            while value >= 1024.0 && unit < UNITS.len() - 1 {
+                value /= 1024.0;
+                unit += 1;
+            }
+
+            format!("{:.1} {}", value, UNITS[unit])
+        }
+    }
+}
+
+/// The name of the type of a value, as shown in argument and data section dumps
+pub fn kosvalue_type_str(value: &KOSValue) -> &'static str {
+    match value {
+        KOSValue::Null => "NULL",
+        KOSValue::Bool(_) => "BOOL",
+        KOSValue::Byte(_) => "BYTE",
+        KOSValue::Int16(_) => "INT16",
+        KOSValue::Int32(_) => "INT32",
+        KOSValue::Float(_) => "FLOAT",
+        KOSValue::Double(_) => "DOUBLE",
+        KOSValue::String(_) => "STRING",
+        KOSValue::ArgMarker => "ARGMARKER",
+        KOSValue::ScalarInt(_) => "SCALARINT",
+        KOSValue::ScalarDouble(_) => "SCALARDOUBLE",
+        KOSValue::BoolValue(_) => "BOOLVALUE",
+        KOSValue::StringValue(_) => "STRINGVALUE",
+    }
+}
+
+/// The title of a report written to a file, which is the name of the file that was dumped
+pub fn report_title(config: &CLIConfig) -> String {
+    config
+        .file_path
+        .as_ref()
+        .and_then(|path| path.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("kDump report"))
+}
+
+/// The color to print one kind of text in, which is no color at all if it was given to --no-color-for
+fn role_color(config: &CLIConfig, role: ColorRole, color: Color) -> ColorSpec {
+    let mut spec = ColorSpec::new();
+
+    if !config.no_color_for.contains(&role) {
+        spec.set_fg(Some(color));
+    }
+
+    spec
+}
+
+/// Writes the --verify report of how each code section compares with the bytes it was decoded
+/// from, naming the sections with the names given in the same order
+fn write_verification(
+    stream: &mut dyn WriteColor,
+    checks: &[analysis::SectionCheck],
+    names: &[&str],
+) -> DumpResult {
+    writeln!(stream, "\nVerification:")?;
+
+    for check in checks {
+        let name = names.get(check.section).copied().unwrap_or("");
+
+        for i in &check.unknown {
+            writeln!(
+                stream,
+                "  {} instruction {}: unknown opcode, the instruction stream may be desynchronized",
+                name, i
+            )?;
+        }
+
+        match check.extra_bytes() {
+            None => writeln!(
+                stream,
+                "  {}: the section couldn't be found in the file's bytes",
+                name
+            )?,
+            Some(extra) if extra > 0 => writeln!(
+                stream,
+                "  {}: takes up {} more byte(s) than its {} decoded instruction(s), so it may have extra operand bytes",
+                name, extra, check.decoded_count
+            )?,
+            Some(extra) if extra < 0 => writeln!(
+                stream,
+                "  {}: takes up {} fewer byte(s) than its {} decoded instruction(s), so it may be missing operand bytes",
+                name, -extra, check.decoded_count
+            )?,
+            Some(_) => {}
+        }
+
+        if let Some(count) = check
+            .encoded_count
+            .filter(|count| *count != check.decoded_count)
+        {
+            writeln!(
+                stream,
+                "  {}: {} instruction(s) are in the file, but {} were decoded",
+                name, count, check.decoded_count
+            )?;
+        }
+    }
+
+    let num_mismatches: usize = checks.iter().map(|check| check.num_mismatches()).sum();
+
+    if num_mismatches == 0 {
+        writeln!(stream, "  No operand mismatches found.")?;
+    } else {
+        writeln!(stream, "  {} operand mismatch(es) found.", num_mismatches)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the note left where an analysis stopped early, after the sections it got through, and
+/// whatever it wrote of the one after those if it stopped partway through it
+///
+/// The note starts with the comment marker given, so that DOT and decompiled output stay valid.
+fn write_stop_note(
+    stream: &mut dyn WriteColor,
+    comment: &str,
+    reason: StopReason,
+    done: usize,
+    total: usize,
+    partway: bool,
+) -> DumpResult {
+    if partway {
+        writeln!(
+            stream,
+            "\n{}Note: stopped partway through section {} of {} because {}",
+            comment,
+            done + 1,
+            total,
+            reason
+        )?;
+    } else {
+        writeln!(
+            stream,
+            "\n{}Note: stopped after {} of {} sections because {}",
+            comment, done, total, reason
+        )?;
+    }
+
+    stream.flush()?;
+
+    Ok(())
+}
+
+/// Starts a JSON document that is written a section at a time, with the fields that every kDump JSON document begins with
+fn start_json_stream(out: &mut dyn Write, title: &str) -> DumpResult {
+    write!(
+        out,
+        "{{\"format_version\":{},\"file\":",
+        model::FORMAT_VERSION
+    )?;
+    serde_json::to_writer(&mut *out, title)?;
+
+    Ok(())
+}
+
+/// Writes one list field of a streamed JSON document, an element at a time, so that only the
+/// element being written has to be in memory
+fn write_json_stream_list<T: Serialize>(
+    out: &mut dyn Write,
+    name: &str,
+    items: impl Iterator<Item = T>,
+) -> DumpResult {
+    write!(out, ",\"{}\":[", name)?;
+
+    for (i, item) in items.enumerate() {
+        if i > 0 {
+            write!(out, ",")?;
+        }
+
+        serde_json::to_writer(&mut *out, &item)?;
+    }
+
+    write!(out, "]")?;
+
+    Ok(())
+}
+
+/// Writes a single record as one line of JSON, flushing it so that readers get it right away
+fn write_ndjson_line(stream: &mut dyn WriteColor, record: &impl Serialize) -> DumpResult {
+    serde_json::to_writer(&mut *stream, record)?;
+    writeln!(stream)?;
+    stream.flush()?;
+
+    Ok(())
+}
+
+/// Writes every instruction in a file to a Parquet file, using the same operands as --style
+fn write_parquet(
+    path: &Path,
+    config: &CLIConfig,
+    dump_styled: impl FnOnce(&mut dyn WriteColor, &dyn style::DisasmStyle) -> DumpResult,
+) -> DumpResult {
+    let collector = parquet::SectionCollector::default();
+
+    dump_styled(&mut termcolor::NoColor::new(std::io::sink()), &collector)?;
+
+    parquet::write_instruction_parquet(path, &report_title(config), collector)
+}
+
+/// Writes every record of a file to a file with bincode
+fn write_binary_dump(path: &Path, dump: &model::BinaryDump) -> DumpResult {
+    let mut out = BufWriter::new(File::create(path)?);
+
+    dump.write(&mut out)?;
+    out.flush()?;
+
+    Ok(())
+}
+
+/// Writes each problem found in a file to stderr as one line of JSON
+pub fn write_diagnostics(diagnostics: &[model::Diagnostic]) -> DumpResult {
+    let mut stderr = std::io::stderr().lock();
+
+    for diagnostic in diagnostics {
+        serde_json::to_writer(&mut stderr, diagnostic)?;
+        writeln!(stderr)?;
+    }
+
+    Ok(())
+}
+
+/// Quotes a CSV field if it contains anything that would break the row apart
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Writes a single row of CSV
+pub fn write_csv_row(stream: &mut dyn WriteColor, fields: &[&str]) -> DumpResult {
+    let row: Vec<Cow<str>> = fields.iter().map(|field| csv_field(field)).collect();
+
+    writeln!(stream, "{}", row.join(","))?;
+
+    Ok(())
+}
+
+pub fn kosvalue_str(value: &KOSValue, encoding: StringEncoding) -> String {
     let mut s = String::new();
 
     match value {
@@ -40,7 +403,7 @@ pub fn kosvalue_str(value: &KOSValue) -> String {
             s = format!("{:.5}", d);
         }
         KOSValue::String(v) => {
-            s = v.clone();
+            s = decode_string(v, encoding).into_owned();
         }
         KOSValue::ArgMarker => {
             s.push('@');
@@ -55,16 +418,92 @@ pub fn kosvalue_str(value: &KOSValue) -> String {
             s.push_str(if *b { "true" } else { "false" });
         }
         KOSValue::StringValue(v) => {
-            s = v.clone();
+            s = decode_string(v, encoding).into_owned();
         }
     }
 
     s
 }
 
+/// Formats a value the way it is shown in disassembly, with strings in quotes
+pub fn kosvalue_quoted(value: &KOSValue, encoding: StringEncoding) -> String {
+    match value {
+        KOSValue::String(s) | KOSValue::StringValue(s) => {
+            format!("\"{}\"", decode_string(s, encoding))
+        }
+        _ => kosvalue_str(value, encoding),
+    }
+}
+
+/// Whether an instruction matches --grep, which is tried against its mnemonic and operands
+/// written the way the disassembly shows them, like: stog "$throttle"
+fn grep_matches(pattern: &GrepPattern, mnemonic: &str, operands: &[String]) -> bool {
+    let text = if operands.is_empty() {
+        mnemonic.to_string()
+    } else {
+        format!("{} {}", mnemonic, operands.join(", "))
+    };
+
+    pattern.0.is_match(&text)
+}
+
+/// Which instructions to show, given which ones matched --grep, including the ones around each
+/// match that are within the context
+fn grep_context(matches: &[bool], before: usize, after: usize) -> Vec<bool> {
+    let mut shown = vec![false; matches.len()];
+
+    for (i, _) in matches.iter().enumerate().filter(|(_, matched)| **matched) {
+        let start = i.saturating_sub(before);
+        let end = (i + after + 1).min(matches.len());
+
+        shown[start..end].fill(true);
+    }
+
+    shown
+}
+
+/// The number of columns a value takes up when it is written using write_kosvalue()
+fn kosvalue_width(value: &KOSValue, encoding: StringEncoding) -> usize {
+    let quotes = match value {
+        KOSValue::String(_) | KOSValue::StringValue(_) => 2,
+        _ => 0,
+    };
+
+    kosvalue_str(value, encoding).chars().count() + quotes
+}
+
+/// Writes an unknown opcode as a .byte pseudo-instruction holding the byte it was, along with
+/// where that byte is in the file, and ends the line
+///
+/// Either can be missing when the raw file isn't available.
+fn write_unknown_byte(
+    stream: &mut dyn WriteColor,
+    mnemonic_color: &ColorSpec,
+    regular_color: &ColorSpec,
+    byte: Option<u8>,
+    offset: Option<usize>,
+) -> DumpResult {
+    stream.set_color(mnemonic_color)?;
+    write!(stream, "  {:<6}", ".byte")?;
+    stream.set_color(regular_color)?;
+
+    match byte {
+        Some(byte) => write!(stream, "0x{:0>2x}", byte)?,
+        None => write!(stream, "0x??")?,
+    }
+
+    match offset {
+        Some(offset) => writeln!(stream, "  ; unknown opcode at offset 0x{:x}", offset)?,
+        None => writeln!(stream, "  ; unknown opcode")?,
+    }
+
+    Ok(())
+}
+
 fn write_kosvalue(
-    stream: &mut StandardStream,
+    stream: &mut dyn WriteColor,
     value: &KOSValue,
+    encoding: StringEncoding,
     regular_color: &ColorSpec,
     variable_color: &ColorSpec,
 ) -> DumpResult {
@@ -88,7 +527,7 @@ fn write_kosvalue(
         stream.set_color(variable_color)?;
     }
 
-    write!(stream, "{}", kosvalue_str(value))?;
+    write!(stream, "{}", kosvalue_str(value, encoding))?;
 
     if is_string {
         stream.set_color(regular_color)?;
@@ -97,3 +536,18 @@ fn write_kosvalue(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_strings_by_the_encoding_given() {
+        // "é" in UTF-8, read one byte per character the way kerbalobjects reads strings
+        let read = "\u{c3}\u{a9}";
+
+        assert_eq!(decode_string(read, StringEncoding::Utf8), "é");
+        assert_eq!(decode_string(read, StringEncoding::Latin1), read);
+        assert_eq!(decode_string("plain", StringEncoding::Utf8), "plain");
+    }
+}
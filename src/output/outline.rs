@@ -0,0 +1,121 @@
+use termcolor::WriteColor;
+
+use crate::fio::layout::{scan_ksm, KsmLayout, RegionKind, INDEX_WIDTHS};
+
+use super::DumpResult;
+
+/// Prints where each region of a KSM file is, and how many things are in it
+pub fn dump_ksm_outline(stream: &mut dyn WriteColor, layout: &KsmLayout) -> DumpResult {
+    writeln!(stream, "\nOutline:")?;
+    writeln!(
+        stream,
+        "  {:<10}{:<8}{:<16}{:<10}Count",
+        "Offset", "Marker", "Region", "Size"
+    )?;
+
+    for region in &layout.regions {
+        let count = match region.kind {
+            RegionKind::Header => String::new(),
+            RegionKind::Arguments => format!(
+                "{} argument(s), index width {}",
+                region.count, layout.index_width
+            ),
+            RegionKind::Debug => format!("{} debug entries", region.count),
+            _ if region.num_unknown > 0 => format!(
+                "{} instruction(s), {} unknown",
+                region.count, region.num_unknown
+            ),
+            _ => format!("{} instruction(s)", region.count),
+        };
+
+        writeln!(
+            stream,
+            "  {:0>8x}  {:<8}{:<16}{:<10}{}",
+            region.offset,
+            region.kind.marker().unwrap_or("-"),
+            region.kind.name(),
+            region.size,
+            count
+        )?;
+    }
+
+    if let Some(error) = &layout.error {
+        writeln!(
+            stream,
+            "\n  Scan stopped at offset {:0>8x}: {}",
+            error.offset, error.message
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Prints how the code sections of a KSM file decode with each possible index width, so that a
+/// file with a damaged or hand-edited argument section header can still be read
+pub fn dump_width_trials(stream: &mut dyn WriteColor, payload: &[u8]) -> DumpResult {
+    let own_width = scan_ksm(payload, None).index_width;
+    let mut clean_widths = Vec::new();
+
+    writeln!(stream, "\nIndex width trials:")?;
+    writeln!(
+        stream,
+        "  {:<8}{:<16}{:<10}Result",
+        "Width", "Instructions", "Unknown"
+    )?;
+
+    for width in INDEX_WIDTHS {
+        let layout = scan_ksm(payload, Some(width));
+        let num_instructions: usize = layout
+            .regions
+            .iter()
+            .filter(|region| region.kind.is_code())
+            .map(|region| region.count)
+            .sum();
+
+        let result = match &layout.error {
+            Some(error) => format!("stopped at offset {:0>8x}: {}", error.offset, error.message),
+            None if layout.num_unknown() > 0 => String::from("decodes with unknown opcodes"),
+            None => {
+                clean_widths.push(width);
+                String::from("clean")
+            }
+        };
+
+        writeln!(
+            stream,
+            "  {:<8}{:<16}{:<10}{}{}",
+            width,
+            num_instructions,
+            layout.num_unknown(),
+            result,
+            if width == own_width {
+                " (the file's own width)"
+            } else {
+                ""
+            }
+        )?;
+    }
+
+    writeln!(stream)?;
+
+    match clean_widths.as_slice() {
+        [] => writeln!(
+            stream,
+            "  No index width decodes cleanly, so the damage is not only to the width"
+        )?,
+        [width] if *width == own_width => {
+            writeln!(stream, "  The file's own index width decodes cleanly")?
+        }
+        widths => writeln!(
+            stream,
+            "  Index width(s) that decode cleanly: {}",
+            widths
+                .iter()
+                .map(|width| width.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::path::Path;
+
+use super::style::{DisasmSection, DisasmStyle};
+use super::DumpResult;
+
+/// A disassembly style that keeps every section instead of writing it, so that the instructions
+/// can be written out as rows
+#[derive(Default)]
+pub struct SectionCollector {
+    sections: RefCell<Vec<DisasmSection>>,
+}
+
+impl DisasmStyle for SectionCollector {
+    fn write_file_header(
+        &self,
+        _out: &mut dyn Write,
+        _file_name: &str,
+        _format: &str,
+    ) -> DumpResult {
+        Ok(())
+    }
+
+    fn write_section(&self, _out: &mut dyn Write, section: &DisasmSection) -> DumpResult {
+        self.sections.borrow_mut().push(section.clone());
+        Ok(())
+    }
+}
+
+/// Writes one row per instruction, with the file, section, index in the section, address, opcode,
+/// mnemonic, and the index and resolved value of each operand
+#[cfg(feature = "parquet")]
+pub fn write_instruction_parquet(
+    path: &Path,
+    file_name: &str,
+    collector: SectionCollector,
+) -> DumpResult {
+    use arrow_array::{ArrayRef, RecordBatch, StringArray, UInt64Array, UInt8Array};
+    use arrow_schema::{DataType, Field, Schema};
+    use parquet::arrow::ArrowWriter;
+    use std::fs::File;
+    use std::sync::Arc;
+
+    let sections = collector.sections.into_inner();
+    let lines = || {
+        sections.iter().flat_map(|section| {
+            section
+                .lines
+                .iter()
+                .enumerate()
+                .map(move |line| (section, line))
+        })
+    };
+    let operand = |n: usize| -> ArrayRef {
+        Arc::new(
+            lines()
+                .map(|(_, (_, line))| line.operand_indices.get(n).map(|&index| index as u64))
+                .collect::<UInt64Array>(),
+        )
+    };
+    let resolved = |n: usize| -> ArrayRef {
+        Arc::new(
+            lines()
+                .map(|(_, (_, line))| line.operands.get(n).cloned())
+                .collect::<StringArray>(),
+        )
+    };
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("file", DataType::Utf8, false),
+        Field::new("section", DataType::Utf8, false),
+        Field::new("index", DataType::UInt64, false),
+        Field::new("address", DataType::UInt64, false),
+        Field::new("opcode", DataType::UInt8, false),
+        Field::new("mnemonic", DataType::Utf8, false),
+        Field::new("operand1", DataType::UInt64, true),
+        Field::new("operand2", DataType::UInt64, true),
+        Field::new("resolved1", DataType::Utf8, true),
+        Field::new("resolved2", DataType::Utf8, true),
+    ]));
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(lines().map(|_| file_name))),
+        Arc::new(StringArray::from_iter_values(
+            lines().map(|(section, _)| section.name.as_str()),
+        )),
+        Arc::new(UInt64Array::from_iter_values(
+            lines().map(|(_, (i, _))| i as u64),
+        )),
+        Arc::new(UInt64Array::from_iter_values(
+            lines().map(|(_, (_, line))| line.addr as u64),
+        )),
+        Arc::new(UInt8Array::from_iter_values(
+            lines().map(|(_, (_, line))| line.bytes[0]),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            lines().map(|(_, (_, line))| line.mnemonic),
+        )),
+        operand(0),
+        operand(1),
+        resolved(0),
+        resolved(1),
+    ];
+
+    let batch = RecordBatch::try_new(schema.clone(), columns)?;
+
+    let mut writer = ArrowWriter::try_new(File::create(path)?, schema, None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+pub fn write_instruction_parquet(
+    _path: &Path,
+    _file_name: &str,
+    _collector: SectionCollector,
+) -> DumpResult {
+    Err("This build of kDump can't write Parquet files, since it was built without the parquet feature".into())
+}
@@ -0,0 +1,254 @@
+use std::io::Write;
+
+use crate::OutputStyle;
+
+use super::DumpResult;
+
+/// One disassembled instruction, in a form that any output style can lay out
+#[derive(Debug, Clone)]
+pub struct DisasmLine {
+    /// The byte address of the instruction
+    pub addr: usize,
+    /// The instruction as it is encoded in the file
+    pub bytes: Vec<u8>,
+    pub mnemonic: &'static str,
+    /// The argument or data index each operand refers to
+    #[cfg_attr(not(feature = "parquet"), allow(dead_code))]
+    pub operand_indices: Vec<usize>,
+    /// The already formatted operands
+    pub operands: Vec<String>,
+    /// The symbol or label a branch refers to, if it could be resolved
+    pub target: Option<String>,
+}
+
+/// One disassembled code section
+#[derive(Debug, Clone)]
+pub struct DisasmSection {
+    pub name: String,
+    pub lines: Vec<DisasmLine>,
+}
+
+/// A way of laying out disassembly that other tools expect, used instead of kDump's own
+/// colored layout when --style is given
+pub trait DisasmStyle {
+    /// Writes whatever comes before the first section, given the file's name and format
+    fn write_file_header(&self, out: &mut dyn Write, file_name: &str, format: &str) -> DumpResult;
+
+    fn write_section(&self, out: &mut dyn Write, section: &DisasmSection) -> DumpResult;
+}
+
+/// Gets the formatter for the style that was asked for, or None if kDump's own layout should be used
+pub fn disasm_style(style: Option<OutputStyle>) -> Option<Box<dyn DisasmStyle>> {
+    match style? {
+        OutputStyle::Objdump => Some(Box::new(ObjdumpStyle)),
+        OutputStyle::Readelf => None,
+    }
+}
+
+/// The fields of a KO file header
+#[derive(Debug, Clone)]
+pub struct KoHeaderInfo {
+    pub version: u8,
+    pub num_headers: u16,
+    pub shstrtab_idx: u16,
+}
+
+/// One entry of the KO section header table, along with where the section is in the file
+#[derive(Debug, Clone)]
+pub struct SectionRow {
+    pub name: String,
+    pub kind: &'static str,
+    pub offset: usize,
+    pub size: usize,
+    /// The size of each entry of a section that is a table of fixed size entries, otherwise 0
+    pub entry_size: usize,
+    pub flags: &'static str,
+    /// The index of the section this one refers to, like a symbol table's string table
+    pub link: usize,
+}
+
+/// One entry of a KO symbol table
+#[derive(Debug, Clone)]
+pub struct SymbolRow {
+    pub value: u32,
+    pub size: u16,
+    pub sym_type: &'static str,
+    pub bind: &'static str,
+    pub section: u16,
+    pub name: String,
+}
+
+/// A way of laying out the KO header and tables that other tools expect, used instead of kDump's
+/// own layout when --style is given
+pub trait TableStyle {
+    fn write_ko_header(&self, out: &mut dyn Write, header: &KoHeaderInfo) -> DumpResult;
+
+    fn write_section_headers(&self, out: &mut dyn Write, rows: &[SectionRow]) -> DumpResult;
+
+    fn write_symbols(
+        &self,
+        out: &mut dyn Write,
+        table_name: &str,
+        rows: &[SymbolRow],
+    ) -> DumpResult;
+}
+
+/// Gets the header and table formatter for the style that was asked for, or None if kDump's own
+/// layout should be used
+pub fn table_style(style: Option<OutputStyle>) -> Option<Box<dyn TableStyle>> {
+    match style? {
+        OutputStyle::Objdump => None,
+        OutputStyle::Readelf => Some(Box::new(ReadelfStyle)),
+    }
+}
+
+/// Lays disassembly out the same way GNU objdump -d does
+pub struct ObjdumpStyle;
+
+impl ObjdumpStyle {
+    /// objdump pads the raw bytes out to the width of 7 bytes
+    const BYTES_WIDTH: usize = 21;
+}
+
+impl DisasmStyle for ObjdumpStyle {
+    fn write_file_header(&self, out: &mut dyn Write, file_name: &str, format: &str) -> DumpResult {
+        writeln!(out, "\n{}:     file format {}\n", file_name, format)?;
+
+        Ok(())
+    }
+
+    fn write_section(&self, out: &mut dyn Write, section: &DisasmSection) -> DumpResult {
+        writeln!(out, "\nDisassembly of section {}:\n", section.name)?;
+
+        let start = section.lines.first().map(|line| line.addr).unwrap_or(0);
+
+        writeln!(out, "{:0>8x} <{}>:", start, section.name)?;
+
+        for line in &section.lines {
+            let bytes: Vec<String> = line
+                .bytes
+                .iter()
+                .map(|byte| format!("{:0>2x}", byte))
+                .collect();
+
+            let mut instr = format!("{:<6} {}", line.mnemonic, line.operands.join(","));
+
+            if let Some(target) = &line.target {
+                instr.push_str(&format!(" <{}>", target));
+            }
+
+            writeln!(
+                out,
+                "{:>8x}:\t{:<width$}\t{}",
+                line.addr,
+                bytes.join(" ") + " ",
+                instr.trim_end(),
+                width = Self::BYTES_WIDTH
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Lays out headers and tables the same way GNU readelf does for 32 bit files
+pub struct ReadelfStyle;
+
+impl TableStyle for ReadelfStyle {
+    fn write_ko_header(&self, out: &mut dyn Write, header: &KoHeaderInfo) -> DumpResult {
+        writeln!(out, "KO Header:")?;
+        writeln!(out, "  Magic:   6b 01 6f 66")?;
+        writeln!(out, "  {:<35}KO", "Class:")?;
+        writeln!(out, "  {:<35}{}", "Version:", header.version)?;
+        writeln!(
+            out,
+            "  {:<35}{} (bytes)",
+            "Size of this header:",
+            crate::fio::KO_HEADER_SIZE
+        )?;
+        writeln!(
+            out,
+            "  {:<35}{} (bytes)",
+            "Size of section headers:",
+            crate::fio::KO_SECTION_HEADER_SIZE
+        )?;
+        writeln!(
+            out,
+            "  {:<35}{}",
+            "Number of section headers:", header.num_headers
+        )?;
+        writeln!(
+            out,
+            "  {:<35}{}",
+            "Section header string table index:", header.shstrtab_idx
+        )?;
+
+        Ok(())
+    }
+
+    fn write_section_headers(&self, out: &mut dyn Write, rows: &[SectionRow]) -> DumpResult {
+        writeln!(out, "There are {} section headers:\n", rows.len())?;
+        writeln!(out, "Section Headers:")?;
+        writeln!(
+            out,
+            "  [Nr] Name              Type            Addr     Off    Size   ES Flg Lk Inf Al"
+        )?;
+
+        for (i, row) in rows.iter().enumerate() {
+            writeln!(
+                out,
+                "  [{:>2}] {:<17.17} {:<15} {:0>8x} {:0>6x} {:0>6x} {:0>2x} {:>3} {:>2} {:>3} {:>2}",
+                i,
+                row.name,
+                row.kind,
+                0,
+                row.offset,
+                row.size,
+                row.entry_size,
+                row.flags,
+                row.link,
+                0,
+                if row.kind == "NULL" { 0 } else { 1 }
+            )?;
+        }
+
+        writeln!(out, "Key to Flags:")?;
+        writeln!(out, "  W (write), A (alloc), X (execute)")?;
+
+        Ok(())
+    }
+
+    fn write_symbols(
+        &self,
+        out: &mut dyn Write,
+        table_name: &str,
+        rows: &[SymbolRow],
+    ) -> DumpResult {
+        writeln!(
+            out,
+            "\nSymbol table '{}' contains {} entries:",
+            table_name,
+            rows.len()
+        )?;
+        writeln!(
+            out,
+            "   Num:    Value  Size Type    Bind   Vis      Ndx Name"
+        )?;
+
+        for (i, row) in rows.iter().enumerate() {
+            let ndx = if row.section == 0 {
+                String::from("UND")
+            } else {
+                row.section.to_string()
+            };
+
+            writeln!(
+                out,
+                "{:>6}: {:0>8x} {:>5} {:<7} {:<6} {:<7} {:>4} {}",
+                i, row.value, row.size, row.sym_type, row.bind, "DEFAULT", ndx, row.name
+            )?;
+        }
+
+        Ok(())
+    }
+}
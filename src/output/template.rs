@@ -0,0 +1,32 @@
+use minijinja::Environment;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use termcolor::WriteColor;
+
+use super::DumpResult;
+
+/// Renders a user's minijinja template with the file's records as its context, and writes the result
+pub fn dump_template(
+    stream: &mut dyn WriteColor,
+    template_path: &Path,
+    context: &Value,
+) -> DumpResult {
+    let source = fs::read_to_string(template_path)?;
+
+    let mut env = Environment::new();
+    env.add_template("template", &source)
+        .map_err(|e| format!("Failed to load template {}: {}", template_path.display(), e))?;
+
+    let rendered = env.get_template("template")?.render(context).map_err(|e| {
+        format!(
+            "Failed to render template {}: {}",
+            template_path.display(),
+            e
+        )
+    })?;
+
+    write!(stream, "{}", rendered)?;
+
+    Ok(())
+}
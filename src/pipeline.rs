@@ -0,0 +1,336 @@
+use kerbalobjects::ko::KOFile;
+use kerbalobjects::ksm::KSMFile;
+use kerbalobjects::BufferIterator;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use termcolor::WriteColor;
+
+use crate::cancel::Deadline;
+use crate::fio::{self, FileType};
+use crate::mem::{self, Footprint};
+use crate::output::{self, KOFileDebug, KSMFileDebug};
+use crate::{AssumedType, CLIConfig, StringEncoding};
+
+type PipelineResult<T> = Result<T, Box<dyn Error>>;
+
+/// A file that has been read and parsed, but not looked at any further
+pub struct ParsedFile {
+    raw_contents: Vec<u8>,
+    contents: FileContents,
+}
+
+enum FileContents {
+    Ksm(KSMFile),
+    Ko {
+        kofile: KOFile,
+        /// The bytes the KO file was parsed from, which section offsets are counted from
+        parser_input: Vec<u8>,
+    },
+}
+
+impl ParsedFile {
+    /// The bytes of the file exactly as they were read
+    pub fn raw_contents(&self) -> &[u8] {
+        &self.raw_contents
+    }
+
+    /// The parsed KSM file, if this is one
+    pub fn ksm(&self) -> Option<&KSMFile> {
+        match &self.contents {
+            FileContents::Ksm(ksm) => Some(ksm),
+            FileContents::Ko { .. } => None,
+        }
+    }
+
+    /// Takes the parsed KSM file out, if this is one
+    pub(crate) fn into_ksm(self) -> Option<KSMFile> {
+        match self.contents {
+            FileContents::Ksm(ksm) => Some(ksm),
+            FileContents::Ko { .. } => None,
+        }
+    }
+
+    /// The parsed KO file, if this is one
+    pub fn ko(&self) -> Option<&KOFile> {
+        match &self.contents {
+            FileContents::Ko { kofile, .. } => Some(kofile),
+            FileContents::Ksm(_) => None,
+        }
+    }
+
+    /// The bytes the KO file was parsed from, if this is one
+    pub(crate) fn ko_parser_input(&self) -> Option<&[u8]> {
+        match &self.contents {
+            FileContents::Ko { parser_input, .. } => Some(parser_input),
+            FileContents::Ksm(_) => None,
+        }
+    }
+}
+
+/// The settings that change what analyze computes
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AnalysisOpts {
+    /// Whether to estimate how much memory each parsed structure takes up, for --mem-stats
+    pub footprints: bool,
+    /// How the strings in the file are decoded when they are shown
+    pub encoding: StringEncoding,
+    /// How long the analyses written out by render can take before they stop with what they have
+    pub timeout: Option<Duration>,
+}
+
+impl From<&CLIConfig> for AnalysisOpts {
+    fn from(config: &CLIConfig) -> Self {
+        AnalysisOpts {
+            footprints: config.mem_stats,
+            encoding: config.encoding,
+            timeout: config.timeout.map(Duration::from_secs),
+        }
+    }
+}
+
+/// A parsed file along with the lookup tables built for it, which is everything that rendering needs
+pub struct Report<'a> {
+    file: &'a ParsedFile,
+    kind: ReportKind<'a>,
+    footprints: Vec<Footprint>,
+}
+
+enum ReportKind<'a> {
+    Ksm(Box<KSMFileDebug<'a>>),
+    Ko(KOFileDebug<'a>),
+}
+
+/// Works out what kind of file the bytes are, unless --assume already says
+pub(crate) fn detect_file_type(
+    raw_contents: &[u8],
+    assume: Option<AssumedType>,
+) -> PipelineResult<FileType> {
+    Ok(match assume {
+        Some(AssumedType::Ksm) => fio::assume_ksm(raw_contents),
+        Some(AssumedType::Ko) => FileType::KerbalObject,
+        None => fio::determine_file_type(raw_contents)?,
+    })
+}
+
+/// Reads and parses the KSM or KO file at the given path
+pub fn load_file(path: &Path, assume: Option<AssumedType>) -> PipelineResult<ParsedFile> {
+    let raw_contents = fs::read(path)?;
+    let file_type = detect_file_type(&raw_contents, assume)?;
+
+    parse_file(raw_contents, file_type)
+}
+
+/// Parses bytes that have already been read, as the given type of file
+pub(crate) fn parse_file(raw_contents: Vec<u8>, file_type: FileType) -> PipelineResult<ParsedFile> {
+    let contents = match file_type {
+        FileType::KerbalMachineCode | FileType::UncompressedKerbalMachineCode => {
+            let contents = fio::ksm_parser_input(&raw_contents)?;
+            let mut contents_iter = BufferIterator::new(&contents);
+
+            FileContents::Ksm(KSMFile::parse(&mut contents_iter)?)
+        }
+        FileType::KerbalObject => {
+            let parser_input = fio::ko_parser_input(&raw_contents).into_owned();
+            let mut contents_iter = BufferIterator::new(&parser_input);
+
+            let kofile = KOFile::parse(&mut contents_iter)?;
+
+            FileContents::Ko {
+                kofile,
+                parser_input,
+            }
+        }
+        // If we have no idea what the heck the file is
+        FileType::Unknown => return Err(fio::describe_unrecognized(&raw_contents).into()),
+    };
+
+    Ok(ParsedFile {
+        raw_contents,
+        contents,
+    })
+}
+
+/// Builds the lookup tables that every kind of output uses
+///
+/// The timeout, if there is one, starts here.
+pub fn analyze<'a>(file: &'a ParsedFile, opts: &AnalysisOpts) -> Report<'a> {
+    let deadline = Deadline::after(opts.timeout);
+    let (kind, footprints) = match &file.contents {
+        FileContents::Ksm(ksm) => (
+            ReportKind::Ksm(Box::new(
+                KSMFileDebug::new(ksm)
+                    .with_raw_contents(&file.raw_contents)
+                    .with_encoding(opts.encoding)
+                    .with_deadline(deadline),
+            )),
+            opts.footprints.then(|| mem::ksm_footprint(ksm)),
+        ),
+        FileContents::Ko {
+            kofile,
+            parser_input,
+        } => (
+            ReportKind::Ko(
+                KOFileDebug::new(kofile)
+                    .with_raw_contents(parser_input)
+                    .with_encoding(opts.encoding)
+                    .with_deadline(deadline),
+            ),
+            opts.footprints.then(|| mem::ko_footprint(kofile)),
+        ),
+    };
+
+    Report {
+        file,
+        kind,
+        footprints: footprints.unwrap_or_default(),
+    }
+}
+
+/// Writes everything the configuration asks for about an analyzed file
+///
+/// Only the output settings of the configuration are read here, since the file has already been
+/// loaded and analyzed. Programs that use kDump as a library can make one with CLIConfig::builder().
+pub fn render(
+    report: &Report,
+    config: &CLIConfig,
+    stream: &mut dyn WriteColor,
+) -> PipelineResult<()> {
+    match &report.kind {
+        ReportKind::Ksm(ksm_debug) => ksm_debug.dump(stream, config)?,
+        ReportKind::Ko(ko_debug) => ko_debug.dump(stream, config)?,
+    }
+
+    if config.mem_stats {
+        dump_mem_stats(
+            stream,
+            config,
+            report.file.raw_contents.len(),
+            &report.footprints,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn dump_mem_stats(
+    stream: &mut dyn WriteColor,
+    config: &CLIConfig,
+    file_size: usize,
+    footprints: &[Footprint],
+) -> PipelineResult<()> {
+    let alloc_stats = mem::alloc_stats();
+
+    writeln!(stream, "\nMemory usage:")?;
+    writeln!(
+        stream,
+        "  {:<24}{}",
+        "Input file",
+        output::format_size(file_size, config.human_readable)
+    )?;
+    if cfg!(feature = "alloc-stats") {
+        writeln!(
+            stream,
+            "  {:<24}{}",
+            "Peak heap usage",
+            output::format_size(alloc_stats.peak_bytes, config.human_readable)
+        )?;
+        writeln!(
+            stream,
+            "  {:<24}{}",
+            "Allocations", alloc_stats.total_allocations
+        )?;
+    } else {
+        writeln!(
+            stream,
+            "  {:<24}not counted without the alloc-stats feature",
+            "Peak heap usage"
+        )?;
+    }
+
+    writeln!(stream, "\n  Estimated footprint per structure:")?;
+
+    for footprint in footprints {
+        writeln!(
+            stream,
+            "    {:<22}{}",
+            footprint.name,
+            output::format_size(footprint.bytes, config.human_readable)
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kerbalobjects::{KOSValue, Opcode};
+    use termcolor::NoColor;
+
+    use crate::testing::{ksm_from, written, TempFile};
+
+    /// print("Hello, world!").
+    fn hello_file() -> KSMFile {
+        ksm_from(&[
+            (Opcode::Push, &[KOSValue::ArgMarker]),
+            (
+                Opcode::Push,
+                &[KOSValue::StringValue(String::from("Hello, world!"))],
+            ),
+            (
+                Opcode::Call,
+                &[
+                    KOSValue::String(String::from("print()")),
+                    KOSValue::String(String::new()),
+                ],
+            ),
+            (Opcode::Pop, &[]),
+        ])
+    }
+
+    #[test]
+    fn loads_analyzes_and_renders_a_file() {
+        let contents = written(&hello_file());
+        let temp = TempFile::new("hello.ksm", &contents);
+
+        let file = load_file(temp.path(), None).unwrap();
+
+        assert!(file.ksm().is_some());
+        assert_eq!(file.raw_contents(), contents.as_slice());
+
+        let config = CLIConfig::builder().disassemble(true).build(temp.path());
+        let report = analyze(&file, &AnalysisOpts::from(&config));
+
+        let mut out = NoColor::new(Vec::new());
+        render(&report, &config, &mut out).unwrap();
+        let out = String::from_utf8(out.into_inner()).unwrap();
+
+        assert!(out.contains("MAIN"), "{}", out);
+        assert!(out.contains("call"), "{}", out);
+        assert!(out.contains("\"Hello, world!\""), "{}", out);
+        assert!(out.contains("\"print()\""), "{}", out);
+    }
+
+    #[test]
+    fn keeps_what_was_written_when_the_timeout_runs_out() {
+        let file = parse_file(written(&hello_file()), FileType::KerbalMachineCode).unwrap();
+        let config = CLIConfig::builder()
+            .disassemble(true)
+            .timeout(0u64)
+            .build("hello.ksm");
+        let report = analyze(&file, &AnalysisOpts::from(&config));
+
+        let mut out = NoColor::new(Vec::new());
+        render(&report, &config, &mut out).unwrap();
+        let out = String::from_utf8(out.into_inner()).unwrap();
+
+        assert!(
+            out.contains("Note: stopped after 0 of 3 sections because the --timeout ran out"),
+            "{}",
+            out
+        );
+        assert!(!out.contains("call"), "{}", out);
+    }
+}
@@ -0,0 +1,565 @@
+//! A JSON-RPC 2.0 server over stdin and stdout, so that an editor or GUI can keep one kDump
+//! process open and ask it about a file, instead of starting a new one for every question
+//!
+//! Each request and response is one line of JSON.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufRead, BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::model::{self, Record};
+use crate::{AssumedType, StringEncoding};
+
+type RpcResult<T> = Result<T, Box<dyn Error>>;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Anything that went wrong carrying out a valid request, like the file not parsing
+const SERVER_ERROR: i64 = -32000;
+
+/// The version of the session file format, which goes up whenever a change means an older
+/// session file can't be restored, separately from the version of the records
+const SESSION_FORMAT_VERSION: u32 = 1;
+
+/// A failed request, which is sent back as the error of the response
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<Box<dyn Error>> for RpcError {
+    fn from(error: Box<dyn Error>) -> Self {
+        RpcError::new(SERVER_ERROR, error.to_string())
+    }
+}
+
+/// A file that has been opened, kept as its records so that requests don't have to parse it again
+struct OpenFile {
+    path: PathBuf,
+    assume: Option<String>,
+    format: String,
+    records: Vec<Value>,
+    /// The positions in records of the instructions, which are kept in file order
+    instructions: Vec<usize>,
+    /// Each code section that has instructions, in file order
+    sections: Vec<SectionSpan>,
+}
+
+/// A code section, which is told apart from others by its index, since names can repeat
+struct SectionSpan {
+    /// The position of the section among a KSM file's code sections, or a KO file's section index
+    index: usize,
+    name: String,
+    /// The positions in the instruction list of the section's instructions
+    instructions: Range<usize>,
+}
+
+impl OpenFile {
+    fn open(path: &Path, assume: Option<&str>) -> RpcResult<Self> {
+        let assume_type = match assume {
+            Some("ksm") => Some(AssumedType::Ksm),
+            Some("ko") => Some(AssumedType::Ko),
+            Some(other) => return Err(format!("assume must be ksm or ko, not {}", other).into()),
+            None => None,
+        };
+
+        let file = crate::load_file(path, assume_type)?;
+        let mut format = String::new();
+        let mut records = Vec::new();
+        let mut instructions = Vec::new();
+
+        model::visit_file(&file, StringEncoding::default(), &mut |record| {
+            match &record {
+                Record::File { format: kind, .. } => format = kind.to_string(),
+                Record::Instruction { .. } => instructions.push(records.len()),
+                _ => {}
+            }
+
+            records.push(serde_json::to_value(&record)?);
+
+            Ok(())
+        })?;
+
+        // Instructions are visited one code section after another, so each section's
+        // instructions follow on from the last one's
+        let section_sizes: Vec<(usize, usize)> = if let Some(ksm) = file.ksm() {
+            ksm.code_sections()
+                .map(|code_section| code_section.instructions().len())
+                .enumerate()
+                .collect()
+        } else if let Some(kofile) = file.ko() {
+            kofile
+                .func_sections()
+                .map(|func_section| {
+                    (
+                        usize::from(u16::from(func_section.section_index())),
+                        func_section.instructions().count(),
+                    )
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let mut sections = Vec::new();
+        let mut start = 0;
+
+        for (index, size) in section_sizes.into_iter().filter(|(_, size)| *size > 0) {
+            let end = (start + size).min(instructions.len());
+            let name = records[instructions[start]]["section"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+
+            sections.push(SectionSpan {
+                index,
+                name,
+                instructions: start..end,
+            });
+
+            start = end;
+        }
+
+        Ok(OpenFile {
+            path: path.to_path_buf(),
+            assume: assume.map(String::from),
+            format,
+            records,
+            instructions,
+            sections,
+        })
+    }
+
+    /// The instructions at the given positions in the instruction list
+    fn instructions(&self, positions: Range<usize>) -> impl Iterator<Item = &Value> {
+        self.instructions[positions]
+            .iter()
+            .map(|position| &self.records[*position])
+    }
+
+    fn all_instructions(&self) -> impl Iterator<Item = &Value> {
+        self.instructions(0..self.instructions.len())
+    }
+
+    fn section(&self, index: u64) -> Option<&SectionSpan> {
+        self.sections
+            .iter()
+            .find(|section| section.index as u64 == index)
+    }
+
+    /// Each code section, with how many instructions it has and the addresses they span
+    fn sections(&self) -> Vec<Value> {
+        self.sections
+            .iter()
+            .map(|section| {
+                let addr = |instr: Option<&Value>| {
+                    instr.map_or(Value::Null, |instr| instr["addr"].clone())
+                };
+
+                json!({
+                    "index": section.index,
+                    "name": section.name,
+                    "instructions": section.instructions.len(),
+                    "start": addr(self.instructions(section.instructions.clone()).next()),
+                    "end": addr(self.instructions(section.instructions.clone()).last()),
+                })
+            })
+            .collect()
+    }
+}
+
+/// A place in the file that was marked to come back to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Bookmark {
+    addr: u64,
+    note: String,
+}
+
+/// Where someone was in a file, which can be saved to a session file and restored later
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Position {
+    cursor: Option<u64>,
+    bookmarks: Vec<Bookmark>,
+    search_history: Vec<String>,
+}
+
+/// Everything written to a session file
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionFile {
+    format_version: u32,
+    file: PathBuf,
+    assume: Option<String>,
+    #[serde(flatten)]
+    position: Position,
+}
+
+/// The state kept between requests
+#[derive(Default)]
+struct Session {
+    file: Option<OpenFile>,
+    position: Position,
+    shutting_down: bool,
+}
+
+impl Session {
+    fn file(&self) -> Result<&OpenFile, RpcError> {
+        self.file
+            .as_ref()
+            .ok_or_else(|| RpcError::new(SERVER_ERROR, "No file is open, call open first"))
+    }
+
+    /// Opens a file, which starts over with no cursor or bookmarks, but keeps the search history
+    fn open(&mut self, path: &Path, assume: Option<&str>) -> Result<Value, RpcError> {
+        let file = OpenFile::open(path, assume)?;
+        let result = json!({
+            "format": file.format,
+            "format_version": model::FORMAT_VERSION,
+            "sections": file.sections().len(),
+            "instructions": file.instructions.len(),
+        });
+
+        self.file = Some(file);
+        self.position.cursor = None;
+        self.position.bookmarks.clear();
+
+        Ok(result)
+    }
+
+    fn handle(&mut self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        match method {
+            "open" => {
+                let path = string_param(params, "path")?
+                    .ok_or_else(|| RpcError::new(INVALID_PARAMS, "open needs a path"))?;
+                let assume = string_param(params, "assume")?;
+
+                self.open(Path::new(path), assume)
+            }
+            "sections" => Ok(Value::from(self.file()?.sections())),
+            "disassemble" => {
+                let file = self.file()?;
+                let start = number_param(params, "start")?.unwrap_or(0);
+                let end = number_param(params, "end")?.unwrap_or(u64::MAX);
+
+                let instructions: Box<dyn Iterator<Item = &Value>> =
+                    match number_param(params, "section")? {
+                        Some(index) => {
+                            let section = file.section(index).ok_or_else(|| {
+                                RpcError::new(
+                                    INVALID_PARAMS,
+                                    format!("There is no code section with index {}", index),
+                                )
+                            })?;
+
+                            Box::new(file.instructions(section.instructions.clone()))
+                        }
+                        None => Box::new(file.all_instructions()),
+                    };
+
+                let instructions = instructions
+                    .filter(|instr| {
+                        let addr = instr["addr"].as_u64().unwrap_or(0);
+                        start <= addr && addr < end
+                    })
+                    .cloned()
+                    .collect::<Vec<_>>();
+
+                Ok(Value::from(instructions))
+            }
+            "search" => {
+                let file = self.file()?;
+                let query = string_param(params, "query")?
+                    .ok_or_else(|| RpcError::new(INVALID_PARAMS, "search needs a query"))?;
+                let limit = number_param(params, "limit")?.unwrap_or(u64::MAX) as usize;
+
+                let lowercase_query = query.to_lowercase();
+                let matches = file
+                    .records
+                    .iter()
+                    .filter(|record| {
+                        record["kind"] != "file" && contains_text(record, &lowercase_query)
+                    })
+                    .take(limit)
+                    .cloned()
+                    .collect::<Vec<_>>();
+
+                self.position.search_history.push(query.to_string());
+
+                Ok(Value::from(matches))
+            }
+            "set_cursor" => {
+                self.file()?;
+                self.position.cursor = number_param(params, "addr")?;
+                Ok(Value::Null)
+            }
+            "bookmark" => {
+                self.file()?;
+                let addr = number_param(params, "addr")?
+                    .ok_or_else(|| RpcError::new(INVALID_PARAMS, "bookmark needs an addr"))?;
+                let note = string_param(params, "note")?.unwrap_or_default();
+
+                self.position
+                    .bookmarks
+                    .retain(|bookmark| bookmark.addr != addr);
+                self.position.bookmarks.push(Bookmark {
+                    addr,
+                    note: note.to_string(),
+                });
+                self.position
+                    .bookmarks
+                    .sort_by_key(|bookmark| bookmark.addr);
+
+                Ok(Value::Null)
+            }
+            "remove_bookmark" => {
+                let addr = number_param(params, "addr")?.ok_or_else(|| {
+                    RpcError::new(INVALID_PARAMS, "remove_bookmark needs an addr")
+                })?;
+
+                self.position
+                    .bookmarks
+                    .retain(|bookmark| bookmark.addr != addr);
+
+                Ok(Value::Null)
+            }
+            "position" => Ok(json!(self.position)),
+            "save_session" => {
+                let path = string_param(params, "path")?
+                    .ok_or_else(|| RpcError::new(INVALID_PARAMS, "save_session needs a path"))?;
+                let file = self.file()?;
+
+                let session_file = SessionFile {
+                    format_version: SESSION_FORMAT_VERSION,
+                    file: file.path.clone(),
+                    assume: file.assume.clone(),
+                    position: self.position.clone(),
+                };
+
+                save_session(Path::new(path), &session_file)?;
+
+                Ok(Value::Null)
+            }
+            "restore_session" => {
+                let path = string_param(params, "path")?
+                    .ok_or_else(|| RpcError::new(INVALID_PARAMS, "restore_session needs a path"))?;
+
+                let session_file = load_session(Path::new(path))?;
+                let mut result = self.open(&session_file.file, session_file.assume.as_deref())?;
+
+                self.position = session_file.position;
+                result["position"] = json!(self.position);
+
+                Ok(result)
+            }
+            "close" => {
+                self.file = None;
+                self.position = Position::default();
+                Ok(Value::Null)
+            }
+            "shutdown" => {
+                self.shutting_down = true;
+                Ok(Value::Null)
+            }
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("There is no method called {}", method),
+            )),
+        }
+    }
+}
+
+fn save_session(path: &Path, session_file: &SessionFile) -> RpcResult<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+
+    serde_json::to_writer_pretty(&mut out, session_file)?;
+    writeln!(out)?;
+    out.flush()?;
+
+    Ok(())
+}
+
+fn load_session(path: &Path) -> RpcResult<SessionFile> {
+    let session_file: SessionFile = serde_json::from_slice(&fs::read(path)?)?;
+
+    if session_file.format_version != SESSION_FORMAT_VERSION {
+        return Err(format!(
+            "The session was saved with format version {}, but this version of kDump reads version {}",
+            session_file.format_version,
+            SESSION_FORMAT_VERSION
+        )
+        .into());
+    }
+
+    Ok(session_file)
+}
+
+fn string_param<'a>(params: &'a Value, name: &str) -> Result<Option<&'a str>, RpcError> {
+    match params.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s)),
+        Some(_) => Err(RpcError::new(
+            INVALID_PARAMS,
+            format!("{} must be a string", name),
+        )),
+    }
+}
+
+fn number_param(params: &Value, name: &str) -> Result<Option<u64>, RpcError> {
+    match params.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => value.as_u64().map(Some).ok_or_else(|| {
+            RpcError::new(
+                INVALID_PARAMS,
+                format!("{} must be a non-negative integer", name),
+            )
+        }),
+    }
+}
+
+/// Whether any string or number in a record contains the lowercase query
+fn contains_text(value: &Value, query: &str) -> bool {
+    match value {
+        Value::String(s) => s.to_lowercase().contains(query),
+        Value::Number(n) => n.to_string().contains(query),
+        Value::Array(values) => values.iter().any(|value| contains_text(value, query)),
+        Value::Object(fields) => fields
+            .iter()
+            .any(|(key, value)| key != "kind" && contains_text(value, query)),
+        Value::Null | Value::Bool(_) => false,
+    }
+}
+
+/// Carries out one line of input, giving the response to send back, if there is one
+fn respond(session: &mut Session, line: &str) -> Option<Value> {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => {
+            return Some(error_response(
+                Value::Null,
+                RpcError::new(PARSE_ERROR, e.to_string()),
+            ))
+        }
+    };
+
+    // Requests without an id are notifications, which never get a response
+    let id = request.get("id").cloned();
+
+    let result = match request.get("method").and_then(|method| method.as_str()) {
+        Some(method) => session.handle(method, request.get("params").unwrap_or(&Value::Null)),
+        None => Err(RpcError::new(INVALID_REQUEST, "The request has no method")),
+    };
+
+    let id = id?;
+
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => error_response(id, error),
+    })
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": error.code, "message": error.message },
+    })
+}
+
+/// Answers requests until the input ends or a shutdown request comes in
+///
+/// The methods are open (path, and optionally assume), sections, disassemble (optionally the index
+/// of a section, and a start and end address), search (query, and optionally limit), close, and shutdown.
+/// Instructions and search results are given as the same records that --ndjson writes.
+///
+/// A client's place in the file is kept with set_cursor (addr), bookmark (addr, and optionally
+/// note), remove_bookmark (addr), and position, and along with the searches made, can be written
+/// to a session file with save_session (path) and picked back up with restore_session (path).
+pub fn serve(input: impl BufRead, output: &mut dyn Write) -> RpcResult<()> {
+    let mut session = Session::default();
+
+    for line in input.lines() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(response) = respond(&mut session, &line) {
+            serde_json::to_writer(&mut *output, &response)?;
+            writeln!(output)?;
+            output.flush()?;
+        }
+
+        if session.shutting_down {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kerbalobjects::ksm::KSMFile;
+    use kerbalobjects::{KOSValue, Opcode};
+
+    use crate::testing::{ksm_with, written, TempFile, TestInstr};
+
+    /// Two functions that kOS named the same, one after another, each of which returns
+    fn same_named_functions() -> KSMFile {
+        let first: [TestInstr; 2] = [
+            (Opcode::Lbrt, &[KOSValue::String(String::from("f`1"))]),
+            (Opcode::Ret, &[KOSValue::Int16(0)]),
+        ];
+        let second: [TestInstr; 2] = [
+            (Opcode::Lbrt, &[KOSValue::String(String::from("f`2"))]),
+            (Opcode::Ret, &[KOSValue::Int16(0)]),
+        ];
+
+        ksm_with(&[], &[&first, &second], &[])
+    }
+
+    fn call(session: &mut Session, method: &str, params: Value) -> Value {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let response = respond(session, &request.to_string()).unwrap();
+
+        response["result"].clone()
+    }
+
+    #[test]
+    fn keeps_same_named_sections_apart() {
+        let temp = TempFile::new("functions.ksm", &written(&same_named_functions()));
+
+        let mut session = Session::default();
+        let opened = call(&mut session, "open", json!({ "path": temp.path() }));
+
+        assert_eq!(opened["sections"], 2);
+
+        let sections = call(&mut session, "sections", Value::Null);
+        assert_eq!(sections[0]["name"], "f");
+        assert_eq!(sections[1]["name"], "f");
+        assert_eq!(sections[0]["index"], 0);
+        assert_eq!(sections[1]["index"], 1);
+        assert_eq!(sections[0]["instructions"], 2);
+        assert_eq!(sections[1]["instructions"], 2);
+
+        let second = call(&mut session, "disassemble", json!({ "section": 1 }));
+        assert_eq!(second.as_array().map(Vec::len), Some(2));
+        assert_eq!(second[0]["addr"], sections[1]["start"]);
+    }
+}
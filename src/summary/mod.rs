@@ -0,0 +1,266 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use termcolor::{NoColor, WriteColor};
+
+use crate::analysis;
+use crate::index::IndexedKsm;
+use crate::{output, pipeline, CLIConfig, ParsedFile};
+
+type SummaryResult<T> = Result<T, Box<dyn Error>>;
+
+const COLUMNS: [&str; 10] = [
+    "path",
+    "type",
+    "file_size",
+    "payload_size",
+    "code_sections",
+    "instructions",
+    "arguments",
+    "symbols",
+    "compiler",
+    "verification",
+];
+
+/// Finds every KSM and KO file in a directory and all of the directories inside of it, by extension
+pub(crate) fn find_files(dir: &Path, files: &mut Vec<PathBuf>) -> SummaryResult<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            find_files(&path, files)?;
+        } else if path.extension().is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("ksm") || extension.eq_ignore_ascii_case("ko")
+        }) {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// The verification column: whether every instruction has a known opcode, and every code section
+/// takes up as many bytes as the operands of its opcodes need, the same as --verify checks
+fn verification_status(num_mismatches: usize) -> String {
+    if num_mismatches == 0 {
+        String::from("ok")
+    } else {
+        format!("{} operand mismatches", num_mismatches)
+    }
+}
+
+/// The sizes, counts, and compiler of one file
+struct FileSummary {
+    file_type: &'static str,
+    /// The version in a KO file's header. KSM files don't have one.
+    version: Option<u8>,
+    file_size: usize,
+    /// The size of the file once it is decompressed
+    payload_size: usize,
+    code_sections: usize,
+    instructions: usize,
+    arguments: Option<usize>,
+    symbols: Option<usize>,
+    compiler: Option<String>,
+    num_mismatches: usize,
+}
+
+impl FileSummary {
+    /// Every CSV column after the path
+    fn csv_columns(&self) -> Vec<String> {
+        let optional = |count: Option<usize>| count.map(|n| n.to_string()).unwrap_or_default();
+
+        vec![
+            String::from(self.file_type),
+            self.file_size.to_string(),
+            self.payload_size.to_string(),
+            self.code_sections.to_string(),
+            self.instructions.to_string(),
+            optional(self.arguments),
+            optional(self.symbols),
+            self.compiler.clone().unwrap_or_default(),
+            verification_status(self.num_mismatches),
+        ]
+    }
+
+    /// The fields of a --summary line after the path
+    fn summary_fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("type", String::from(self.file_type)),
+            (
+                "version",
+                self.version.map(|v| v.to_string()).unwrap_or_default(),
+            ),
+            ("sections", self.code_sections.to_string()),
+            ("instructions", self.instructions.to_string()),
+            ("size", self.file_size.to_string()),
+            ("uncompressed_size", self.payload_size.to_string()),
+            ("compiler", self.compiler.clone().unwrap_or_default()),
+        ]
+    }
+}
+
+/// Works out the summary of a file that was parsed
+fn summarize(file: &ParsedFile) -> SummaryResult<FileSummary> {
+    let file_size = file.raw_contents().len();
+
+    if let Some(ksm) = file.ksm() {
+        let index = IndexedKsm::new(ksm);
+        let payload = crate::fio::ksm_payload(file.raw_contents())?;
+
+        let checks = analysis::check_ksm_sections(&index, Some(&payload));
+
+        let attribution = analysis::attribute_ksm(&index);
+
+        Ok(FileSummary {
+            file_type: "ksm",
+            version: None,
+            file_size,
+            payload_size: payload.len(),
+            code_sections: ksm.code_sections().count(),
+            instructions: checks.iter().map(|check| check.decoded_count).sum(),
+            arguments: Some(ksm.arg_section.arguments().count()),
+            symbols: None,
+            compiler: attribution.toolchain,
+            num_mismatches: checks.iter().map(|check| check.num_mismatches()).sum(),
+        })
+    } else if let Some(kofile) = file.ko() {
+        let checks = analysis::check_ko_sections(kofile);
+
+        let num_symbols = kofile
+            .sym_tab_by_name(".symtab")
+            .map(|symtab| symtab.symbols().count())
+            .unwrap_or(0);
+
+        Ok(FileSummary {
+            file_type: "ko",
+            version: Some(kofile.header().version),
+            file_size,
+            payload_size: file_size,
+            code_sections: kofile.func_sections().count(),
+            instructions: checks.iter().map(|check| check.decoded_count).sum(),
+            arguments: None,
+            symbols: Some(num_symbols),
+            compiler: None,
+            num_mismatches: checks.iter().map(|check| check.num_mismatches()).sum(),
+        })
+    } else {
+        Err("File type not recognized.".into())
+    }
+}
+
+/// Writes one CSV row for the file at the given path, which still gets a row if it can't be read
+fn write_row(out: &mut dyn WriteColor, path: &Path, config: &CLIConfig) -> SummaryResult<()> {
+    let path_str = path.display().to_string();
+
+    let row = match pipeline::load_file(path, config.assume).and_then(|file| summarize(&file)) {
+        Ok(summary) => summary.csv_columns(),
+        Err(e) => {
+            let mut columns = vec![String::new(); COLUMNS.len() - 2];
+            columns[0] = String::from("unknown");
+            columns.push(format!("unreadable: {}", e));
+            columns
+        }
+    };
+
+    let fields: Vec<&str> = std::iter::once(path_str.as_str())
+        .chain(row.iter().map(|column| column.as_str()))
+        .collect();
+
+    output::write_csv_row(out, &fields)
+}
+
+/// Quotes a summary line value if it is empty or has anything in it that would split the line up
+fn summary_value(value: &str) -> String {
+    if value.is_empty() || value.contains([' ', '"', '=', '\t']) {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        String::from(value)
+    }
+}
+
+/// Writes one line of key=value fields for the file at the given path
+fn write_summary_line(
+    stream: &mut dyn WriteColor,
+    path: &Path,
+    config: &CLIConfig,
+) -> SummaryResult<()> {
+    let path_str = path.display().to_string();
+    let mut fields = vec![("path", path_str)];
+
+    match pipeline::load_file(path, config.assume).and_then(|file| summarize(&file)) {
+        Ok(summary) => fields.extend(summary.summary_fields()),
+        Err(e) => fields.push(("error", e.to_string())),
+    }
+
+    let line: Vec<String> = fields
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, summary_value(value)))
+        .collect();
+
+    writeln!(stream, "{}", line.join(" "))?;
+
+    Ok(())
+}
+
+/// The input file, or every KSM and KO file under the input directory
+fn input_files(config: &CLIConfig) -> SummaryResult<Vec<PathBuf>> {
+    let input = config
+        .file_path
+        .as_ref()
+        .ok_or("No input file was provided")?;
+
+    let mut files = Vec::new();
+
+    if input.is_dir() {
+        find_files(input, &mut files)?;
+    } else {
+        files.push(input.clone());
+    }
+
+    Ok(files)
+}
+
+/// Prints one line of key=value fields for the input file, or for every KSM and KO file under the
+/// input directory, with the type, version, counts, sizes, and compiler of each
+pub fn write_summary_lines(stream: &mut dyn WriteColor, config: &CLIConfig) -> SummaryResult<()> {
+    for path in &input_files(config)? {
+        write_summary_line(stream, path, config)?;
+    }
+
+    Ok(())
+}
+
+/// Writes a CSV file with one row for the input file, or for every KSM and KO file under the
+/// input directory, with the sizes, counts, compiler, and verification status of each
+pub fn write_csv_summary(
+    stream: &mut dyn WriteColor,
+    config: &CLIConfig,
+    csv_path: &Path,
+) -> SummaryResult<()> {
+    let files = input_files(config)?;
+
+    let mut out = NoColor::new(BufWriter::new(File::create(csv_path)?));
+
+    output::write_csv_row(&mut out, &COLUMNS)?;
+
+    for path in &files {
+        write_row(&mut out, path, config)?;
+    }
+
+    out.flush()?;
+
+    writeln!(
+        stream,
+        "Summarized {} file(s) into {}",
+        files.len(),
+        csv_path.display()
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use kerbalobjects::ko::symbols::SymType;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::index::IndexedKsm;
+use crate::{CLIConfig, ParsedFile};
+
+/// Something that can be jumped to, and the kind of tag it is given
+struct TagTarget {
+    name: String,
+    /// f for functions and v for data, which is what editors expect from C tags files
+    kind: char,
+}
+
+/// Lists the KSM function sections or KO symbols that get a tag
+fn tag_targets(file: &ParsedFile) -> Result<Vec<TagTarget>, Box<dyn Error>> {
+    let mut targets = Vec::new();
+
+    if let Some(ksm) = file.ksm() {
+        let index = IndexedKsm::new(ksm);
+
+        for code_section in ksm.code_sections() {
+            targets.push(TagTarget {
+                name: index.section_name(code_section)?.to_string(),
+                kind: 'f',
+            });
+        }
+    } else if let Some(kofile) = file.ko() {
+        let symtab = kofile.sym_tab_by_name(".symtab");
+        let symstrtab = kofile.str_tab_by_name(".symstrtab");
+
+        if let (Some(symtab), Some(symstrtab)) = (symtab, symstrtab) {
+            for symbol in symtab.symbols() {
+                let kind = match symbol.sym_type {
+                    SymType::Func => 'f',
+                    SymType::Object | SymType::NoType => 'v',
+                    SymType::Section | SymType::File => continue,
+                };
+
+                if let Some(name) = symstrtab.get(symbol.name_idx) {
+                    if !name.is_empty() {
+                        targets.push(TagTarget {
+                            name: name.clone(),
+                            kind,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(targets)
+}
+
+/// Finds the line of the dump that introduces a name: a line that starts with it, like a
+/// section header or symbol table row, or that names it in angle brackets, like a KO function
+/// section header
+fn find_line(dump: &str, name: &str) -> Option<usize> {
+    let bracketed = format!("<{}>", name);
+
+    dump.lines()
+        .position(|line| {
+            let first_word = line.split_whitespace().next().unwrap_or("");
+
+            first_word.trim_end_matches(':') == name || line.contains(&bracketed)
+        })
+        .map(|position| position + 1)
+}
+
+/// Writes the dump to the file given to -o, and a tags file that points into it at the line
+/// each function or symbol is shown at
+pub fn write_tags(config: &CLIConfig, tags_path: &Path) -> Result<(), Box<dyn Error>> {
+    let dump_path = config
+        .output
+        .as_ref()
+        .ok_or("--ctags needs the dump to be written to a file, given with -o")?;
+    let file_path = config
+        .file_path
+        .as_ref()
+        .ok_or("No input file was provided")?;
+
+    let mut dump = Vec::new();
+    crate::run_to(&mut dump, config)?;
+    fs::write(dump_path, &dump)?;
+
+    let dump = String::from_utf8_lossy(&dump);
+    let file = crate::load_file(file_path, config.assume)?;
+
+    let mut lines: Vec<String> = tag_targets(&file)?
+        .into_iter()
+        .filter_map(|target| {
+            find_line(&dump, &target.name).map(|line| {
+                format!(
+                    "{}\t{}\t{};\"\t{}",
+                    target.name,
+                    dump_path.display(),
+                    line,
+                    target.kind
+                )
+            })
+        })
+        .collect();
+
+    // Editors binary search the tags file, so it has to be sorted by name
+    lines.sort();
+    lines.dedup();
+
+    let mut tags = String::from("!_TAG_FILE_FORMAT\t2\t/extended format/\n");
+    tags.push_str("!_TAG_FILE_SORTED\t1\t/0=unsorted, 1=sorted/\n");
+
+    for line in lines {
+        tags.push_str(&line);
+        tags.push('\n');
+    }
+
+    fs::write(tags_path, tags)?;
+
+    Ok(())
+}
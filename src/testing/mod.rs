@@ -0,0 +1,148 @@
+//! Fixtures shared by the tests of every module
+
+use kerbalobjects::ksm::sections::{
+    ArgumentSection, CodeSection, CodeType, DebugEntry, DebugRange, DebugSection,
+};
+use kerbalobjects::ksm::{Instr, KSMFile};
+use kerbalobjects::{KOSValue, Opcode};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// One instruction, as its opcode and the values of its operands
+pub type TestInstr<'a> = (Opcode, &'a [KOSValue]);
+
+/// Every code section begins with a two byte marker, like %F
+const CODE_MARKER_SIZE: usize = 2;
+
+/// A KSM file with nothing but the given main section, all of which compiled from line 1
+pub fn ksm_from(main: &[TestInstr]) -> KSMFile {
+    ksm_with(&[], &[], &[main])
+}
+
+/// A KSM file laid out the way the compiler lays one out: the function sections, an empty init
+/// section, then the main section
+///
+/// Every function section, and every group of main instructions, compiled from its own source
+/// line, counting up from line 1. Operand values are added to the argument section as they are
+/// first used, after the given arguments, which lets a test choose how the section is laid out.
+pub fn ksm_with(
+    arguments: &[KOSValue],
+    functions: &[&[TestInstr]],
+    main_lines: &[&[TestInstr]],
+) -> KSMFile {
+    let mut arg_section = ArgumentSection::new();
+
+    for value in arguments {
+        arg_section.add(value.clone());
+    }
+
+    // A file always has a function section, even when it is empty
+    let function_lines: Vec<[&[TestInstr]; 1]> = functions.iter().map(|f| [*f]).collect();
+    let mut layout: Vec<(CodeType, &[&[TestInstr]])> = function_lines
+        .iter()
+        .map(|lines| (CodeType::Function, &lines[..]))
+        .collect();
+
+    if functions.is_empty() {
+        layout.push((CodeType::Function, &[]));
+    }
+
+    layout.push((CodeType::Initialization, &[]));
+    layout.push((CodeType::Main, main_lines));
+
+    let mut code_sections = Vec::new();
+
+    for (section_type, lines) in layout.iter().copied() {
+        let mut code_section = CodeSection::new(section_type);
+
+        for (opcode, values) in lines.iter().copied().flatten() {
+            let mut ops = values
+                .iter()
+                .map(|value| arg_section.add_checked(value.clone()));
+
+            code_section.add(match (ops.next(), ops.next()) {
+                (None, _) => Instr::ZeroOp(*opcode),
+                (Some(op), None) => Instr::OneOp(*opcode, op),
+                (Some(first), Some(second)) => Instr::TwoOp(*opcode, first, second),
+            });
+        }
+
+        code_sections.push(code_section);
+    }
+
+    // Each line covers its instructions, which are laid out after the marker of their section
+    let index_width = arg_section.num_index_bytes() as usize;
+    let mut addr = 0;
+    let mut ranges = Vec::new();
+
+    for (_, lines) in layout {
+        addr += CODE_MARKER_SIZE;
+
+        for line in lines {
+            let line_size: usize = line
+                .iter()
+                .map(|(_, values)| 1 + values.len() * index_width)
+                .sum();
+
+            if line_size > 0 {
+                ranges.push(DebugRange::new(addr, addr + line_size - 1));
+            }
+
+            addr += line_size;
+        }
+    }
+
+    let mut entries = ranges
+        .into_iter()
+        .zip(1..)
+        .map(|(range, line_number)| DebugEntry::new(line_number).with_range(range));
+    let mut debug_section = DebugSection::new(
+        entries
+            .next()
+            .expect("a test file needs at least one instruction"),
+    );
+
+    entries.for_each(|entry| debug_section.add(entry));
+
+    KSMFile::new_from_parts(arg_section, code_sections, debug_section)
+}
+
+/// The bytes a KSM file is written as
+pub fn written(ksmfile: &KSMFile) -> Vec<u8> {
+    let mut contents = Vec::new();
+    ksmfile.write(&mut contents);
+
+    contents
+}
+
+/// A file in the temporary directory, which is removed again when this is dropped
+pub struct TempFile(PathBuf);
+
+impl TempFile {
+    /// Writes the contents to a new file, whose name ends with the given one
+    pub fn new(name: &str, contents: &[u8]) -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+        let path = std::env::temp_dir().join(format!(
+            "kdump-{}-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed),
+            name
+        ));
+
+        fs::write(&path, contents).unwrap();
+
+        TempFile(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
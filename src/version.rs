@@ -0,0 +1,42 @@
+use serde::Serialize;
+use std::error::Error;
+use termcolor::WriteColor;
+
+/// The version of kerbalobjects that kDump is built against, which has to be kept in step with Cargo.toml
+pub const KERBALOBJECTS_VERSION: &str = "4.0.2";
+
+/// The KO file format versions that kerbalobjects can read
+pub const KO_FILE_VERSIONS: &[u8] = &[4];
+
+/// The argument index widths that a KSM file can use, in bytes
+pub const KSM_INDEX_WIDTHS: &[u8] = &[1, 2, 3, 4];
+
+/// Everything an installer needs to check that this build can read the files it will be given
+#[derive(Debug, Serialize)]
+struct VersionInfo {
+    format_version: u32,
+    kdump: &'static str,
+    kerbalobjects: &'static str,
+    ko_file_versions: &'static [u8],
+    ksm_index_widths: &'static [u8],
+    ksm_compressed: bool,
+    ksm_uncompressed: bool,
+}
+
+/// Writes the version and supported file formats as a JSON document
+pub fn write_version_json(stream: &mut dyn WriteColor) -> Result<(), Box<dyn Error>> {
+    let info = VersionInfo {
+        format_version: crate::model::FORMAT_VERSION,
+        kdump: crate::VERSION,
+        kerbalobjects: KERBALOBJECTS_VERSION,
+        ko_file_versions: KO_FILE_VERSIONS,
+        ksm_index_widths: KSM_INDEX_WIDTHS,
+        ksm_compressed: true,
+        ksm_uncompressed: true,
+    };
+
+    serde_json::to_writer_pretty(&mut *stream, &info)?;
+    writeln!(stream)?;
+
+    Ok(())
+}